@@ -0,0 +1,327 @@
+//! # CONcurrent DOWnloads from AWS S3 via the AWS SDK
+//!
+//! Download speed from S3 can be significantly improved by
+//! downloading parts of the file concurrently. This crate
+//! does exactly that using the official [aws_sdk_s3] crate.
+//!
+//! ```rust, noexec
+//!
+//! use condow_aws_sdk::*;
+//! use condow_aws_sdk::config::Config;
+//!
+//! # async {
+//! let sdk_config = aws_sdk_s3::Config::builder()
+//!     .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
+//!     .region(aws_sdk_s3::config::Region::new("eu-central-1"))
+//!     .build();
+//! let client = S3ClientWrapper::from_client(aws_sdk_s3::Client::from_conf(sdk_config));
+//! let condow = client.condow(Config::default()).unwrap();
+//!
+//! let location = Bucket::new("my_bucket").object("my_object");
+//!
+//! let stream = condow.download(location, 23..46).await.unwrap();
+//! let downloaded_bytes: Vec<u8> = stream.into_vec().await.unwrap();
+//! # };
+//! # ()
+//! ```
+//!
+//! Use [S3ClientWrapper::with_endpoint] for S3 compatible services like MinIO.
+//!
+//! [S3RequestOptions] like SSE-C keys or requester pays can be set on the
+//! [S3ClientWrapper] and overridden per [S3Location].
+//!
+//! The SDK retries failed requests itself. Consider disabling retries
+//! either in the SDK or in condow.
+use std::error::Error as StdError;
+
+use anyhow::Error as AnyError;
+use aws_sdk_s3::{
+    config::{http::HttpResponse, BehaviorVersion, Credentials, Region},
+    error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
+    operation::{get_object::GetObjectError, head_object::HeadObjectError},
+    primitives::{DateTime, DateTimeFormat},
+    types::RequestPayer,
+    Client,
+};
+use futures::future::BoxFuture;
+
+use condow_core::{
+    condow_client::*,
+    config::Config,
+    errors::{CondowError, IoError},
+    streams::{BytesHint, BytesStream},
+};
+
+pub use condow_core::*;
+pub use condow_s3::{Bucket, ObjectKey, S3Location, S3RequestOptions};
+
+/// Just a wrapper around a [Client](aws_sdk_s3::Client)
+/// to implement the trait [CondowClient](condow_client::CondowClient) on.
+#[derive(Clone)]
+pub struct S3ClientWrapper {
+    client: Client,
+    options: S3RequestOptions,
+}
+
+impl S3ClientWrapper {
+    /// Create a new wrapper wrapping the given [Client](aws_sdk_s3::Client)
+    pub fn from_client(client: Client) -> Self {
+        Self {
+            client,
+            options: S3RequestOptions::default(),
+        }
+    }
+
+    /// Create a new wrapper for an S3 compatible service like MinIO
+    ///
+    /// Objects are addressed path style (`{endpoint}/{bucket}/{key}`).
+    pub fn with_endpoint<E, R, K, S>(
+        endpoint_url: E,
+        region: R,
+        access_key_id: K,
+        secret_access_key: S,
+    ) -> Self
+    where
+        E: Into<String>,
+        R: Into<String>,
+        K: Into<String>,
+        S: Into<String>,
+    {
+        let credentials = Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,
+            None,
+            "condow_aws_sdk",
+        );
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(endpoint_url)
+            .region(Region::new(region.into()))
+            .credentials_provider(credentials)
+            .force_path_style(true)
+            .build();
+
+        Self::from_client(Client::from_conf(config))
+    }
+
+    /// Options applied to all requests unless overridden by an [S3Location]
+    pub fn request_options(mut self, options: S3RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Create a concurrent downloader from this adapter and the given [Config]
+    pub fn condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
+    }
+
+    /// The wrapped [Client](aws_sdk_s3::Client)
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl CondowClient for S3ClientWrapper {
+    type Location = S3Location;
+
+    fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let client = self.client.clone();
+        let options = self.options.overridden_by(location.request_options());
+        let f = async move {
+            let if_unmodified_since = if_unmodified_since(&options)?;
+            let (bucket, object_key, version_id) = location.into_parts();
+
+            let response = client
+                .head_object()
+                .bucket(bucket.into_inner())
+                .key(object_key.into_inner())
+                .set_version_id(version_id)
+                .set_sse_customer_algorithm(options.sse_customer_algorithm)
+                .set_sse_customer_key(options.sse_customer_key)
+                .set_sse_customer_key_md5(options.sse_customer_key_md5)
+                .set_request_payer(options.request_payer.as_deref().map(RequestPayer::from))
+                .set_expected_bucket_owner(options.expected_bucket_owner)
+                .set_if_match(options.if_match)
+                .set_if_unmodified_since(if_unmodified_since)
+                .send()
+                .await
+                .map_err(head_obj_err_to_get_size_err)?;
+
+            match response.content_length() {
+                Some(size) if size >= 0 => Ok(size as u64),
+                Some(size) => Err(CondowError::new_other(format!(
+                    "response had a negative content length: {}",
+                    size
+                ))),
+                None => Err(CondowError::new_other("response had no content length")),
+            }
+        };
+
+        Box::pin(f)
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.client.clone();
+        let options = self.options.overridden_by(location.request_options());
+        let f = async move {
+            let if_unmodified_since = if_unmodified_since(&options)?;
+            let (bucket, object_key, version_id) = location.into_parts();
+
+            let response = client
+                .get_object()
+                .bucket(bucket.into_inner())
+                .key(object_key.into_inner())
+                .set_version_id(version_id)
+                .set_sse_customer_algorithm(options.sse_customer_algorithm)
+                .set_sse_customer_key(options.sse_customer_key)
+                .set_sse_customer_key_md5(options.sse_customer_key_md5)
+                .set_request_payer(options.request_payer.as_deref().map(RequestPayer::from))
+                .set_expected_bucket_owner(options.expected_bucket_owner)
+                .set_if_match(options.if_match)
+                .set_if_unmodified_since(if_unmodified_since)
+                .set_range(spec.http_range_value())
+                .send()
+                .await
+                .map_err(get_obj_err_to_download_err)?;
+
+            let bytes_hint = response
+                .content_length()
+                .filter(|&size| size >= 0)
+                .map(|size| BytesHint::new_exact(size as u64))
+                .unwrap_or_else(BytesHint::new_no_hint);
+
+            let stream = futures::stream::unfold(response.body, |mut body| async move {
+                match body.next().await? {
+                    Ok(bytes) => Some((Ok(bytes), body)),
+                    Err(err) => Some((Err(IoError::new(err.to_string()).with_source(err)), body)),
+                }
+            });
+
+            let stream: BytesStream = Box::pin(stream);
+
+            Ok((stream, bytes_hint))
+        };
+
+        Box::pin(f)
+    }
+}
+
+/// The SDK takes a [DateTime] while the option is an HTTP date
+fn if_unmodified_since(options: &S3RequestOptions) -> Result<Option<DateTime>, CondowError> {
+    options
+        .if_unmodified_since
+        .as_deref()
+        .map(|date| {
+            DateTime::from_str(date, DateTimeFormat::HttpDate).map_err(|err| {
+                CondowError::new_other(format!("invalid 'if_unmodified_since': {}", date))
+                    .with_source(err)
+            })
+        })
+        .transpose()
+}
+
+fn get_obj_err_to_download_err(err: SdkError<GetObjectError, HttpResponse>) -> CondowError {
+    match err.as_service_error() {
+        Some(GetObjectError::NoSuchKey(_)) => {
+            CondowError::new_not_found(DisplayErrorContext(&err).to_string()).with_source(err)
+        }
+        Some(GetObjectError::InvalidObjectState(_)) => CondowError::new_other(format!(
+            "invalid object state: {}",
+            DisplayErrorContext(&err)
+        ))
+        .with_source(err),
+        _ => sdk_err_to_condow_err(err),
+    }
+}
+
+fn head_obj_err_to_get_size_err(err: SdkError<HeadObjectError, HttpResponse>) -> CondowError {
+    match err.as_service_error() {
+        Some(HeadObjectError::NotFound(_)) => {
+            CondowError::new_not_found(DisplayErrorContext(&err).to_string()).with_source(err)
+        }
+        _ => sdk_err_to_condow_err(err),
+    }
+}
+
+/// Errors which occurred before a response was received are retryable
+/// unless the request could not even be constructed.
+fn sdk_err_to_condow_err<E>(err: SdkError<E, HttpResponse>) -> CondowError
+where
+    E: ProvideErrorMetadata + StdError + Send + Sync + 'static,
+{
+    let message = DisplayErrorContext(&err).to_string();
+    let condow_err = match err {
+        SdkError::ConstructionFailure(_) => {
+            CondowError::new_other(format!("request construction failed: {}", message))
+        }
+        SdkError::TimeoutError(_) => CondowError::new_io(format!("request timed out: {}", message)),
+        SdkError::DispatchFailure(_) => {
+            CondowError::new_io(format!("dispatch failure: {}", message))
+        }
+        SdkError::ResponseError(_) => CondowError::new_io(format!("invalid response: {}", message)),
+        SdkError::ServiceError(ref service_err) => service_err_to_condow_err(
+            service_err.raw().status().as_u16(),
+            service_err.err().code(),
+            message,
+        ),
+        _ => CondowError::new_other(message),
+    };
+
+    condow_err.with_source(err)
+}
+
+/// Throttling and server errors are retryable
+fn service_err_to_condow_err(status: u16, code: Option<&str>, message: String) -> CondowError {
+    match (status, code) {
+        (_, Some("NoSuchKey" | "NoSuchBucket" | "NoSuchVersion")) | (404, _) => {
+            CondowError::new_not_found(message)
+        }
+        (_, Some("AccessDenied" | "InvalidAccessKeyId" | "SignatureDoesNotMatch"))
+        | (401 | 403, _) => CondowError::new_access_denied(message),
+        (_, Some("InvalidRange")) | (416, _) => CondowError::new_invalid_range(message),
+        (_, Some("PreconditionFailed")) | (412, _) => {
+            CondowError::new_other(format!("precondition failed: {}", message))
+        }
+        (_, Some("SlowDown" | "Throttling" | "ThrottlingException" | "RequestLimitExceeded"))
+        | (429 | 503, _) => CondowError::new_remote(message),
+        (status, _) if (500..600).contains(&status) => CondowError::new_remote(message),
+        _ => CondowError::new_other(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use condow_core::errors::CondowErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn service_error_kinds() {
+        let expected = [
+            (404, None, CondowErrorKind::NotFound),
+            (400, Some("NoSuchBucket"), CondowErrorKind::NotFound),
+            (403, None, CondowErrorKind::AccessDenied),
+            (
+                400,
+                Some("SignatureDoesNotMatch"),
+                CondowErrorKind::AccessDenied,
+            ),
+            (416, None, CondowErrorKind::InvalidRange),
+            (503, Some("SlowDown"), CondowErrorKind::Remote),
+            (400, Some("RequestLimitExceeded"), CondowErrorKind::Remote),
+            (429, None, CondowErrorKind::Remote),
+            (500, None, CondowErrorKind::Remote),
+            (400, Some("InvalidArgument"), CondowErrorKind::Other),
+        ];
+
+        for (status, code, kind) in expected {
+            let err = service_err_to_condow_err(status, code, String::new());
+            assert_eq!(err.kind(), kind, "{} {:?}", status, code);
+        }
+    }
+}
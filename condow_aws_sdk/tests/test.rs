@@ -0,0 +1,208 @@
+use aws_sdk_s3::config::{
+    retry::RetryConfig as SdkRetryConfig, BehaviorVersion, Credentials, Region,
+};
+use condow_aws_sdk::{
+    condow_client::{CondowClient, DownloadSpec},
+    config::Config,
+    errors::CondowErrorKind,
+    Bucket, InclusiveRange, S3ClientWrapper, S3Location, S3RequestOptions,
+};
+use condow_test_server::{adapter_tests, object_server, RangeResponder, TestAdapter, DATA};
+use futures::TryStreamExt;
+use wiremock::{
+    matchers::{header, header_regex, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const OBJECT_PATH: &str = "/bucket/dir/object";
+
+fn location() -> S3Location {
+    Bucket::new("bucket").object("dir/object")
+}
+
+/// A client without retries of the SDK
+fn client(server: &MockServer) -> S3ClientWrapper {
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .endpoint_url(server.uri())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+        .force_path_style(true)
+        .retry_config(SdkRetryConfig::disabled())
+        .build();
+
+    S3ClientWrapper::from_client(aws_sdk_s3::Client::from_conf(config))
+}
+
+/// The error code S3 answers with for the given status
+fn error_code(status: u16) -> &'static str {
+    match status {
+        404 => "NoSuchKey",
+        401 | 403 => "AccessDenied",
+        416 => "InvalidRange",
+        429 | 503 => "SlowDown",
+        500 => "InternalError",
+        _ => "InvalidArgument",
+    }
+}
+
+/// A stand-in for MinIO serving a single object at [OBJECT_PATH]
+struct MinIo;
+
+impl TestAdapter for MinIo {
+    type Client = S3ClientWrapper;
+
+    fn object_mocks() -> Vec<Mock> {
+        vec![
+            Mock::given(method("HEAD"))
+                .and(path(OBJECT_PATH))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("content-length", DATA.len().to_string()),
+                ),
+            Mock::given(method("GET"))
+                .and(path(OBJECT_PATH))
+                .respond_with(RangeResponder::new()),
+        ]
+    }
+
+    /// Responses to HEAD requests have no body
+    fn error_mocks(status: u16) -> Vec<Mock> {
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <Error><Code>{}</Code><Message>message</Message></Error>",
+            error_code(status)
+        );
+
+        vec![
+            Mock::given(method("HEAD")).respond_with(ResponseTemplate::new(status)),
+            Mock::given(method("GET")).respond_with(
+                ResponseTemplate::new(status)
+                    .insert_header("content-type", "application/xml")
+                    .set_body_string(body),
+            ),
+        ]
+    }
+
+    fn client(server: &MockServer) -> S3ClientWrapper {
+        client(server)
+    }
+
+    fn location(_server: &MockServer) -> S3Location {
+        location()
+    }
+}
+
+adapter_tests!(MinIo);
+
+#[tokio::test]
+async fn version_id_is_sent() {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path(OBJECT_PATH))
+        .and(query_param("versionId", "v1"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-length", "3"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(OBJECT_PATH))
+        .and(query_param("versionId", "v1"))
+        .respond_with(ResponseTemplate::new(206).set_body_bytes(&DATA[0..3]))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let condow = client(&server).condow(Config::default()).unwrap();
+
+    let data = condow
+        .download(location().with_version_id("v1"), ..)
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, &DATA[0..3]);
+}
+
+#[tokio::test]
+async fn request_options_are_sent() {
+    let server = MockServer::start().await;
+    for verb in ["HEAD", "GET"] {
+        Mock::given(method(verb))
+            .and(path(OBJECT_PATH))
+            .and(header("x-amz-request-payer", "requester"))
+            .and(header("x-amz-expected-bucket-owner", "456"))
+            .and(header(
+                "x-amz-server-side-encryption-customer-algorithm",
+                "AES256",
+            ))
+            .and(header("x-amz-server-side-encryption-customer-key", "a2V5"))
+            .and(header(
+                "x-amz-server-side-encryption-customer-key-md5",
+                "bWQ1",
+            ))
+            .and(header("if-match", "\"etag\""))
+            .respond_with(RangeResponder::new())
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+    let client = client(&server).request_options(
+        S3RequestOptions::new()
+            .requester_pays()
+            .expected_bucket_owner("123"),
+    );
+    let location = location().with_request_options(
+        S3RequestOptions::new()
+            .sse_customer_key("AES256", "a2V5", "bWQ1")
+            .expected_bucket_owner("456")
+            .if_match("\"etag\""),
+    );
+
+    client.get_size(location.clone()).await.unwrap();
+    let (stream, _) = client
+        .download(location, DownloadSpec::Range(InclusiveRange(0, 9)))
+        .await
+        .unwrap();
+
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), &DATA[0..10]);
+}
+
+#[tokio::test]
+async fn invalid_if_unmodified_since_is_rejected() {
+    let server = object_server::<MinIo>().await;
+    let location =
+        location().with_request_options(S3RequestOptions::new().if_unmodified_since("yesterday"));
+
+    let err = client(&server).get_size(location).await.unwrap_err();
+
+    assert_eq!(err.kind(), CondowErrorKind::Other);
+}
+
+#[tokio::test]
+async fn with_endpoint_signs_path_style_requests() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(OBJECT_PATH))
+        .and(header("range", "bytes=0-9"))
+        .and(header_regex(
+            "authorization",
+            "^AWS4-HMAC-SHA256 Credential=minio_key/[0-9]{8}/eu-central-1/s3/aws4_request",
+        ))
+        .respond_with(RangeResponder::new())
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client =
+        S3ClientWrapper::with_endpoint(server.uri(), "eu-central-1", "minio_key", "minio_secret");
+
+    let (stream, _) = client
+        .download(location(), DownloadSpec::Range(InclusiveRange(0, 9)))
+        .await
+        .unwrap();
+
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), &DATA[0..10]);
+}
@@ -0,0 +1,198 @@
+//! Authorization of requests to Azure Blob Storage
+use std::fmt;
+
+use anyhow::{Context, Error as AnyError};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use reqwest::{header, Request};
+use sha2::Sha256;
+
+use condow_core::errors::CondowError;
+
+/// Credentials used to authorize requests
+#[derive(Clone, Default)]
+pub enum Credentials {
+    /// Requests are not authorized which only works for public containers
+    #[default]
+    Anonymous,
+    /// A shared access signature appended to the query of each request
+    Sas(String),
+    /// Requests are signed with the key of the storage account
+    SharedKey { account: String, key: Vec<u8> },
+}
+
+impl Credentials {
+    /// A shared access signature with or without a leading `?`
+    pub fn sas<T: Into<String>>(token: T) -> Self {
+        let token = token.into();
+        Credentials::Sas(token.trim_start_matches('?').to_string())
+    }
+
+    /// The name of a storage account and its base64 encoded key
+    pub fn shared_key<A: Into<String>, K: AsRef<str>>(
+        account: A,
+        key: K,
+    ) -> Result<Self, AnyError> {
+        let key = STANDARD
+            .decode(key.as_ref())
+            .context("the shared key is not base64 encoded")?;
+
+        Ok(Credentials::SharedKey {
+            account: account.into(),
+            key,
+        })
+    }
+
+    /// The query to append to the URL of a request
+    pub(crate) fn query(&self) -> Option<&str> {
+        match self {
+            Credentials::Sas(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Adds an `Authorization` header if requests have to be signed
+    ///
+    /// Must be called after all other headers have been set.
+    pub(crate) fn authorize(&self, request: &mut Request) -> Result<(), CondowError> {
+        let (account, key) = match self {
+            Credentials::SharedKey { account, key } => (account, key),
+            _ => return Ok(()),
+        };
+
+        let signature = sign(key, &string_to_sign(account, request));
+        let value = format!("SharedKey {}:{}", account, signature)
+            .parse()
+            .map_err(|err| {
+                CondowError::new_other("invalid authorization header").with_source(err)
+            })?;
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+
+        Ok(())
+    }
+}
+
+/// Does not print the secrets
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Anonymous => write!(f, "Anonymous"),
+            Credentials::Sas(_) => write!(f, "Sas(***)"),
+            Credentials::SharedKey { account, .. } => {
+                write!(f, "SharedKey {{ account: {:?}, key: *** }}", account)
+            }
+        }
+    }
+}
+
+/// The string to sign for the Shared Key authorization of
+/// the Blob service (version 2009-09-19 and later)
+fn string_to_sign(account: &str, request: &Request) -> String {
+    let headers = request.headers();
+    let header = |name: header::HeaderName| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    let content_length = match header(header::CONTENT_LENGTH) {
+        "0" => "",
+        content_length => content_length,
+    };
+
+    let mut ms_headers = headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-ms-"))
+        .map(|(name, value)| {
+            format!(
+                "{}:{}\n",
+                name.as_str(),
+                value.to_str().unwrap_or_default().trim()
+            )
+        })
+        .collect::<Vec<_>>();
+    ms_headers.sort();
+
+    let url = request.url();
+    let mut resource = format!("/{}{}", account, url.path());
+    let mut query = url
+        .query_pairs()
+        .map(|(name, value)| (name.to_lowercase(), value.into_owned()))
+        .collect::<Vec<_>>();
+    query.sort();
+    for (name, value) in query {
+        resource.push_str(&format!("\n{}:{}", name, value));
+    }
+
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}{}",
+        request.method(),
+        header(header::CONTENT_ENCODING),
+        header(header::CONTENT_LANGUAGE),
+        content_length,
+        header(header::HeaderName::from_static("content-md5")),
+        header(header::CONTENT_TYPE),
+        header(header::DATE),
+        header(header::IF_MODIFIED_SINCE),
+        header(header::IF_MATCH),
+        header(header::IF_NONE_MATCH),
+        header(header::IF_UNMODIFIED_SINCE),
+        header(header::RANGE),
+        ms_headers.concat(),
+        resource
+    )
+}
+
+fn sign(key: &[u8], string_to_sign: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(string_to_sign.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, Method};
+
+    use super::*;
+
+    #[test]
+    fn shared_key_signature() {
+        let credentials = Credentials::shared_key("account", "c2VjcmV0LWtleQ==").unwrap();
+        let mut request = Client::new()
+            .request(
+                Method::GET,
+                "https://account.blob.core.windows.net/container/dir/blob",
+            )
+            .header("x-ms-version", "2021-08-06")
+            .header("x-ms-date", "Mon, 01 Jan 2024 00:00:00 GMT")
+            .header("x-ms-range", "bytes=0-9")
+            .build()
+            .unwrap();
+
+        credentials.authorize(&mut request).unwrap();
+
+        assert_eq!(
+            request.headers()[header::AUTHORIZATION],
+            "SharedKey account:RIJY6tQ/MWZlaRpi0rxz4wjBB2JiEPkVRpUxGD5O20M="
+        );
+    }
+
+    #[test]
+    fn sas_without_question_mark() {
+        assert_eq!(
+            Credentials::sas("?sv=1&sig=abc").query(),
+            Some("sv=1&sig=abc")
+        );
+    }
+
+    #[test]
+    fn debug_does_not_print_secrets() {
+        let credentials = Credentials::shared_key("account", "c2VjcmV0LWtleQ==").unwrap();
+        assert_eq!(
+            format!("{:?}", credentials),
+            r#"SharedKey { account: "account", key: *** }"#
+        );
+        assert_eq!(format!("{:?}", Credentials::sas("sig=abc")), "Sas(***)");
+    }
+}
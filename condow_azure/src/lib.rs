@@ -0,0 +1,410 @@
+//! # CONcurrent DOWnloads from Azure Blob Storage
+//!
+//! Download speed from Azure Blob Storage can be significantly improved by
+//! downloading parts of the file concurrently. This crate
+//! does exactly that.
+//!
+//! ```rust, noexec
+//!
+//! use condow_azure::*;
+//! use condow_azure::config::Config;
+//!
+//! # async {
+//! let credentials = Credentials::sas("sv=2021-08-06&sig=...");
+//! let client = AzureClient::new().credentials(credentials);
+//! let condow = client.condow(Config::default()).unwrap();
+//!
+//! let location = AzureLocation::new("my_account", "my_container", "my_blob");
+//!
+//! let stream = condow.download(location, 23..46).await.unwrap();
+//! let downloaded_bytes: Vec<u8> = stream.into_vec().await.unwrap();
+//! # };
+//! # ()
+//! ```
+//!
+//! Use [AzureClient::endpoint] to download from e.g. a local Azurite.
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::Error as AnyError;
+use futures::future::BoxFuture;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{header, Method, Response};
+
+use condow_core::{
+    condow_client::*,
+    config::Config,
+    errors::CondowError,
+    streams::{BytesHint, BytesStream},
+};
+use condow_http::{
+    http_err_to_condow_err, response_text, response_to_download, status_to_condow_err,
+};
+
+pub use condow_core::*;
+pub use credentials::Credentials;
+
+mod credentials;
+
+/// The version of the Blob service REST API requests are made with
+pub const API_VERSION: &str = "2021-08-06";
+
+/// Everything except unreserved characters and `/` is encoded
+const BLOB_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Azure storage account name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Account(String);
+
+impl Account {
+    pub fn new<T: Into<String>>(account: T) -> Self {
+        Self(account.into())
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for Account {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for Account {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Account {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Azure blob container name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Container(String);
+
+impl Container {
+    pub fn new<T: Into<String>>(container: T) -> Self {
+        Self(container.into())
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for Container {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for Container {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Container {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Azure blob name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlobName(String);
+
+impl BlobName {
+    pub fn new<T: Into<String>>(blob: T) -> Self {
+        Self(blob.into())
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for BlobName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for BlobName {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for BlobName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BlobName {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Full "path" to a blob
+///
+/// Displayed as the https URL of the blob.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AzureLocation(Account, Container, BlobName);
+
+impl AzureLocation {
+    pub fn new<A: Into<Account>, C: Into<Container>, B: Into<BlobName>>(
+        account: A,
+        container: C,
+        blob: B,
+    ) -> Self {
+        Self(account.into(), container.into(), blob.into())
+    }
+
+    pub fn account(&self) -> &Account {
+        &self.0
+    }
+
+    pub fn container(&self) -> &Container {
+        &self.1
+    }
+
+    pub fn blob(&self) -> &BlobName {
+        &self.2
+    }
+
+    /// Turn this into its three components
+    pub fn into_inner(self) -> (Account, Container, BlobName) {
+        (self.0, self.1, self.2)
+    }
+
+    /// URL of the blob for the given endpoint or the endpoint of the account
+    fn url(&self, endpoint: Option<&str>) -> String {
+        let path = format!(
+            "{}/{}",
+            utf8_percent_encode(&self.1, BLOB_PATH),
+            utf8_percent_encode(&self.2, BLOB_PATH)
+        );
+        match endpoint {
+            Some(endpoint) => format!("{}/{}", endpoint, path),
+            None => format!("https://{}.blob.core.windows.net/{}", self.0, path),
+        }
+    }
+}
+
+impl fmt::Display for AzureLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url(None))
+    }
+}
+
+/// A client for Azure Blob Storage to implement [CondowClient] on
+#[derive(Clone)]
+pub struct AzureClient {
+    http: reqwest::Client,
+    endpoint: Option<Arc<str>>,
+    credentials: Arc<Credentials>,
+}
+
+impl AzureClient {
+    /// Create an anonymous client with a default HTTP client
+    pub fn new() -> Self {
+        Self::from_client(reqwest::Client::new())
+    }
+
+    /// Create an anonymous client with the given HTTP client
+    pub fn from_client(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            endpoint: None,
+            credentials: Default::default(),
+        }
+    }
+
+    /// Set the [Credentials] requests are authorized with
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    /// Send all requests to the given endpoint instead of the endpoint
+    /// of the account of a location
+    ///
+    /// E.g. `http://127.0.0.1:10000/devstoreaccount1` for Azurite.
+    pub fn endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = Some(endpoint.into().trim_end_matches('/').into());
+        self
+    }
+
+    /// Create a concurrent downloader from this adapter and the given [Config]
+    pub fn condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
+    }
+
+    /// Sends an authorized request and fails unless the response is a success
+    async fn send(
+        &self,
+        method: Method,
+        location: &AzureLocation,
+        range: Option<String>,
+    ) -> Result<Response, CondowError> {
+        let mut url = location.url(self.endpoint.as_deref());
+        if let Some(query) = self.credentials.query() {
+            url.push('?');
+            url.push_str(query);
+        }
+
+        let mut request = self
+            .http
+            .request(method, url)
+            .header("x-ms-version", API_VERSION)
+            .header("x-ms-date", httpdate::fmt_http_date(SystemTime::now()));
+        if let Some(range) = range {
+            request = request.header("x-ms-range", range);
+        }
+        let mut request = request.build().map_err(http_err_to_condow_err)?;
+        self.credentials.authorize(&mut request)?;
+
+        let response = self
+            .http
+            .execute(request)
+            .await
+            .map_err(http_err_to_condow_err)?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(response_to_condow_err(response).await)
+        }
+    }
+}
+
+impl Default for AzureClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CondowClient for AzureClient {
+    type Location = AzureLocation;
+
+    /// Uses "Get Blob Properties"
+    fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let response = client.send(Method::HEAD, &location, None).await?;
+
+            let content_length = response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .ok_or_else(|| CondowError::new_other("response had no content length"))?;
+
+            content_length
+                .to_str()
+                .ok()
+                .and_then(|content_length| content_length.parse().ok())
+                .ok_or_else(|| {
+                    CondowError::new_other(format!(
+                        "response had an invalid content length: {:?}",
+                        content_length
+                    ))
+                })
+        };
+
+        Box::pin(f)
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let response = client
+                .send(Method::GET, &location, spec.http_range_value())
+                .await?;
+
+            response_to_download(response, &spec)
+        };
+
+        Box::pin(f)
+    }
+}
+
+/// Responses to HEAD requests have no body so the
+/// error code is taken from the `x-ms-error-code` header.
+async fn response_to_condow_err(response: Response) -> CondowError {
+    let status = response.status();
+    let error_code = response
+        .headers()
+        .get("x-ms-error-code")
+        .and_then(|error_code| error_code.to_str().ok())
+        .map(str::to_string);
+    let body = response_text(response).await;
+
+    let message = match error_code {
+        Some(error_code) if body.is_empty() => error_code,
+        _ => body,
+    };
+
+    status_to_condow_err(status, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_display() {
+        let location = AzureLocation::new("account", "container", "dir/a blob");
+        assert_eq!(
+            location.to_string(),
+            "https://account.blob.core.windows.net/container/dir/a%20blob"
+        );
+    }
+
+    #[test]
+    fn location_url_with_endpoint() {
+        let location = AzureLocation::new("account", "container", "dir/blob");
+        assert_eq!(
+            location.url(Some("http://127.0.0.1:10000/devstoreaccount1")),
+            "http://127.0.0.1:10000/devstoreaccount1/container/dir/blob"
+        );
+    }
+}
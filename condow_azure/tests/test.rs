@@ -0,0 +1,149 @@
+use condow_azure::{
+    condow_client::{CondowClient, DownloadSpec},
+    errors::CondowErrorKind,
+    AzureClient, AzureLocation, Credentials, InclusiveRange,
+};
+use condow_test_server::{
+    adapter_tests, server_responding_with, RangeResponder, TestAdapter, DATA,
+};
+use futures::TryStreamExt;
+use wiremock::{
+    matchers::{header, header_exists, header_regex, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const ACCOUNT: &str = "devstoreaccount1";
+
+const BLOB_PATH: &str = "/devstoreaccount1/container/dir/blob";
+
+fn location() -> AzureLocation {
+    AzureLocation::new(ACCOUNT, "container", "dir/blob")
+}
+
+fn client(server: &MockServer) -> AzureClient {
+    AzureClient::new().endpoint(format!("{}/{}", server.uri(), ACCOUNT))
+}
+
+/// A stand-in for Azurite serving a single blob at [BLOB_PATH]
+struct Azurite;
+
+impl TestAdapter for Azurite {
+    type Client = AzureClient;
+
+    fn object_mocks() -> Vec<Mock> {
+        vec![
+            Mock::given(method("HEAD"))
+                .and(path(BLOB_PATH))
+                .and(header_exists("x-ms-version"))
+                .and(header_exists("x-ms-date"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("content-length", DATA.len().to_string()),
+                ),
+            Mock::given(method("GET"))
+                .and(path(BLOB_PATH))
+                .and(header_exists("x-ms-version"))
+                .and(header_exists("x-ms-date"))
+                .respond_with(RangeResponder::with_header("x-ms-range")),
+        ]
+    }
+
+    /// Responses to HEAD requests only have the `x-ms-error-code` header
+    fn error_mocks(status: u16) -> Vec<Mock> {
+        vec![
+            Mock::given(method("HEAD")).respond_with(
+                ResponseTemplate::new(status).insert_header("x-ms-error-code", "SomeError"),
+            ),
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(status).set_body_string("<Error/>")),
+        ]
+    }
+
+    fn client(server: &MockServer) -> AzureClient {
+        client(server)
+    }
+
+    fn location(_server: &MockServer) -> AzureLocation {
+        location()
+    }
+}
+
+adapter_tests!(Azurite);
+
+#[tokio::test]
+async fn sas_is_appended_to_the_query() {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path(BLOB_PATH))
+        .and(query_param("sv", "2021-08-06"))
+        .and(query_param("sig", "signature"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-length", "3"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = client(&server).credentials(Credentials::sas("?sv=2021-08-06&sig=signature"));
+
+    let size = client.get_size(location()).await.unwrap();
+
+    assert_eq!(size, 3);
+}
+
+#[tokio::test]
+async fn sas_is_not_part_of_errors() {
+    // Nothing listens on the port once the listener is dropped
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/{}", listener.local_addr().unwrap(), ACCOUNT);
+    drop(listener);
+    let client = AzureClient::new()
+        .endpoint(endpoint)
+        .credentials(Credentials::sas("?sv=2021-08-06&sig=secret"));
+
+    let err = client.get_size(location()).await.unwrap_err();
+    assert_eq!(err.kind(), CondowErrorKind::Io);
+    let debug = format!("{:?}", err);
+    assert!(!debug.contains("secret"), "{}", debug);
+
+    let err = client
+        .download(location(), DownloadSpec::Complete)
+        .await
+        .err()
+        .unwrap();
+    let debug = format!("{:?}", err);
+    assert!(!debug.contains("secret"), "{}", debug);
+}
+
+#[tokio::test]
+async fn requests_are_signed_with_the_shared_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(BLOB_PATH))
+        .and(header("x-ms-range", "bytes=0-9"))
+        .and(header_regex(
+            "authorization",
+            "^SharedKey devstoreaccount1:[A-Za-z0-9+/]{43}=$",
+        ))
+        .respond_with(RangeResponder::with_header("x-ms-range"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let credentials = Credentials::shared_key(ACCOUNT, "c2VjcmV0LWtleQ==").unwrap();
+    let client = client(&server).credentials(credentials);
+
+    let (stream, _) = client
+        .download(location(), DownloadSpec::Range(InclusiveRange(0, 9)))
+        .await
+        .unwrap();
+
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), &DATA[0..10]);
+}
+
+#[tokio::test]
+async fn error_code_is_the_message_of_errors_without_body() {
+    let server = server_responding_with::<Azurite>(404).await;
+
+    let err = client(&server).get_size(location()).await.unwrap_err();
+
+    assert_eq!(err.kind(), CondowErrorKind::NotFound);
+    assert!(err.msg().ends_with("SomeError"), "{}", err.msg());
+}
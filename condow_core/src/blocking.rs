@@ -0,0 +1,553 @@
+//! A blocking API on top of the asynchronous downloaders
+//!
+//! Some libraries (e.g. decoders or legacy parsers) require
+//! [std::io::Read] and [std::io::Seek] and can not be made async.
+//! [BlockingCondow] drives any implementor of [Downloads] on a tokio
+//! runtime and blocks the calling thread until a result is available.
+//!
+//! The blocking API must not be used from within an async context. Calls made
+//! on a thread which has entered a tokio runtime context will fail with an error
+//! instead of blocking (or panicking). This includes threads of tokio's blocking
+//! pool (e.g. [tokio::task::spawn_blocking]). Use a dedicated thread in
+//! these cases.
+//!
+//! ```rust
+//! # use std::io::Read;
+//! use condow_core::{condow_client::InMemoryClient, config::Config};
+//!
+//! let condow = InMemoryClient::<String>::new_static(b"a remote BLOB")
+//!     .condow(Config::default())
+//!     .unwrap();
+//!
+//! let blocking = condow.blocking().unwrap();
+//!
+//! let mut reader = blocking.reader("location".to_string()).unwrap();
+//! let mut buf = String::new();
+//! reader.read_to_string(&mut buf).unwrap();
+//!
+//! assert_eq!(buf, "a remote BLOB");
+//! ```
+use std::{
+    fmt,
+    future::Future,
+    io::{Error as IoError, Read, Result as IoResult, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use futures::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    StreamExt,
+};
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+use crate::{
+    errors::CondowError,
+    reader::{FetchAheadMode, RandomAccessReader},
+    streams::{ChunkStream, PartStream, PartStreamItem},
+    DownloadRange, Downloads,
+};
+
+/// The runtime used to drive the futures of the blocking API
+#[derive(Clone)]
+enum BlockingRuntime {
+    /// A runtime owned by the blocking API (or shared with the user)
+    Owned(OwnedRuntime),
+    /// A borrowed multi threaded runtime
+    Handle(Handle),
+}
+
+/// A runtime which is shut down in the background once
+/// the blocking API drops the last reference to it
+///
+/// Dropping a runtime from within an async context panics
+/// while shutting it down in the background does not.
+#[derive(Clone)]
+struct OwnedRuntime(Option<Arc<Runtime>>);
+
+impl OwnedRuntime {
+    fn runtime(&self) -> &Runtime {
+        self.0
+            .as_ref()
+            .expect("the runtime is only taken when dropped")
+    }
+}
+
+impl Drop for OwnedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take().and_then(|rt| Arc::try_unwrap(rt).ok()) {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl BlockingRuntime {
+    /// Drive the future to completion on the runtime and block the current thread.
+    ///
+    /// Fails if the current thread is within an async context.
+    fn block_on<F: Future>(&self, f: F) -> Result<F::Output, CondowError> {
+        if Handle::try_current().is_ok() {
+            return Err(CondowError::new_other(
+                "the blocking API must not be used from within an async context",
+            ));
+        }
+
+        let output = match self {
+            BlockingRuntime::Owned(runtime) => runtime.runtime().block_on(f),
+            BlockingRuntime::Handle(handle) => handle.block_on(f),
+        };
+
+        Ok(output)
+    }
+}
+
+/// A blocking facade for downloading
+///
+/// Drives the downloads of an implementor of [Downloads]
+/// (e.g. [Condow](crate::Condow) or [Downloader](crate::Downloader))
+/// on a tokio runtime. The runtime can be owned by `BlockingCondow` or
+/// borrowed via a [Handle].
+///
+/// Cloning is cheap and clones share the runtime.
+///
+/// See the [module](self) level documentation for the limitations.
+#[derive(Clone)]
+pub struct BlockingCondow<D> {
+    downloader: D,
+    runtime: BlockingRuntime,
+}
+
+impl<D> BlockingCondow<D> {
+    /// Create a new instance with its own runtime.
+    ///
+    /// The runtime is a current thread runtime which will drive the downloads
+    /// only while a thread is blocked on one of the methods.
+    ///
+    /// The runtime is shut down in the background once the last clone
+    /// (including readers and iterators) was dropped. This can
+    /// also happen from within an async context.
+    pub fn new(downloader: D) -> Result<Self, CondowError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| CondowError::new_other("failed to create a runtime").with_source(err))?;
+
+        Ok(Self::with_runtime(downloader, Arc::new(runtime)))
+    }
+
+    /// Create a new instance which drives downloads on the given runtime.
+    ///
+    /// If the blocking API holds the last reference to the runtime, the runtime
+    /// is shut down in the background once that reference is dropped.
+    ///
+    /// **Dropping the last reference to the runtime held outside
+    /// of the blocking API from within an async context will panic**
+    pub fn with_runtime(downloader: D, runtime: Arc<Runtime>) -> Self {
+        Self {
+            downloader,
+            runtime: BlockingRuntime::Owned(OwnedRuntime(Some(runtime))),
+        }
+    }
+
+    /// Create a new instance which drives downloads on the runtime of the given [Handle].
+    ///
+    /// Fails if the runtime is not a multi threaded runtime since
+    /// a current thread runtime can not be driven via a [Handle].
+    pub fn with_handle(downloader: D, handle: Handle) -> Result<Self, CondowError> {
+        if handle.runtime_flavor() == RuntimeFlavor::CurrentThread {
+            return Err(CondowError::new_other(
+                "a handle to a current thread runtime can not drive downloads",
+            ));
+        }
+
+        Ok(Self {
+            downloader,
+            runtime: BlockingRuntime::Handle(handle),
+        })
+    }
+
+    /// Returns the wrapped downloader
+    pub fn downloader(&self) -> &D {
+        &self.downloader
+    }
+
+    /// Get the size of the BLOB at the given location
+    pub fn get_size<L>(&self, location: L) -> Result<u64, CondowError>
+    where
+        D: Downloads<L>,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+    {
+        self.runtime
+            .block_on(self.downloader.get_size(location))
+            .and_then(|r| r)
+    }
+
+    /// Download a BLOB range
+    ///
+    /// Returns an [Iterator] over the downloaded [Parts](crate::streams::Part)
+    /// in the order as they appear in the BLOB.
+    pub fn download<L, R>(&self, location: L, range: R) -> Result<BlockingPartIter, CondowError>
+    where
+        D: Downloads<L>,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+        R: Into<DownloadRange> + Send + Sync + 'static,
+    {
+        let stream = self
+            .runtime
+            .block_on(self.downloader.download(location, range))??;
+
+        Ok(BlockingPartIter {
+            stream,
+            runtime: self.runtime.clone(),
+            is_finished: false,
+        })
+    }
+
+    /// Download a BLOB range into a `Vec`
+    pub fn download_into_vec<L, R>(&self, location: L, range: R) -> Result<Vec<u8>, CondowError>
+    where
+        D: Downloads<L>,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+        R: Into<DownloadRange> + Send + Sync + 'static,
+    {
+        self.runtime.block_on(async {
+            self.downloader
+                .download_chunks(location, range)
+                .await?
+                .into_vec()
+                .await
+        })?
+    }
+
+    /// Download a BLOB range into the given buffer
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// Fails if the buffer is too small.
+    pub fn download_into_buffer<L, R>(
+        &self,
+        location: L,
+        range: R,
+        buffer: &mut [u8],
+    ) -> Result<usize, CondowError>
+    where
+        D: Downloads<L>,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+        R: Into<DownloadRange> + Send + Sync + 'static,
+    {
+        self.runtime.block_on(async {
+            self.downloader
+                .download_chunks(location, range)
+                .await?
+                .write_buffer(buffer)
+                .await
+        })?
+    }
+
+    /// Download multiple BLOB ranges concurrently into the given buffers
+    ///
+    /// See [Downloads::download_into_buffers]
+    pub fn download_into_buffers<'a, L, R, I>(
+        &'a self,
+        location: L,
+        targets: I,
+    ) -> Result<Vec<usize>, CondowError>
+    where
+        D: Downloads<L> + Sync,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+        R: Into<DownloadRange> + Send + Sync + 'static,
+        I: IntoIterator<Item = (R, &'a mut [u8])>,
+    {
+        self.runtime
+            .block_on(self.downloader.download_into_buffers(location, targets))?
+    }
+
+    /// Creates a [BlockingRandomAccessReader] for the given location
+    ///
+    /// This function will query the size of the BLOB. If the size is already known
+    /// call [BlockingCondow::reader_with_length]
+    pub fn reader<L>(&self, location: L) -> Result<BlockingRandomAccessReader<D, L>, CondowError>
+    where
+        D: Downloads<L> + Clone + Send + Sync + 'static,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+    {
+        let length = self.get_size(location.clone())?;
+        Ok(self.reader_with_length(location, length))
+    }
+
+    /// Creates a [BlockingRandomAccessReader] for the given location
+    ///
+    /// This function will create a new reader immediately
+    pub fn reader_with_length<L>(
+        &self,
+        location: L,
+        length: u64,
+    ) -> BlockingRandomAccessReader<D, L>
+    where
+        D: Downloads<L> + Clone + Send + Sync + 'static,
+        L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+    {
+        BlockingRandomAccessReader {
+            reader: RandomAccessReader::new_with_length(self.downloader.clone(), location, length),
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+/// An [Iterator] over the [Parts](crate::streams::Part) of a download
+///
+/// Each call to `next` blocks until the next part is available.
+/// After an error was returned the iterator is finished.
+pub struct BlockingPartIter {
+    stream: PartStream<ChunkStream>,
+    runtime: BlockingRuntime,
+    is_finished: bool,
+}
+
+impl Iterator for BlockingPartIter {
+    type Item = PartStreamItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+
+        let next = match self.runtime.block_on(self.stream.next()) {
+            Ok(next) => next,
+            Err(err) => Some(Err(err)),
+        };
+
+        if !matches!(next, Some(Ok(_))) {
+            self.is_finished = true;
+        }
+
+        next
+    }
+}
+
+/// Implements [Read] and [Seek]
+///
+/// A blocking version of a [RandomAccessReader].
+pub struct BlockingRandomAccessReader<D, L> {
+    reader: RandomAccessReader<D, L>,
+    runtime: BlockingRuntime,
+}
+
+impl<D, L> BlockingRandomAccessReader<D, L>
+where
+    D: Downloads<L> + Clone + Send + Sync + 'static,
+    L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static,
+{
+    /// Returns the current offset of the next byte to read.
+    ///
+    /// The offset is from the start of the BLOB.
+    pub fn pos(&self) -> u64 {
+        self.reader.pos()
+    }
+
+    pub fn set_fetch_ahead_mode<T: Into<FetchAheadMode>>(&mut self, mode: T) {
+        self.reader.set_fetch_ahead_mode(mode)
+    }
+
+    pub fn fetch_ahead_mode(&self) -> FetchAheadMode {
+        self.reader.fetch_ahead_mode()
+    }
+
+    /// Turns this into the wrapped [RandomAccessReader]
+    pub fn into_async_reader(self) -> RandomAccessReader<D, L> {
+        self.reader
+    }
+}
+
+impl<D, L> Read for BlockingRandomAccessReader<D, L>
+where
+    D: Downloads<L> + Clone + Send + Sync + 'static + Unpin,
+    L: fmt::Debug + fmt::Display + Clone + Send + Sync + 'static + Unpin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let reader = &mut self.reader;
+        self.runtime
+            .block_on(reader.read(buf))
+            .map_err(IoError::from)?
+    }
+}
+
+impl<D, L> Seek for BlockingRandomAccessReader<D, L>
+where
+    D: Unpin,
+    L: Unpin,
+{
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let reader = &mut self.reader;
+        self.runtime
+            .block_on(reader.seek(pos))
+            .map_err(IoError::from)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use crate::{
+        condow_client::{InMemoryClient, NoLocation},
+        config::Config,
+        Condow,
+    };
+
+    use super::BlockingCondow;
+
+    fn blocking_condow(blob: &'static [u8]) -> BlockingCondow<Condow<InMemoryClient>> {
+        let config = Config::default()
+            .part_size_bytes(3)
+            .max_concurrency(2)
+            .buffers_full_delay_ms(0);
+        InMemoryClient::new_static(blob)
+            .chunk_size(2)
+            .condow(config)
+            .unwrap()
+            .blocking()
+            .unwrap()
+    }
+
+    #[test]
+    fn get_size() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        assert_eq!(blocking.get_size(NoLocation).unwrap(), 10);
+    }
+
+    #[test]
+    fn download_into_vec() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let bytes = blocking.download_into_vec(NoLocation, 2..=8).unwrap();
+
+        assert_eq!(bytes, b"cdefghi");
+    }
+
+    #[test]
+    fn download_into_buffer() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let mut buffer = [0u8; 10];
+        let n_bytes = blocking
+            .download_into_buffer(NoLocation, .., &mut buffer)
+            .unwrap();
+
+        assert_eq!(n_bytes, 10);
+        assert_eq!(&buffer, b"abcdefghij");
+    }
+
+    #[test]
+    fn download_into_buffers() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let mut page_1 = [0u8; 4];
+        let mut page_2 = [0u8; 2];
+        let bytes_written = blocking
+            .download_into_buffers(
+                NoLocation,
+                vec![(0..4, &mut page_1[..]), (8..10, &mut page_2[..])],
+            )
+            .unwrap();
+
+        assert_eq!(bytes_written, vec![4, 2]);
+        assert_eq!(&page_1, b"abcd");
+        assert_eq!(&page_2, b"ij");
+    }
+
+    #[test]
+    fn download_parts() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let mut collected = Vec::new();
+        let mut n_parts = 0;
+        for part in blocking.download(NoLocation, ..).unwrap() {
+            let part = part.unwrap();
+            part.chunks
+                .iter()
+                .for_each(|c| collected.extend_from_slice(c));
+            n_parts += 1;
+        }
+
+        assert_eq!(n_parts, 4);
+        assert_eq!(collected, b"abcdefghij");
+    }
+
+    #[test]
+    fn read_and_seek() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let mut reader = blocking.reader(NoLocation).unwrap();
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"abcdefghij");
+
+        reader.seek(SeekFrom::Start(4)).unwrap();
+        let mut buf = [0u8; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"efg");
+
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "ij");
+    }
+
+    #[test]
+    fn use_from_multiple_threads() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let blocking = blocking.clone();
+                std::thread::spawn(move || blocking.download_into_vec(NoLocation, ..).unwrap())
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), b"abcdefghij");
+        }
+    }
+
+    #[test]
+    fn fails_within_async_context() {
+        let blocking = blocking_condow(b"abcdefghij");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let result = runtime.block_on(async { blocking.get_size(NoLocation) });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn can_be_dropped_within_async_context() {
+        let blocking = blocking_condow(b"abcdefghij");
+        let reader = blocking.reader(NoLocation).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async move {
+            drop(blocking);
+            drop(reader);
+        });
+    }
+
+    #[test]
+    fn with_handle_requires_multi_threaded_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let condow = InMemoryClient::<NoLocation>::new_static(b"abc")
+            .condow(Config::default())
+            .unwrap();
+
+        assert!(BlockingCondow::with_handle(condow, runtime.handle().clone()).is_err());
+    }
+}
@@ -866,7 +866,9 @@ pub mod failing_client_simulator {
             if self.next == self.end_excl || self.chunk_size == 0 {
                 if let Some(error_action) = self.error.take() {
                     match error_action {
-                        ErrorAction::Err(msg) => return task::Poll::Ready(Some(Err(IoError(msg)))),
+                        ErrorAction::Err(msg) => {
+                            return task::Poll::Ready(Some(Err(IoError::new(msg))))
+                        }
                         ErrorAction::Panic(msg) => panic!("{}", msg),
                     }
                 } else {
@@ -120,6 +120,8 @@ mod range {
                                 NoLocation,
                                 range.clone(),
                                 crate::GetSizeMode::Always,
+                                crate::DownloadPriority::default(),
+                                None,
                                 SimpleReporter::default(),
                             )
                             .await
@@ -166,6 +168,8 @@ mod range {
                                 NoLocation,
                                 range.clone(),
                                 crate::GetSizeMode::Required,
+                                crate::DownloadPriority::default(),
+                                None,
                                 SimpleReporter::default(),
                             )
                             .await
@@ -222,6 +226,8 @@ mod range {
                                 NoLocation,
                                 range.clone(),
                                 crate::GetSizeMode::Default,
+                                crate::DownloadPriority::default(),
+                                None,
                                 SimpleReporter::default(),
                             )
                             .await
@@ -268,6 +274,8 @@ mod range {
                                 NoLocation,
                                 range.clone(),
                                 crate::GetSizeMode::Default,
+                                crate::DownloadPriority::default(),
+                                None,
                                 SimpleReporter::default(),
                             )
                             .await
@@ -324,6 +332,8 @@ mod range {
                                         NoLocation,
                                         range,
                                         crate::GetSizeMode::Default,
+                                        crate::DownloadPriority::default(),
+                                        None,
                                         SimpleReporter::default(),
                                     )
                                     .await
@@ -371,6 +381,8 @@ mod range {
                                         NoLocation,
                                         range,
                                         crate::GetSizeMode::Default,
+                                        crate::DownloadPriority::default(),
+                                        None,
                                         SimpleReporter::default(),
                                     )
                                     .await
@@ -430,6 +442,8 @@ mod range {
                                             NoLocation,
                                             range,
                                             crate::GetSizeMode::Default,
+                                            crate::DownloadPriority::default(),
+                                            None,
                                             SimpleReporter::default(),
                                         )
                                         .await
@@ -484,6 +498,8 @@ mod range {
                                             NoLocation,
                                             range,
                                             crate::GetSizeMode::Default,
+                                            crate::DownloadPriority::default(),
+                                            None,
                                             SimpleReporter::default(),
                                         )
                                         .await
@@ -506,3 +522,472 @@ mod range {
         }
     }
 }
+
+mod download_into_buffers {
+    use std::sync::Arc;
+
+    use crate::condow_client::NoLocation;
+    use crate::{config::Config, test_utils::*, Condow, DownloadRange, Downloads};
+
+    #[tokio::test]
+    async fn scatter_into_disjoint_buffers() {
+        let data = Arc::new(create_test_data());
+
+        let client = TestCondowClient {
+            data: Arc::clone(&data),
+            max_jitter_ms: 5,
+            include_size_hint: true,
+            max_chunk_size: 3,
+        };
+
+        let config = Config::default()
+            .buffers_full_delay_ms(0)
+            .part_size_bytes(7)
+            .max_concurrency(4);
+        let condow = Condow::new(client, config).unwrap();
+
+        let ranges: Vec<DownloadRange> = vec![
+            (10..50).into(),
+            (0..=0).into(),
+            (33..90).into(),
+            (200..).into(),
+        ];
+
+        let mut page_1 = vec![0u8; 40];
+        let mut page_2 = vec![0u8; 1];
+        let mut page_3 = vec![0u8; 57];
+        let mut page_4 = vec![0u8; data.len() - 200];
+
+        let targets = ranges.clone().into_iter().zip(vec![
+            page_1.as_mut_slice(),
+            page_2.as_mut_slice(),
+            page_3.as_mut_slice(),
+            page_4.as_mut_slice(),
+        ]);
+
+        let bytes_written = condow
+            .download_into_buffers(NoLocation, targets)
+            .await
+            .unwrap();
+
+        assert_eq!(bytes_written, vec![40, 1, 57, data.len() - 200]);
+        assert_eq!(page_1, data[10..50]);
+        assert_eq!(page_2, data[0..=0]);
+        assert_eq!(page_3, data[33..90]);
+        assert_eq!(page_4, data[200..]);
+    }
+
+    #[tokio::test]
+    async fn fails_if_a_buffer_is_too_small() {
+        let client = TestCondowClient::new();
+        let condow = Condow::new(client, Config::default()).unwrap();
+
+        let mut page_1 = vec![0u8; 10];
+        let mut page_2 = vec![0u8; 9];
+
+        let result = condow
+            .download_into_buffers(
+                NoLocation,
+                vec![
+                    (0..10, page_1.as_mut_slice()),
+                    (10..20, page_2.as_mut_slice()),
+                ],
+            )
+            .await;
+
+        assert!(result.is_err());
+    }
+}
+
+mod memory_budget {
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+
+    use crate::condow_client::NoLocation;
+    use crate::reporter::SimpleReporterFactory;
+    use crate::{config::Config, test_utils::*, Condow};
+
+    fn condow(budget: u64) -> (Condow<TestCondowClient>, Arc<Vec<u8>>) {
+        let client = TestCondowClient::new().max_chunk_size(3);
+        let data = client.data();
+
+        let config = Config::default()
+            .buffers_full_delay_ms(0)
+            .part_size_bytes(10)
+            .max_concurrency(4)
+            .memory_budget_bytes(budget);
+
+        (Condow::new(client, config).unwrap(), data)
+    }
+
+    #[tokio::test]
+    async fn download_chunks_within_budget() {
+        for budget in [10, 15, 25, 1_000] {
+            let (condow, data) = condow(budget);
+
+            let result = condow
+                .download_chunks(NoLocation, ..)
+                .await
+                .unwrap()
+                .into_vec()
+                .await
+                .unwrap();
+
+            assert_eq!(result, data[..], "budget: {}", budget);
+            assert_eq!(condow.memory_budget_bytes_in_use(), Some(0));
+        }
+    }
+
+    #[tokio::test]
+    async fn download_parts_within_budget() {
+        for budget in [10, 15, 25, 1_000] {
+            let (condow, data) = condow(budget);
+
+            let result = condow
+                .download(NoLocation, ..)
+                .await
+                .unwrap()
+                .into_vec()
+                .await
+                .unwrap();
+
+            assert_eq!(result, data[..], "budget: {}", budget);
+            assert_eq!(condow.memory_budget_bytes_in_use(), Some(0));
+        }
+    }
+
+    #[test]
+    fn parts_larger_than_the_budget_are_rejected() {
+        let config = Config::default().part_size_bytes(10).memory_budget_bytes(9);
+
+        assert!(Condow::new(TestCondowClient::new(), config).is_err());
+    }
+
+    #[tokio::test]
+    async fn reservations_are_reported() {
+        let (condow, _data) = condow(20);
+
+        let downloader = condow.downloader_with_reporting(SimpleReporterFactory::default());
+
+        let (stream, reporter) = downloader
+            .download_rep(NoLocation, ..)
+            .await
+            .unwrap()
+            .into_parts();
+        let _ = stream.into_vec().await.unwrap();
+
+        let report = reporter.report();
+        assert!(report.n_memory_budget_exhausted > 0);
+        assert!(report.max_memory_bytes_in_use <= 20);
+        assert!(report.max_memory_bytes_in_use > 0);
+    }
+
+    #[tokio::test]
+    async fn dropping_a_stream_returns_its_reservations() {
+        let (condow, _data) = condow(15);
+
+        let mut stream = condow.download(NoLocation, ..).await.unwrap();
+        let _ = stream.next().await.unwrap().unwrap();
+
+        let stream_b = condow.download_chunks(NoLocation, 0..10).await.unwrap();
+
+        drop(stream);
+
+        let result = stream_b.into_vec().await.unwrap();
+        assert_eq!(result.len(), 10);
+
+        // Let the tasks of the dropped download notice
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(condow.memory_budget_bytes_in_use(), Some(0));
+    }
+}
+
+mod priorities {
+    use futures::future;
+
+    use crate::condow_client::NoLocation;
+    use crate::{config::Config, test_utils::*, Condow, DownloadPriority};
+
+    #[tokio::test]
+    async fn downloads_with_shared_concurrency() {
+        let client = TestCondowClient::new().max_chunk_size(3).max_jitter_ms(2);
+        let data = client.data();
+
+        for min_share in [0, 10, 100] {
+            let config = Config::default()
+                .buffers_full_delay_ms(0)
+                .part_size_bytes(10)
+                .max_concurrency(4)
+                .shared_concurrency(2)
+                .low_priority_min_share_percent(min_share);
+            let condow = Condow::new(client.clone(), config).unwrap();
+
+            let downloads = (0..10).map(|n| {
+                let priority = if n % 2 == 0 {
+                    DownloadPriority::High
+                } else {
+                    DownloadPriority::Low
+                };
+                let downloader = condow.downloader().priority(priority);
+                async move {
+                    downloader
+                        .download(NoLocation, ..)
+                        .await
+                        .unwrap()
+                        .into_vec()
+                        .await
+                        .unwrap()
+                }
+            });
+
+            for result in future::join_all(downloads).await {
+                assert_eq!(result, data[..], "min share: {}", min_share);
+            }
+        }
+    }
+}
+
+mod request_reporting {
+    use futures::StreamExt;
+
+    use crate::condow_client::failing_client_simulator::FailingClientSimulatorBuilder;
+    use crate::condow_client::NoLocation;
+    use crate::config::{Config, RetryConfig};
+    use crate::errors::CondowError;
+    use crate::reporter::{ChannelReporter, ReportEvent};
+
+    #[tokio::test]
+    async fn size_requests_and_retried_requests_are_reported() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(10)
+            .responses()
+            .failure(CondowError::new_remote("remote failed"))
+            .success()
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(100)
+                    .retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let (reporter, receiver) = ChannelReporter::unbounded();
+
+        let result = condow
+            .downloader()
+            .download_wrep(NoLocation, .., reporter)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap();
+        assert_eq!(result, blob);
+
+        let events = receiver
+            .filter_map(|event| async move {
+                match event {
+                    ReportEvent::SizeRequestStarted { .. } => Some("size started".to_string()),
+                    ReportEvent::SizeRequestCompleted { size, .. } => {
+                        Some(format!("size completed {}", size))
+                    }
+                    ReportEvent::RequestStarted { attempt, .. } => {
+                        Some(format!("request started {}", attempt))
+                    }
+                    ReportEvent::RequestFailed { attempt, .. } => {
+                        Some(format!("request failed {}", attempt))
+                    }
+                    ReportEvent::RequestCompleted {
+                        attempt,
+                        latency,
+                        time_to_first_byte,
+                        ..
+                    } => {
+                        assert!(latency <= time_to_first_byte);
+                        Some(format!("request completed {}", attempt))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            events,
+            vec![
+                "size started",
+                "size completed 100",
+                "request started 1",
+                "request failed 1",
+                "request started 2",
+                "request completed 2"
+            ]
+        );
+    }
+}
+
+mod download_context {
+    use futures::StreamExt;
+
+    use crate::condow_client::failing_client_simulator::FailingClientSimulatorBuilder;
+    use crate::condow_client::NoLocation;
+    use crate::config::Config;
+    use crate::errors::{CondowError, CondowErrorKind};
+    use crate::reporter::{ChannelReporter, ReportEvent};
+    use crate::DownloadContext;
+
+    #[tokio::test]
+    async fn context_is_reported_and_attached_to_errors() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(10)
+            .responses()
+            .failure(CondowError::new_not_found("not there"))
+            .finish()
+            .condow(Config::default().part_size_bytes(100))
+            .unwrap();
+
+        let context = DownloadContext::from_request_id("abc").with_tag("tenant", "t1");
+        let (reporter, receiver) = ChannelReporter::unbounded();
+
+        let err = condow
+            .downloader()
+            .context(context.clone())
+            .download_wrep(NoLocation, .., reporter)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), CondowErrorKind::NotFound);
+        assert_eq!(err.context(), Some(&context));
+        assert_eq!(err.to_string(), "not there [request_id=abc tenant=t1]");
+
+        let events = receiver.collect::<Vec<_>>().await;
+        assert_eq!(events[0], ReportEvent::DownloadContext(context));
+        let part_failed_error = events
+            .iter()
+            .find_map(|event| match event {
+                ReportEvent::PartFailed { error, .. } => Some(error.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(part_failed_error, "not there [request_id=abc tenant=t1]");
+    }
+
+    #[tokio::test]
+    async fn errors_without_context_are_unchanged() {
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(vec![0; 100])
+            .responses()
+            .failure(CondowError::new_not_found("not there"))
+            .finish()
+            .condow(Config::default())
+            .unwrap();
+
+        let err = condow
+            .download(NoLocation, ..)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap_err();
+
+        assert!(err.context().is_none());
+        assert_eq!(err.to_string(), "not there");
+    }
+}
+
+mod structured_errors {
+    use crate::condow_client::failing_client_simulator::FailingClientSimulatorBuilder;
+    use crate::condow_client::NoLocation;
+    use crate::config::{Config, RetryConfig};
+    use crate::errors::{CondowError, CondowErrorKind};
+    use crate::InclusiveRange;
+
+    #[tokio::test]
+    async fn retries_exhausted() {
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(vec![0; 100])
+            .responses()
+            .failures([
+                CondowError::new_remote("remote 1"),
+                CondowError::new_io("io 2"),
+                CondowError::new_remote("remote 3"),
+            ])
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(50)
+                    .max_concurrency(1)
+                    .retries(RetryConfig::default().max_attempts(2).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let err = condow
+            .download(NoLocation, 0..100)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), CondowErrorKind::Remote);
+        assert_eq!(err.msg(), "remote 3");
+        assert_eq!(err.location(), Some("<no location>"));
+        assert_eq!(err.part_index(), Some(0));
+        assert_eq!(err.range(), Some(InclusiveRange(0, 49)));
+        assert_eq!(err.bytes_received(), Some(0));
+        assert_eq!(err.attempts(), Some(3));
+        let retry_errors = err
+            .retry_errors()
+            .iter()
+            .map(|e| e.msg())
+            .collect::<Vec<_>>();
+        assert_eq!(retry_errors, ["remote 1", "io 2"]);
+    }
+
+    #[tokio::test]
+    async fn resume_of_broken_stream_failed() {
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(vec![0; 100])
+            .chunk_size(10)
+            .responses()
+            .success_with_stream_failure(15)
+            .failure(CondowError::new_not_found("gone"))
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(100)
+                    .retries(RetryConfig::default().max_attempts(2).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let err = condow
+            .download(NoLocation, 0..100)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), CondowErrorKind::Io);
+        assert_eq!(err.part_index(), Some(0));
+        assert_eq!(err.range(), Some(InclusiveRange(0, 99)));
+        assert_eq!(err.bytes_received(), Some(15));
+        assert_eq!(err.attempts(), Some(2));
+        let retry_errors = err
+            .retry_errors()
+            .iter()
+            .map(|e| e.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            retry_errors,
+            [CondowErrorKind::Io, CondowErrorKind::NotFound]
+        );
+    }
+}
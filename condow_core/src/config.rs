@@ -51,6 +51,40 @@ pub struct Config {
     ///
     /// Retries are turned on by default
     pub retries: Option<RetryConfig>,
+    /// The maximum number of bytes held in memory by all downloads
+    /// of a [Condow](super::Condow) and its clones.
+    ///
+    /// Bytes are reserved for a part before the part is scheduled for
+    /// download. The reservation is returned once the bytes were
+    /// pulled from the stream of a download. If the budget is exhausted,
+    /// scheduling of parts waits.
+    ///
+    /// Streams of downloads which are not consumed keep their reservations.
+    /// Therefore a consumer must not wait for the bytes of another download
+    /// of the same [Condow](super::Condow) before it pulled all bytes of a
+    /// download or dropped its stream. Otherwise both downloads may wait
+    /// for each other forever.
+    ///
+    /// Must not be smaller than [Config::part_size_bytes].
+    ///
+    /// The default is no budget.
+    pub memory_budget_bytes: Option<MemoryBudgetBytes>,
+    /// The maximum number of parts downloaded at the same time
+    /// by all downloads of a [Condow](super::Condow) and its clones.
+    ///
+    /// This enables scheduling by [DownloadPriority](super::DownloadPriority).
+    ///
+    /// The default is no limit.
+    pub shared_concurrency: Option<SharedConcurrency>,
+    /// The share of the [Config::shared_concurrency] in percent guaranteed
+    /// to downloads with [DownloadPriority::Low](super::DownloadPriority::Low)
+    /// so that they do not starve.
+    ///
+    /// If not 0 at least one part of a low priority download can be downloaded
+    /// at any time.
+    ///
+    /// Default is 10%
+    pub low_priority_min_share_percent: LowPriorityMinSharePercent,
 }
 
 impl Config {
@@ -124,6 +158,45 @@ impl Config {
         self
     }
 
+    /// Set a budget for the bytes held in memory by all downloads
+    pub fn memory_budget_bytes<T: Into<MemoryBudgetBytes>>(
+        mut self,
+        memory_budget_bytes: T,
+    ) -> Self {
+        self.memory_budget_bytes = Some(memory_budget_bytes.into());
+        self
+    }
+
+    /// Disables the memory budget
+    ///
+    /// The memory budget is disabled by default.
+    pub fn disable_memory_budget(mut self) -> Self {
+        self.memory_budget_bytes = None;
+        self
+    }
+
+    /// Limit the number of parts downloaded at the same time by all downloads
+    pub fn shared_concurrency<T: Into<SharedConcurrency>>(mut self, shared_concurrency: T) -> Self {
+        self.shared_concurrency = Some(shared_concurrency.into());
+        self
+    }
+
+    /// Removes the limit on the number of parts downloaded at the same time
+    /// by all downloads
+    pub fn disable_shared_concurrency(mut self) -> Self {
+        self.shared_concurrency = None;
+        self
+    }
+
+    /// Set the share of the shared concurrency guaranteed to low priority downloads
+    pub fn low_priority_min_share_percent<T: Into<LowPriorityMinSharePercent>>(
+        mut self,
+        low_priority_min_share_percent: T,
+    ) -> Self {
+        self.low_priority_min_share_percent = low_priority_min_share_percent.into();
+        self
+    }
+
     /// Validate this [Config]
     pub fn validated(self) -> Result<Self, AnyError> {
         if self.max_concurrency.0 == 0 {
@@ -138,6 +211,33 @@ impl Config {
             retries.validate()?;
         }
 
+        if let Some(memory_budget_bytes) = self.memory_budget_bytes {
+            if memory_budget_bytes.0 == 0 {
+                bail!("'memory_budget_bytes' must not be 0");
+            }
+
+            if self.part_size_bytes.0 > memory_budget_bytes.0 {
+                bail!("'part_size_bytes' must not be greater than 'memory_budget_bytes'");
+            }
+
+            if self.part_size_bytes.0 > u32::MAX as u64 {
+                bail!(
+                    "'part_size_bytes' must not be greater than {} with a memory budget",
+                    u32::MAX
+                );
+            }
+        }
+
+        if let Some(shared_concurrency) = self.shared_concurrency {
+            if shared_concurrency.0 == 0 {
+                bail!("'shared_concurrency' must not be 0");
+            }
+        }
+
+        if self.low_priority_min_share_percent.0 > 100 {
+            bail!("'low_priority_min_share_percent' must not be greater than 100");
+        }
+
         Ok(self)
     }
 
@@ -170,6 +270,25 @@ impl Config {
             self.always_get_size = always_get_size;
         }
 
+        if let Some(memory_budget_bytes) =
+            MemoryBudgetBytes::try_from_env_prefixed(prefix.as_ref())?
+        {
+            found_any = true;
+            self.memory_budget_bytes = Some(memory_budget_bytes);
+        }
+
+        if let Some(shared_concurrency) = SharedConcurrency::try_from_env_prefixed(prefix.as_ref())?
+        {
+            found_any = true;
+            self.shared_concurrency = Some(shared_concurrency);
+        }
+        if let Some(low_priority_min_share_percent) =
+            LowPriorityMinSharePercent::try_from_env_prefixed(prefix.as_ref())?
+        {
+            found_any = true;
+            self.low_priority_min_share_percent = low_priority_min_share_percent;
+        }
+
         if let Some(retries) = RetryConfig::from_env_prefixed(prefix.as_ref())? {
             found_any = true;
             self.retries = Some(retries);
@@ -188,6 +307,9 @@ impl Default for Config {
             buffers_full_delay_ms: Default::default(),
             always_get_size: Default::default(),
             retries: Some(Default::default()),
+            memory_budget_bytes: None,
+            shared_concurrency: None,
+            low_priority_min_share_percent: Default::default(),
         }
     }
 }
@@ -341,6 +463,24 @@ impl Default for MaxConcurrency {
     }
 }
 
+new_type! {
+    #[doc="Maximum number of parts downloaded at the same time by all downloads"]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub copy struct SharedConcurrency(usize, env="SHARED_CONCURRENCY");
+}
+
+new_type! {
+    #[doc="Share of the shared concurrency in percent guaranteed to low priority downloads"]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub copy struct LowPriorityMinSharePercent(u8, env="LOW_PRIORITY_MIN_SHARE_PERCENT");
+}
+
+impl Default for LowPriorityMinSharePercent {
+    fn default() -> Self {
+        LowPriorityMinSharePercent(10)
+    }
+}
+
 new_type! {
     #[doc="Buffer size of a concurrent download task"]
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -383,6 +523,85 @@ impl From<BuffersFullDelayMs> for Duration {
     }
 }
 
+/// Maximum number of bytes held in memory by all downloads
+///
+/// Parses the same units as [PartSizeBytes].
+///
+/// # Examples
+///
+/// ```rust
+/// # use condow_core::config::MemoryBudgetBytes;
+///
+/// let n_bytes: MemoryBudgetBytes = "512Mi".parse().unwrap();
+/// assert_eq!(n_bytes, 536_870_912.into());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryBudgetBytes(u64);
+
+impl MemoryBudgetBytes {
+    pub fn new<T: Into<u64>>(memory_budget_bytes: T) -> Self {
+        Self(memory_budget_bytes.into())
+    }
+
+    env_funs!("MEMORY_BUDGET_BYTES");
+}
+
+impl From<u64> for MemoryBudgetBytes {
+    fn from(v: u64) -> Self {
+        MemoryBudgetBytes(v)
+    }
+}
+
+impl From<MemoryBudgetBytes> for u64 {
+    fn from(v: MemoryBudgetBytes) -> Self {
+        v.0
+    }
+}
+
+impl From<Kilo> for MemoryBudgetBytes {
+    fn from(v: Kilo) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl From<Mega> for MemoryBudgetBytes {
+    fn from(v: Mega) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl From<Giga> for MemoryBudgetBytes {
+    fn from(v: Giga) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl From<Kibi> for MemoryBudgetBytes {
+    fn from(v: Kibi) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl From<Mebi> for MemoryBudgetBytes {
+    fn from(v: Mebi) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl From<Gibi> for MemoryBudgetBytes {
+    fn from(v: Gibi) -> Self {
+        MemoryBudgetBytes::new(v)
+    }
+}
+
+impl FromStr for MemoryBudgetBytes {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<PartSizeBytes>().map(|v| Self(v.0))
+    }
+}
+
 /// Multiplies by 1_000 when converted to a u64
 ///
 // # Examples
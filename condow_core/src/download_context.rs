@@ -0,0 +1,102 @@
+//! User provided context of a download
+use std::{collections::BTreeMap, fmt};
+
+/// A context which can be attached to a download
+///
+/// The context consists of an optional request id and arbitrary tags.
+/// It is passed to the [Reporter](crate::reporter::Reporter) via
+/// [Reporter::download_context](crate::reporter::Reporter::download_context)
+/// and attached to the [CondowError](crate::errors::CondowError)s
+/// of a failed download.
+///
+/// Displays as `request_id=<id> <key>=<value>...`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DownloadContext {
+    request_id: Option<String>,
+    tags: BTreeMap<String, String>,
+}
+
+impl DownloadContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a context with the given request id
+    pub fn from_request_id<T: Into<String>>(request_id: T) -> Self {
+        Self::new().with_request_id(request_id)
+    }
+
+    /// Set the request id
+    pub fn with_request_id<T: Into<String>>(mut self, request_id: T) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Add a tag
+    ///
+    /// A tag with the same key will be replaced.
+    pub fn with_tag<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub fn tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    /// Returns `true` if there is neither a request id nor any tags
+    pub fn is_empty(&self) -> bool {
+        self.request_id.is_none() && self.tags.is_empty()
+    }
+}
+
+impl fmt::Display for DownloadContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        if let Some(request_id) = self.request_id.as_deref() {
+            write!(f, "request_id={}", request_id)?;
+            separator = " ";
+        }
+        for (key, value) in &self.tags {
+            write!(f, "{}{}={}", separator, key, value)?;
+            separator = " ";
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(DownloadContext::new().to_string(), "");
+        assert_eq!(
+            DownloadContext::from_request_id("abc").to_string(),
+            "request_id=abc"
+        );
+        assert_eq!(
+            DownloadContext::new()
+                .with_tag("tenant", "a")
+                .with_tag("job", "b")
+                .to_string(),
+            "job=b tenant=a"
+        );
+        assert_eq!(
+            DownloadContext::from_request_id("abc")
+                .with_tag("tenant", "a")
+                .to_string(),
+            "request_id=abc tenant=a"
+        );
+    }
+}
@@ -10,6 +10,7 @@ use crate::errors::CondowError;
 ///
 /// A replacement for [RangeInclusive] with some sugar.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InclusiveRange(pub u64, pub u64);
 
 impl InclusiveRange {
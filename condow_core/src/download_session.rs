@@ -8,9 +8,13 @@ use crate::{
     errors::CondowError,
     machinery,
     reader::RandomAccessReader,
-    reporter::{CompositeReporter, NoReporting, Reporter, ReporterFactory},
+    reporter::{
+        AggregatingReporterFactory, CompositeReporter, NoReporting, Reporter, ReporterFactory,
+        SessionReport,
+    },
     streams::{ChunkStream, PartStream},
-    Condow, DownloadRange, Downloads, GetSizeMode, StreamWithReport,
+    Condow, DownloadContext, DownloadPriority, DownloadRange, Downloads, GetSizeMode,
+    StreamWithReport,
 };
 
 /// A downloading API for instrumented downloading.
@@ -28,6 +32,14 @@ pub struct DownloadSession<C: CondowClient, RF: ReporterFactory = NoReporting> {
     /// Default: As configured with [Condow] itself
     /// or the struct this was cloned from
     get_size_mode: GetSizeMode,
+    /// Priority of the downloads
+    ///
+    /// Default: [DownloadPriority::High]
+    priority: DownloadPriority,
+    /// Context attached to the downloads
+    ///
+    /// Default: None
+    context: Option<Arc<DownloadContext>>,
     condow: Condow<C>,
     reporter_factory: Arc<RF>,
 }
@@ -37,6 +49,8 @@ impl<C: CondowClient, RF: ReporterFactory> DownloadSession<C, RF> {
         Self {
             condow,
             get_size_mode: GetSizeMode::default(),
+            priority: DownloadPriority::default(),
+            context: None,
             reporter_factory: rep_fac,
         }
     }
@@ -47,6 +61,24 @@ impl<C: CondowClient, RF: ReporterFactory> DownloadSession<C, RF> {
         self
     }
 
+    /// Change the priority of the downloads
+    ///
+    /// The priority only has an effect if the shared concurrency is limited.
+    /// See [DownloadPriority].
+    pub fn priority(mut self, priority: DownloadPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Attach a [DownloadContext] to the downloads
+    ///
+    /// The context is passed to the [Reporter]s and attached
+    /// to the errors of failed downloads.
+    pub fn context(mut self, context: DownloadContext) -> Self {
+        self.context = Some(Arc::new(context));
+        self
+    }
+
     /// Returns a reference to the [ReporterFactory].
     pub fn reporter_factory(&self) -> &RF {
         self.reporter_factory.as_ref()
@@ -82,9 +114,17 @@ impl<C: CondowClient, RF: ReporterFactory> DownloadSession<C, RF> {
         range: R,
     ) -> Result<ChunkStream, CondowError> {
         let reporter = self.reporter_factory.make(&location);
-        machinery::download(&self.condow, location, range, self.get_size_mode, reporter)
-            .await
-            .map(|o| o.stream)
+        machinery::download(
+            &self.condow,
+            location,
+            range,
+            self.get_size_mode,
+            self.priority,
+            self.context.clone(),
+            reporter,
+        )
+        .await
+        .map(|o| o.stream)
     }
 
     /// Download the BLOB/range and report events.
@@ -163,15 +203,23 @@ impl<C: CondowClient, RF: ReporterFactory> DownloadSession<C, RF> {
         reporter: RPP,
     ) -> Result<StreamWithReport<ChunkStream, RPP>, CondowError> {
         let composite = CompositeReporter(self.reporter_factory.make(&location), reporter);
-        machinery::download(&self.condow, location, range, self.get_size_mode, composite)
-            .await
-            .map(|sr| {
-                let StreamWithReport { stream, reporter } = sr;
-                StreamWithReport {
-                    stream,
-                    reporter: reporter.1,
-                }
-            })
+        machinery::download(
+            &self.condow,
+            location,
+            range,
+            self.get_size_mode,
+            self.priority,
+            self.context.clone(),
+            composite,
+        )
+        .await
+        .map(|sr| {
+            let StreamWithReport { stream, reporter } = sr;
+            StreamWithReport {
+                stream,
+                reporter: reporter.1,
+            }
+        })
     }
 
     /// Get the size of a file at the BLOB at location
@@ -208,12 +256,23 @@ impl<C: CondowClient, RF: ReporterFactory> DownloadSession<C, RF> {
     }
 }
 
+impl<C: CondowClient> DownloadSession<C, AggregatingReporterFactory> {
+    /// Aggregates the reports of all downloads of this session so far
+    ///
+    /// Clones of this session share the same [AggregatingReporterFactory].
+    pub fn session_report(&self) -> SessionReport {
+        self.reporter_factory.session_report()
+    }
+}
+
 impl<C: CondowClient, RF: ReporterFactory> Clone for DownloadSession<C, RF> {
     fn clone(&self) -> Self {
         Self {
             condow: self.condow.clone(),
             reporter_factory: Arc::clone(&self.reporter_factory),
             get_size_mode: self.get_size_mode,
+            priority: self.priority,
+            context: self.context.clone(),
         }
     }
 }
@@ -2,15 +2,19 @@
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use tokio::sync::watch;
 
 use crate::{
     condow_client::CondowClient,
     errors::CondowError,
     machinery,
     reader::RandomAccessReader,
-    reporter::{NoReporting, Reporter, ReporterFactory},
+    reporter::{
+        CompositeReporter, NoReporting, Progress, ProgressReporter, Reporter, ReporterFactory,
+    },
     streams::{ChunkStream, PartStream},
-    Condow, DownloadRange, Downloads, GetSizeMode, StreamWithReport,
+    Condow, DownloadContext, DownloadPriority, DownloadRange, Downloads, GetSizeMode,
+    StreamWithReport,
 };
 
 /// A downloading API.
@@ -34,6 +38,14 @@ pub struct Downloader<C: CondowClient, RF: ReporterFactory = NoReporting> {
     /// Default: As configured with [Condow] itself
     /// or the struct this was cloned from
     get_size_mode: GetSizeMode,
+    /// Priority of the downloads
+    ///
+    /// Default: [DownloadPriority::High]
+    priority: DownloadPriority,
+    /// Context attached to the downloads
+    ///
+    /// Default: None
+    context: Option<Arc<DownloadContext>>,
     condow: Condow<C>,
     reporter_factory: Arc<RF>,
 }
@@ -53,6 +65,8 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
         Self {
             condow,
             get_size_mode: GetSizeMode::default(),
+            priority: DownloadPriority::default(),
+            context: None,
             reporter_factory: rep_fac,
         }
     }
@@ -63,6 +77,24 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
         self
     }
 
+    /// Change the priority of the downloads
+    ///
+    /// The priority only has an effect if the shared concurrency is limited.
+    /// See [DownloadPriority].
+    pub fn priority(mut self, priority: DownloadPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Attach a [DownloadContext] to the downloads
+    ///
+    /// The context is passed to the [Reporter]s and attached
+    /// to the errors of failed downloads.
+    pub fn context(mut self, context: DownloadContext) -> Self {
+        self.context = Some(Arc::new(context));
+        self
+    }
+
     /// Set or replace the [ReporterFactory] in a builder style
     pub fn with_reporting<RRF: ReporterFactory>(self, rep_fac: RRF) -> Downloader<C, RRF> {
         self.with_reporting_arc(Arc::new(rep_fac))
@@ -72,6 +104,8 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
     pub fn with_reporting_arc<RRF: ReporterFactory>(self, rep_fac: Arc<RRF>) -> Downloader<C, RRF> {
         let Downloader {
             get_size_mode,
+            priority,
+            context,
             condow,
             ..
         } = self;
@@ -79,6 +113,8 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
         Downloader {
             condow,
             get_size_mode,
+            priority,
+            context,
             reporter_factory: rep_fac,
         }
     }
@@ -113,6 +149,8 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
             location,
             range,
             self.get_size_mode,
+            self.priority,
+            self.context.clone(),
             NoReporting,
         )
         .await
@@ -182,7 +220,37 @@ impl<C: CondowClient, RF: ReporterFactory> Downloader<C, RF> {
         range: R,
         reporter: RP,
     ) -> Result<StreamWithReport<ChunkStream, RP>, CondowError> {
-        machinery::download(&self.condow, location, range, self.get_size_mode, reporter).await
+        machinery::download(
+            &self.condow,
+            location,
+            range,
+            self.get_size_mode,
+            self.priority,
+            self.context.clone(),
+            reporter,
+        )
+        .await
+    }
+
+    /// Download the BLOB/range and track its [Progress].
+    ///
+    /// The returned receiver is notified whenever the [Progress] changes.
+    /// The download is also reported to a [Reporter] created by the
+    /// configured [ReporterFactory].
+    ///
+    /// The parts and the chunks streamed have the same ordering as
+    /// within the BLOB/range downloaded.
+    pub async fn download_with_progress<R: Into<DownloadRange>>(
+        &self,
+        location: C::Location,
+        range: R,
+    ) -> Result<(PartStream<ChunkStream>, watch::Receiver<Progress>), CondowError> {
+        let progress_reporter =
+            ProgressReporter::with_part_size(self.condow.config.part_size_bytes.into());
+        let progress = progress_reporter.subscribe();
+        let reporter = CompositeReporter(self.reporter_factory.make(&location), progress_reporter);
+        let stream = self.download_wrep(location, range, reporter).await?.stream;
+        Ok((stream, progress))
     }
 
     /// Get the size of a BLOB at location
@@ -225,6 +293,8 @@ impl<C: CondowClient, RF: ReporterFactory> Clone for Downloader<C, RF> {
             condow: self.condow.clone(),
             reporter_factory: Arc::clone(&self.reporter_factory),
             get_size_mode: self.get_size_mode,
+            priority: self.priority,
+            context: self.context.clone(),
         }
     }
 }
@@ -1,17 +1,36 @@
 //! Error types returned by Condow
-use std::fmt;
+use std::{fmt, io, sync::Arc};
 
 use thiserror::Error;
 
+use crate::{DownloadContext, InclusiveRange};
+
 /// The error type used by `condow`
 ///
 /// Further information is encoded with [CondowErrorKind].
+///
+/// Errors of a download may carry structured details like the
+/// failing part or the attempts made which can be inspected
+/// programmatically.
 #[derive(Error, Debug)]
 pub struct CondowError {
     msg: String,
     #[source]
     source: Option<anyhow::Error>,
     kind: CondowErrorKind,
+    context: Option<Arc<DownloadContext>>,
+    details: Option<Box<ErrorDetails>>,
+}
+
+/// Boxed to keep [CondowError] small
+#[derive(Debug, Default)]
+struct ErrorDetails {
+    location: Option<String>,
+    part: Option<(u64, InclusiveRange)>,
+    bytes_received: Option<u64>,
+    attempts: Option<usize>,
+    retry_errors: Vec<CondowError>,
+    io_kind: Option<io::ErrorKind>,
 }
 
 impl CondowError {
@@ -20,6 +39,8 @@ impl CondowError {
             msg: msg.into(),
             source: None,
             kind,
+            context: None,
+            details: None,
         }
     }
     pub fn new_invalid_range<T: Into<String>>(msg: T) -> Self {
@@ -34,6 +55,10 @@ impl CondowError {
         Self::new(msg, CondowErrorKind::AccessDenied)
     }
 
+    pub fn new_expired<T: Into<String>>(msg: T) -> Self {
+        Self::new(msg, CondowErrorKind::Expired)
+    }
+
     pub fn new_remote<T: Into<String>>(msg: T) -> Self {
         Self::new(msg, CondowErrorKind::Remote)
     }
@@ -51,6 +76,50 @@ impl CondowError {
         self
     }
 
+    /// Attach the [DownloadContext] of the download this error occurred in
+    ///
+    /// The context will be part of the displayed message.
+    pub fn with_context(mut self, context: Arc<DownloadContext>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Set the location of the BLOB
+    pub fn with_location<L: fmt::Display + ?Sized>(mut self, location: &L) -> Self {
+        self.details_mut().location = Some(location.to_string());
+        self
+    }
+
+    /// Set the index and the range of the failing part
+    pub fn with_part(mut self, part_index: u64, range: InclusiveRange) -> Self {
+        self.details_mut().part = Some((part_index, range));
+        self
+    }
+
+    /// Set the number of bytes received before the failure
+    pub fn with_bytes_received(mut self, bytes_received: u64) -> Self {
+        self.details_mut().bytes_received = Some(bytes_received);
+        self
+    }
+
+    /// Set the number of attempts (requests) made
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.details_mut().attempts = Some(attempts);
+        self
+    }
+
+    /// Set the errors of previous attempts which preceded this error
+    pub fn with_retry_errors(mut self, retry_errors: Vec<CondowError>) -> Self {
+        self.details_mut().retry_errors = retry_errors;
+        self
+    }
+
+    /// Set the [io::ErrorKind] of the I/O error which caused this error
+    pub fn with_io_kind(mut self, io_kind: io::ErrorKind) -> Self {
+        self.details_mut().io_kind = Some(io_kind);
+        self
+    }
+
     pub fn msg(&self) -> &str {
         &self.msg
     }
@@ -59,19 +128,79 @@ impl CondowError {
         self.kind
     }
 
+    /// The [DownloadContext] of the download this error occurred in
+    pub fn context(&self) -> Option<&DownloadContext> {
+        self.context.as_deref()
+    }
+
+    /// The location of the BLOB as a string
+    pub fn location(&self) -> Option<&str> {
+        self.details.as_ref()?.location.as_deref()
+    }
+
+    /// The index of the part which failed
+    pub fn part_index(&self) -> Option<u64> {
+        self.details
+            .as_ref()?
+            .part
+            .map(|(part_index, _)| part_index)
+    }
+
+    /// The range of the part which failed
+    pub fn range(&self) -> Option<InclusiveRange> {
+        self.details.as_ref()?.part.map(|(_, range)| range)
+    }
+
+    /// The number of bytes of the part received before the failure
+    pub fn bytes_received(&self) -> Option<u64> {
+        self.details.as_ref()?.bytes_received
+    }
+
+    /// The number of attempts (requests) made including retries and stream resumes
+    pub fn attempts(&self) -> Option<usize> {
+        self.details.as_ref()?.attempts
+    }
+
+    /// The errors of the failed attempts which preceded this error
+    ///
+    /// Includes the errors of broken streams which were attempted to be resumed.
+    pub fn retry_errors(&self) -> &[CondowError] {
+        self.details
+            .as_ref()
+            .map(|details| details.retry_errors.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The [io::ErrorKind] of the I/O error which caused this error
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        self.details.as_ref()?.io_kind
+    }
+
     pub fn is_retryable(&self) -> bool {
         self.kind.is_retryable()
     }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        self.details.get_or_insert_with(Default::default)
+    }
 }
 
 impl fmt::Display for CondowError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.msg)
+        write!(f, "{}", self.msg)?;
+        match self.context.as_deref() {
+            Some(context) if !context.is_empty() => write!(f, " [{}]", context),
+            _ => Ok(()),
+        }
     }
 }
 
 /// Specifies the kind of a [CondowError]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+///
+/// More kinds may be added in the future.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum CondowErrorKind {
     /// An inavlid range was encountered.
     ///
@@ -88,6 +217,12 @@ pub enum CondowErrorKind {
     ///
     /// Errors with this kind are **not retryable**
     AccessDenied,
+    /// The credentials of a request like a pre-signed URL have expired.
+    ///
+    /// Clients may refresh the credentials and send the request again.
+    ///
+    /// Errors with this kind are **not retryable**
+    Expired,
     /// The resource providing the BLOB encountered an error
     ///
     /// Errors with this kind are **retryable**
@@ -110,6 +245,7 @@ impl CondowErrorKind {
             InvalidRange => false,
             NotFound => false,
             AccessDenied => false,
+            Expired => false,
             Remote => true,
             Io => true,
             Other => false,
@@ -123,24 +259,120 @@ impl From<CondowErrorKind> for CondowError {
     }
 }
 
-impl From<std::io::Error> for CondowError {
-    fn from(io: std::io::Error) -> Self {
-        CondowError::new_io("io error").with_source(io)
+/// Maps [io::ErrorKind::NotFound] and [io::ErrorKind::PermissionDenied]
+/// to their counterparts. Everything else is [CondowErrorKind::Io].
+impl From<io::ErrorKind> for CondowErrorKind {
+    fn from(io_kind: io::ErrorKind) -> Self {
+        match io_kind {
+            io::ErrorKind::NotFound => CondowErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => CondowErrorKind::AccessDenied,
+            _ => CondowErrorKind::Io,
+        }
+    }
+}
+
+/// The [CondowErrorKind] is derived from the [io::ErrorKind].
+impl From<io::Error> for CondowError {
+    fn from(io: io::Error) -> Self {
+        let io_kind = io.kind();
+        CondowError::new(io.to_string(), io_kind.into())
+            .with_io_kind(io_kind)
+            .with_source(io)
     }
 }
 
+/// A [CondowError] carried as the source of an [IoError] is returned as is.
+///
+/// Otherwise the error will be of [CondowErrorKind::Io] since the
+/// error occurred "on the wire".
 impl From<IoError> for CondowError {
     fn from(io: IoError) -> Self {
-        CondowError::new_io(io.0)
+        let IoError { msg, kind, source } = io;
+        let err = match source.map(|source| source.downcast::<CondowError>()) {
+            Some(Ok(err)) => return *err,
+            Some(Err(source)) => CondowError::new_io(msg).with_source(anyhow::anyhow!(source)),
+            None => CondowError::new_io(msg),
+        };
+        match kind {
+            Some(kind) => err.with_io_kind(kind),
+            None => err,
+        }
+    }
+}
+
+/// Converts to an [io::Error] with the [io::ErrorKind] of the
+/// [CondowError] if there is one. Otherwise the [io::ErrorKind] is
+/// derived from the [CondowErrorKind].
+impl From<CondowError> for io::Error {
+    fn from(err: CondowError) -> Self {
+        let io_kind = err.io_kind().unwrap_or(match err.kind() {
+            CondowErrorKind::InvalidRange => io::ErrorKind::InvalidInput,
+            CondowErrorKind::NotFound => io::ErrorKind::NotFound,
+            CondowErrorKind::AccessDenied | CondowErrorKind::Expired => {
+                io::ErrorKind::PermissionDenied
+            }
+            _ => io::ErrorKind::Other,
+        });
+        io::Error::new(io_kind, err)
     }
 }
 
+/// The error of a stream of bytes
+///
+/// May carry the [io::ErrorKind] and the error which caused it.
 #[derive(Error, Debug)]
-#[error("io error: {0}")]
-pub struct IoError(pub String);
+#[error("io error: {msg}")]
+pub struct IoError {
+    msg: String,
+    kind: Option<io::ErrorKind>,
+    #[source]
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl IoError {
+    pub fn new<T: Into<String>>(msg: T) -> Self {
+        Self {
+            msg: msg.into(),
+            kind: None,
+            source: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: io::ErrorKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_source<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+        mut self,
+        err: E,
+    ) -> Self {
+        self.source = Some(err.into());
+        self
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn kind(&self) -> Option<io::ErrorKind> {
+        self.kind
+    }
+
+    /// A [CondowError] with the message and the kind but without the source
+    pub(crate) fn to_condow_error(&self) -> CondowError {
+        let err = CondowError::new_io(self.msg.clone());
+        match self.kind {
+            Some(kind) => err.with_io_kind(kind),
+            None => err,
+        }
+    }
+}
 
-impl From<std::io::Error> for IoError {
-    fn from(io: std::io::Error) -> Self {
-        IoError(io.to_string())
+impl From<io::Error> for IoError {
+    fn from(io: io::Error) -> Self {
+        IoError::new(io.to_string())
+            .with_kind(io.kind())
+            .with_source(io)
     }
 }
@@ -420,3 +420,17 @@ macro_rules! env_ctors {
         }
     };
 }
+
+/// Spawns a task on the current runtime
+///
+/// With the feature `tracing` enabled the task is instrumented
+/// with the current span so that it inherits the caller's span.
+pub(crate) fn spawn<F>(future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "tracing")]
+    let future = tracing::Instrument::in_current_span(future);
+    tokio::spawn(future)
+}
@@ -15,6 +15,10 @@
 //!
 //! * [condow_rusoto] for downloading AWS S3 via the rusoto
 //! * [condow_fs] for using async file access via [tokio]
+//! * [condow_gcs] for downloading from Google Cloud Storage
+//! * [condow_azure] for downloading from Azure Blob Storage
+//! * [condow_aws_sdk] for downloading AWS S3 via the official AWS SDK
+//! * [condow_url] for downloading from (pre-signed) URLs
 //!
 //! All that is required to add more "services" is to implement
 //! the [CondowClient] trait.
@@ -34,32 +38,46 @@
 //!
 //! [condow_rusoto]:https://docs.rs/condow_rusoto
 //! [condow_fs]:https://docs.rs/condow_fs
+//! [condow_gcs]:https://docs.rs/condow_gcs
+//! [condow_azure]:https://docs.rs/condow_azure
+//! [condow_aws_sdk]:https://docs.rs/condow_aws_sdk
+//! [condow_url]:https://docs.rs/condow_url
 use std::sync::Arc;
 
-use futures::{future::BoxFuture, FutureExt, Stream};
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt, Stream,
+};
 
+use blocking::BlockingCondow;
 use condow_client::CondowClient;
 use config::{AlwaysGetSize, ClientRetryWrapper, Config};
 use errors::CondowError;
-use reader::RandomAccessReader;
+use machinery::Limits;
+use reader::{PositionalReader, RandomAccessReader};
 use reporter::{NoReporting, Reporter, ReporterFactory};
 use streams::{ChunkStream, ChunkStreamItem, PartStream};
 
 #[macro_use]
 pub(crate) mod helpers;
+pub mod blocking;
 pub mod condow_client;
 pub mod config;
+mod download_context;
 mod download_range;
 mod download_session;
 mod downloader;
 pub mod errors;
 pub mod logging;
 mod machinery;
+mod memory_budget;
 pub mod reader;
 pub mod reporter;
 mod retry;
+mod scheduler;
 pub mod streams;
 
+pub use download_context::*;
 pub use download_range::*;
 pub use download_session::*;
 pub use downloader::*;
@@ -118,6 +136,71 @@ where
     fn reader_with_length(&self, location: L, length: u64) -> RandomAccessReader<Self, L>
     where
         Self: Sized;
+
+    /// Download multiple BLOB ranges concurrently into the given buffers
+    ///
+    /// Each range is downloaded straight into the buffer paired with it.
+    /// The chunks are written at their offsets within the range so no bytes
+    /// are buffered or concatenated on the way.
+    ///
+    /// Returns the number of bytes written into each buffer in the order
+    /// of the given pairs.
+    ///
+    /// Fails if a buffer is too small for its range.
+    fn download_into_buffers<'a, R, I>(
+        &'a self,
+        location: L,
+        targets: I,
+    ) -> BoxFuture<'a, Result<Vec<usize>, CondowError>>
+    where
+        Self: Sized + Sync,
+        R: Into<DownloadRange> + Send + Sync + 'static,
+        I: IntoIterator<Item = (R, &'a mut [u8])>,
+    {
+        let downloads = targets
+            .into_iter()
+            .map(|(range, buffer)| {
+                let location = location.clone();
+                async move {
+                    self.download_chunks(location, range)
+                        .await?
+                        .write_buffer(buffer)
+                        .await
+                }
+            })
+            .collect::<Vec<_>>();
+
+        future::try_join_all(downloads).boxed()
+    }
+
+    /// Creates a [PositionalReader] for the given location
+    ///
+    /// This function will query the size of the BLOB. If the size is already known
+    /// call [Downloads::positional_reader_with_length]
+    fn positional_reader<'a>(
+        &'a self,
+        location: L,
+    ) -> BoxFuture<'a, Result<PositionalReader<Self, L>, CondowError>>
+    where
+        Self: Sized + Clone + Sync,
+    {
+        let me = self;
+        async move {
+            let length = me.get_size(location.clone()).await?;
+            Ok(me.positional_reader_with_length(location, length))
+        }
+        .boxed()
+    }
+
+    /// Creates a [PositionalReader] for the given location
+    ///
+    /// This function will create a new reader immediately
+    fn positional_reader_with_length(&self, location: L, length: u64) -> PositionalReader<Self, L>
+    where
+        Self: Sized + Clone,
+    {
+        PositionalReader::new_with_length(self.clone(), location, length)
+    }
 }
 
 /// The CONcurrent DOWnloader
@@ -137,6 +220,7 @@ where
 pub struct Condow<C> {
     client: ClientRetryWrapper<C>,
     config: Config,
+    limits: Limits,
 }
 
 impl<C: CondowClient> Clone for Condow<C> {
@@ -144,6 +228,7 @@ impl<C: CondowClient> Clone for Condow<C> {
         Self {
             client: self.client.clone(),
             config: self.config.clone(),
+            limits: self.limits.clone(),
         }
     }
 }
@@ -156,10 +241,22 @@ impl<C: CondowClient> Condow<C> {
         let config = config.validated()?;
         Ok(Self {
             client: ClientRetryWrapper::new(client, config.retries.clone()),
+            limits: Limits::from_config(&config),
             config,
         })
     }
 
+    /// Returns the number of bytes currently reserved from the memory budget
+    /// by all downloads of this instance and its clones.
+    ///
+    /// Returns `None` if no memory budget was configured.
+    pub fn memory_budget_bytes_in_use(&self) -> Option<u64> {
+        self.limits
+            .memory_budget
+            .as_ref()
+            .map(|budget| budget.bytes_in_use())
+    }
+
     /// Create a reusable [Downloader] which has a richer API.
     pub fn downloader(&self) -> Downloader<C, NoReporting> {
         Downloader::new(self.clone())
@@ -191,6 +288,13 @@ impl<C: CondowClient> Condow<C> {
         DownloadSession::new_with_reporting_arc(self.clone(), rep_fac)
     }
 
+    /// Create a [BlockingCondow] which owns its own runtime.
+    ///
+    /// See [blocking] for details.
+    pub fn blocking(&self) -> Result<BlockingCondow<Self>, CondowError> {
+        BlockingCondow::new(self.clone())
+    }
+
     /// Download a BLOB range (potentially) concurrently
     ///
     /// Returns a stream of [Chunk](streams::Chunk)s.
@@ -199,9 +303,17 @@ impl<C: CondowClient> Condow<C> {
         location: C::Location,
         range: R,
     ) -> Result<ChunkStream, CondowError> {
-        machinery::download(self, location, range, GetSizeMode::Default, NoReporting)
-            .await
-            .map(|o| o.into_stream())
+        machinery::download(
+            self,
+            location,
+            range,
+            GetSizeMode::Default,
+            DownloadPriority::default(),
+            None,
+            NoReporting,
+        )
+        .await
+        .map(|o| o.into_stream())
     }
 
     /// Download a BLOB range (potentially) concurrently
@@ -212,10 +324,17 @@ impl<C: CondowClient> Condow<C> {
         location: C::Location,
         range: R,
     ) -> Result<PartStream<ChunkStream>, CondowError> {
-        let chunk_stream =
-            machinery::download(self, location, range, GetSizeMode::Default, NoReporting)
-                .await
-                .map(|o| o.into_stream())?;
+        let chunk_stream = machinery::download(
+            self,
+            location,
+            range,
+            GetSizeMode::Default,
+            DownloadPriority::default(),
+            None,
+            NoReporting,
+        )
+        .await
+        .map(|o| o.into_stream())?;
         PartStream::from_chunk_stream(chunk_stream)
     }
 
@@ -364,5 +483,22 @@ impl Default for GetSizeMode {
     }
 }
 
+/// The priority of a download
+///
+/// Priorities only have an effect if the number of parts downloaded
+/// at the same time by all downloads is limited via
+/// [Config::shared_concurrency](config::Config::shared_concurrency).
+/// Parts of downloads with a high priority are then
+/// dispatched ahead of those with a low priority. Downloads with a low priority
+/// get a guaranteed minimum share of the shared concurrency.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DownloadPriority {
+    /// The default
+    #[default]
+    High,
+    /// E.g. for background prefetching
+    Low,
+}
+
 #[cfg(test)]
 mod condow_tests;
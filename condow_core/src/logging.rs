@@ -92,6 +92,10 @@ impl Logger {
 }
 
 impl Reporter for Logger {
+    fn download_context(&self, context: &crate::DownloadContext) {
+        self.debug(format_args!("Download context: {}", context));
+    }
+
     fn effective_range(&self, _range: crate::InclusiveRange) {}
 
     fn download_started(&self) {
@@ -133,12 +137,44 @@ impl Reporter for Logger {
         ));
     }
 
+    fn size_request_failed(
+        &self,
+        _location: &dyn fmt::Display,
+        error: &CondowError,
+        time: std::time::Duration,
+    ) {
+        self.warn(format_args!(
+            "Size request failed after {:?} with `{}`",
+            time, error
+        ));
+    }
+
+    fn request_completed(
+        &self,
+        _location: &dyn fmt::Display,
+        attempt: usize,
+        latency: std::time::Duration,
+        time_to_first_byte: std::time::Duration,
+    ) {
+        self.debug(format_args!(
+            "Request (attempt {}) completed (latency: {:?}, time to first byte: {:?})",
+            attempt, latency, time_to_first_byte
+        ));
+    }
+
     fn panic_detected(&self, msg: &str) {
         self.warn(format_args!("panic detected '{}'", msg));
     }
 
     fn queue_full(&self) {}
 
+    fn memory_budget_exhausted(&self, part_index: u64, n_bytes: u64) {
+        self.debug(format_args!(
+            "Memory budget exhausted. Part {} waits for {} bytes",
+            part_index, n_bytes
+        ));
+    }
+
     fn chunk_completed(
         &self,
         _part_index: u64,
@@ -266,6 +302,18 @@ impl LoggerFactoryBuilder {
         StdErrConfigurator { builder: self }
     }
 
+    /// Returns a [LogConfigurator] to configure logging via the `log` crate.
+    ///
+    /// Requires the feature `log`.
+    #[cfg(feature = "log")]
+    pub fn log(self) -> LogConfigurator {
+        LogConfigurator {
+            builder: self,
+            target: LogConfigurator::DEFAULT_TARGET.to_string(),
+            levels: Vec::new(),
+        }
+    }
+
     /// Create the logging factory
     pub fn finish(self) -> LoggerFactory {
         LoggerFactory {
@@ -407,6 +455,109 @@ impl StdErrConfigurator {
     }
 }
 
+/// Configure logging via the `log` crate
+///
+/// The levels of the [Logger] are mapped to the corresponding levels
+/// of the `log` crate. The location of the download is attached to each
+/// record as the key-value pair `location`.
+///
+/// Requires the feature `log`.
+#[cfg(feature = "log")]
+pub struct LogConfigurator {
+    builder: LoggerFactoryBuilder,
+    target: String,
+    levels: Vec<log::Level>,
+}
+
+#[cfg(feature = "log")]
+impl LogConfigurator {
+    /// The default target of the log records
+    pub const DEFAULT_TARGET: &'static str = "condow";
+
+    /// Set the target of the log records
+    pub fn target<T: Into<String>>(mut self, target: T) -> Self {
+        self.target = target.into();
+        self
+    }
+
+    /// Enable debug logging with [log::Level::Debug]
+    pub fn debug(self) -> Self {
+        self.level(log::Level::Debug)
+    }
+
+    /// Enable info logging with [log::Level::Info]
+    pub fn info(self) -> Self {
+        self.level(log::Level::Info)
+    }
+
+    /// Enable warning logging with [log::Level::Warn]
+    pub fn warn(self) -> Self {
+        self.level(log::Level::Warn)
+    }
+
+    /// Enable error logging with [log::Level::Error]
+    pub fn error(self) -> Self {
+        self.level(log::Level::Error)
+    }
+
+    /// Enable warn and error logging
+    pub fn warn_error(self) -> Self {
+        self.warn().error()
+    }
+
+    /// Enable info, warn and error logging
+    pub fn info_warn_error(self) -> Self {
+        self.info().warn().error()
+    }
+
+    /// Enable debug, info, warn and error logging
+    ///
+    /// Records are filtered by the configured `log` backend.
+    pub fn all(self) -> Self {
+        self.debug().info().warn().error()
+    }
+
+    /// Get back to the [LoggerFactoryBuilder]
+    pub fn done(self) -> LoggerFactoryBuilder {
+        let target: Arc<str> = Arc::from(self.target);
+        self.levels
+            .into_iter()
+            .fold(self.builder, |builder, level| {
+                let target = Arc::clone(&target);
+                let log = Arc::new(move |loc: &str, msg: fmt::Arguments| {
+                    if level <= log::max_level() {
+                        log::logger().log(
+                            &log::Record::builder()
+                                .target(&target)
+                                .level(level)
+                                .args(msg)
+                                .key_values(&[("location", loc)])
+                                .build(),
+                        );
+                    }
+                });
+
+                match level {
+                    log::Level::Debug | log::Level::Trace => builder.on_debug_dyn(log),
+                    log::Level::Info => builder.on_info_dyn(log),
+                    log::Level::Warn => builder.on_warn_dyn(log),
+                    log::Level::Error => builder.on_error_dyn(log),
+                }
+            })
+    }
+
+    /// Build the [LoggerFactory]
+    pub fn finish(self) -> LoggerFactory {
+        self.done().finish()
+    }
+
+    /// The callbacks are created once the target is known
+    fn level(mut self, level: log::Level) -> Self {
+        self.levels.push(level);
+        self
+    }
+}
+
 /// A factory for [Logger]s which can be used to create
 /// [Logger]s for downloads.
 ///
@@ -438,3 +589,69 @@ struct Inner {
     on_warn: Option<Arc<dyn Fn(&str, fmt::Arguments) + Send + Sync + 'static>>,
     on_error: Option<Arc<dyn Fn(&str, fmt::Arguments) + Send + Sync + 'static>>,
 }
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use log::{kv, Log, Metadata, Record};
+
+    use crate::reporter::Reporter;
+
+    use super::*;
+
+    /// Records as "<target>|<level>|<location>|<message>"
+    static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct TestLog;
+
+    impl Log for TestLog {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let location = record
+                .key_values()
+                .get(kv::Key::from("location"))
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            RECORDS.lock().unwrap().push(format!(
+                "{}|{}|{}|{}",
+                record.target(),
+                record.level(),
+                location,
+                record.args()
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn logs_via_the_log_crate() {
+        log::set_logger(&TestLog).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+
+        let factory = LoggerFactoryBuilder::default()
+            .log()
+            .all()
+            .target("my_target")
+            .finish();
+        let logger = factory.make(&"bucket/key");
+
+        logger.part_started(0, InclusiveRange(0, 9));
+        logger.download_started();
+        logger.panic_detected("boom");
+        logger.download_failed(None);
+
+        assert_eq!(
+            *RECORDS.lock().unwrap(),
+            vec![
+                "my_target|INFO|bucket/key|Download started",
+                "my_target|WARN|bucket/key|panic detected 'boom'",
+                "my_target|ERROR|bucket/key|Download failed",
+            ]
+        );
+    }
+}
@@ -11,8 +11,11 @@ use crate::{
     condow_client::CondowClient,
     config::{ClientRetryWrapper, Config},
     machinery::range_stream::RangeRequest,
+    memory_budget::BudgetAccount,
     reporter::Reporter,
+    scheduler::SlotRequester,
     streams::ChunkStreamItem,
+    DownloadContext,
 };
 
 use super::{
@@ -25,16 +28,21 @@ pub(crate) struct ConcurrentDownloader<R: Reporter> {
     counter: usize,
     kill_switch: KillSwitch,
     config: Config,
+    budget_account: Option<Arc<BudgetAccount>>,
     reporter: R,
 }
 
 impl<R: Reporter> ConcurrentDownloader<R> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<C: CondowClient>(
         n_concurrent: usize,
         results_sender: UnboundedSender<ChunkStreamItem>,
         client: ClientRetryWrapper<C>,
         config: Config,
         location: C::Location,
+        budget_account: Option<Arc<BudgetAccount>>,
+        slots: Option<SlotRequester>,
+        context: Option<Arc<DownloadContext>>,
         reporter: R,
     ) -> Self {
         let started_at = Instant::now();
@@ -46,10 +54,12 @@ impl<R: Reporter> ConcurrentDownloader<R> {
                     client.clone(),
                     location.clone(),
                     config.buffer_size.into(),
+                    slots.clone(),
                     DownloaderContext::new(
                         results_sender.clone(),
                         Arc::clone(&counter),
                         kill_switch.clone(),
+                        context.clone(),
                         reporter.clone(),
                         started_at,
                     ),
@@ -62,6 +72,7 @@ impl<R: Reporter> ConcurrentDownloader<R> {
             counter: 0,
             kill_switch,
             config,
+            budget_account,
             reporter,
         }
     }
@@ -73,6 +84,11 @@ impl<R: Reporter> ConcurrentDownloader<R> {
         self.reporter.download_started();
         let mut ranges_stream = Box::pin(ranges_stream);
         while let Some(mut range_request) = ranges_stream.next().await {
+            if self.reserve_memory(&range_request).await.is_err() {
+                self.kill_switch.push_the_button();
+                return Err(());
+            }
+
             let mut attempt = 1;
 
             let buffers_full_delay = self.config.buffers_full_delay_ms.into();
@@ -104,4 +120,34 @@ impl<R: Reporter> ConcurrentDownloader<R> {
         }
         Ok(())
     }
+
+    /// Reserve the bytes of the part from the memory budget if there is one
+    ///
+    /// Waits if the budget is exhausted. Fails if the consumer of the download is gone.
+    async fn reserve_memory(&self, range_request: &RangeRequest) -> Result<(), ()> {
+        let account = if let Some(account) = self.budget_account.as_ref() {
+            account
+        } else {
+            return Ok(());
+        };
+
+        let n_bytes = range_request.blob_range.len();
+        let started_at = Instant::now();
+        let n_bytes_reserved = if let Some(n_bytes_reserved) = account.try_reserve(n_bytes)? {
+            n_bytes_reserved
+        } else {
+            self.reporter
+                .memory_budget_exhausted(range_request.part_index, n_bytes);
+            account.reserve(n_bytes).await?
+        };
+
+        self.reporter.memory_reserved(
+            range_request.part_index,
+            n_bytes_reserved,
+            account.bytes_in_use(),
+            started_at.elapsed(),
+        );
+
+        Ok(())
+    }
 }
@@ -10,8 +10,11 @@ use futures::{channel::mpsc::UnboundedSender, Stream};
 use crate::{
     condow_client::CondowClient,
     config::{ClientRetryWrapper, Config},
+    memory_budget::BudgetAccount,
     reporter::Reporter,
+    scheduler::SlotRequester,
     streams::ChunkStreamItem,
+    DownloadContext,
 };
 
 use self::concurrent::ConcurrentDownloader;
@@ -22,6 +25,7 @@ mod concurrent;
 mod sequential;
 
 /// Download the parst of a BLOB concurrently
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn download_concurrently<C: CondowClient, R: Reporter>(
     ranges_stream: impl Stream<Item = RangeRequest>,
     n_concurrent: usize,
@@ -29,6 +33,9 @@ pub(crate) async fn download_concurrently<C: CondowClient, R: Reporter>(
     client: ClientRetryWrapper<C>,
     config: Config,
     location: C::Location,
+    budget_account: Option<Arc<BudgetAccount>>,
+    slots: Option<SlotRequester>,
+    context: Option<Arc<DownloadContext>>,
     reporter: R,
 ) -> Result<(), ()> {
     let mut downloader = ConcurrentDownloader::new(
@@ -37,6 +44,9 @@ pub(crate) async fn download_concurrently<C: CondowClient, R: Reporter>(
         client,
         config.clone(),
         location,
+        budget_account,
+        slots,
+        context,
         reporter,
     );
 
@@ -1,6 +1,7 @@
 //! Download enqueued [RangeRequest]s sequentially
 
 use std::{
+    fmt,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -16,10 +17,12 @@ use futures::{
 use crate::{
     condow_client::{CondowClient, DownloadSpec},
     config::ClientRetryWrapper,
-    errors::{CondowError, IoError},
+    errors::CondowError,
     machinery::range_stream::RangeRequest,
     reporter::Reporter,
+    scheduler::SlotRequester,
     streams::{BytesStream, Chunk, ChunkStreamItem},
+    DownloadContext,
 };
 
 use super::KillSwitch;
@@ -30,6 +33,8 @@ use super::KillSwitch;
 /// The parts to be downloaded are enqueued in a channel.
 ///
 /// Results are pushed into a channel via the [DownloaderContext].
+/// Chunks are forwarded without waiting for the memory budget since
+/// all bytes of a part were reserved before the part was enqueued.
 ///
 /// Usually one `SequentialDownloader` is created for each level of
 /// concurrency.  
@@ -42,13 +47,27 @@ impl SequentialDownloader {
         client: ClientRetryWrapper<C>,
         location: C::Location,
         buffer_size: usize,
+        slots: Option<SlotRequester>,
         mut context: DownloaderContext<R>,
     ) -> Self {
         let (request_sender, request_receiver) = mpsc::channel::<RangeRequest>(buffer_size);
 
-        tokio::spawn(async move {
+        crate::helpers::spawn(async move {
             let mut request_receiver = Box::pin(request_receiver);
             while let Some(range_request) = request_receiver.next().await {
+                // Held until the part is downloaded completely
+                let _slot = if let Some(slots) = slots.as_ref() {
+                    match slots.acquire().await {
+                        Ok(slot) => Some(slot),
+                        Err(err) => {
+                            context.send_err(err);
+                            return;
+                        }
+                    }
+                } else {
+                    None
+                };
+
                 if context.kill_switch.is_pushed() {
                     // That failed task should have already sent an error...
                     // ...but we do not want to prove that...
@@ -67,20 +86,20 @@ impl SequentialDownloader {
                     .await
                 {
                     Ok((bytes_stream, _total_bytes)) => {
-                        if consume_and_dispatch_bytes(bytes_stream, &mut context, range_request)
-                            .await
-                            .is_err()
+                        if consume_and_dispatch_bytes(
+                            bytes_stream,
+                            &location,
+                            &mut context,
+                            range_request,
+                        )
+                        .await
+                        .is_err()
                         {
                             return;
                         }
                     }
                     Err(err) => {
-                        context.reporter.part_failed(
-                            &err,
-                            range_request.part_index,
-                            &range_request.blob_range,
-                        );
-                        context.send_err(err);
+                        context.send_part_failed(err, &location, &range_request, 0);
                         return;
                     }
                 };
@@ -111,6 +130,7 @@ pub(crate) struct DownloaderContext<R: Reporter> {
     started_at: Instant,
     counter: Arc<AtomicUsize>,
     kill_switch: KillSwitch,
+    download_context: Option<Arc<DownloadContext>>,
     reporter: R,
     results_sender: UnboundedSender<ChunkStreamItem>,
     completed: bool,
@@ -121,6 +141,7 @@ impl<R: Reporter> DownloaderContext<R> {
         results_sender: UnboundedSender<ChunkStreamItem>,
         counter: Arc<AtomicUsize>,
         kill_switch: KillSwitch,
+        download_context: Option<Arc<DownloadContext>>,
         reporter: R,
         started_at: Instant,
     ) -> Self {
@@ -129,6 +150,7 @@ impl<R: Reporter> DownloaderContext<R> {
             counter,
             reporter,
             kill_switch,
+            download_context,
             started_at,
             results_sender,
             completed: false,
@@ -147,11 +169,45 @@ impl<R: Reporter> DownloaderContext<R> {
 
     /// Send an error and mark as completed
     pub fn send_err(&mut self, err: CondowError) {
+        let err = self.attach_download_context(err);
         let _ = self.results_sender.unbounded_send(Err(err));
         self.completed = true;
         self.kill_switch.push_the_button();
     }
 
+    /// Report a failed part, send the error and mark as completed
+    ///
+    /// The location, the part and the bytes received are attached to the error.
+    pub fn send_part_failed(
+        &mut self,
+        err: CondowError,
+        location: &(dyn fmt::Display + Send + Sync),
+        range_request: &RangeRequest,
+        bytes_received: u64,
+    ) {
+        let err = if err.location().is_none() {
+            err.with_location(location)
+        } else {
+            err
+        };
+        let err = err
+            .with_part(range_request.part_index, range_request.blob_range)
+            .with_bytes_received(bytes_received);
+        let err = self.attach_download_context(err);
+        self.reporter
+            .part_failed(&err, range_request.part_index, &range_request.blob_range);
+        self.send_err(err);
+    }
+
+    fn attach_download_context(&self, err: CondowError) -> CondowError {
+        match self.download_context.as_ref() {
+            Some(download_context) if err.context().is_none() => {
+                err.with_context(Arc::clone(download_context))
+            }
+            _ => err,
+        }
+    }
+
     /// Mark the download as complete if successful
     ///
     /// This must be called upon succesful termination of an [InternalDownloader].
@@ -174,6 +230,7 @@ impl<R: Reporter> Drop for DownloaderContext<R> {
             } else {
                 CondowError::new_other("download ended unexpectetly")
             };
+            let err = self.attach_download_context(err);
             let _ = self.results_sender.unbounded_send(Err(err));
         }
 
@@ -200,6 +257,7 @@ impl<R: Reporter> Drop for DownloaderContext<R> {
 /// [Bytes]: bytes::bytes
 async fn consume_and_dispatch_bytes<R: Reporter>(
     mut bytes_stream: BytesStream,
+    location: &(dyn fmt::Display + Send + Sync),
     context: &mut DownloaderContext<R>,
     range_request: RangeRequest,
 ) -> Result<(), ()> {
@@ -231,12 +289,7 @@ async fn consume_and_dispatch_bytes<R: Reporter>(
                         range_request.blob_range.len(),
                         bytes_received
                     ));
-                    context.reporter.part_failed(
-                        &err,
-                        range_request.part_index,
-                        &range_request.blob_range,
-                    );
-                    context.send_err(err);
+                    context.send_part_failed(err, location, &range_request, bytes_received);
                     return Err(());
                 }
 
@@ -258,13 +311,8 @@ async fn consume_and_dispatch_bytes<R: Reporter>(
                 chunk_index += 1;
                 offset_in_range += n_bytes as u64;
             }
-            Err(IoError(msg)) => {
-                context.reporter.part_failed(
-                    &CondowError::new_io(msg.clone()),
-                    range_request.part_index,
-                    &range_request.blob_range,
-                );
-                context.send_err(CondowError::new_io(msg));
+            Err(io_error) => {
+                context.send_part_failed(io_error.into(), location, &range_request, bytes_received);
                 return Err(());
             }
         }
@@ -286,10 +334,7 @@ async fn consume_and_dispatch_bytes<R: Reporter>(
             range_request.blob_range.len(),
             bytes_received
         ));
-        context
-            .reporter
-            .part_failed(&err, range_request.part_index, &range_request.blob_range);
-        let _ = context.send_err(err);
+        context.send_part_failed(err, location, &range_request, bytes_received);
         Err(())
     } else {
         Ok(())
@@ -372,10 +417,12 @@ mod tests {
             client.into(),
             NoLocation,
             config.buffer_size.into(),
+            None,
             DownloaderContext::new(
                 results_sender,
                 Arc::new(AtomicUsize::new(0)),
                 KillSwitch::new(),
+                None,
                 NoReporting,
                 Instant::now(),
             ),
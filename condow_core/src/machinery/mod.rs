@@ -1,30 +1,77 @@
 //! Streams for handling downloads
 
+use std::sync::Arc;
+
 use crate::condow_client::CondowClient;
 use crate::config::{ClientRetryWrapper, Config};
 use crate::errors::CondowError;
+use crate::memory_budget::{BudgetConsumer, MemoryBudget};
+use crate::scheduler::{Scheduler, SlotRequester};
 use crate::streams::{BytesHint, ChunkStream};
 use crate::Reporter;
-use crate::{Condow, DownloadRange, GetSizeMode, InclusiveRange, StreamWithReport};
+use crate::{
+    Condow, DownloadContext, DownloadPriority, DownloadRange, GetSizeMode, InclusiveRange,
+    StreamWithReport,
+};
 
 use self::range_stream::RangeStream;
 
 mod download;
 mod range_stream;
 
+/// Limits shared by all downloads of a [Condow] and its clones
+#[derive(Clone, Default)]
+pub(crate) struct Limits {
+    pub memory_budget: Option<MemoryBudget>,
+    pub scheduler: Option<Scheduler>,
+}
+
+impl Limits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            memory_budget: config
+                .memory_budget_bytes
+                .map(|bytes| MemoryBudget::new(bytes.into())),
+            scheduler: config.shared_concurrency.map(|n_slots| {
+                Scheduler::new(
+                    n_slots.into_inner(),
+                    config.low_priority_min_share_percent.into_inner(),
+                )
+            }),
+        }
+    }
+}
+
 pub async fn download<C: CondowClient, DR: Into<DownloadRange>, R: Reporter>(
     condow: &Condow<C>,
     location: C::Location,
     range: DR,
     get_size_mode: GetSizeMode,
+    priority: DownloadPriority,
+    context: Option<Arc<DownloadContext>>,
     reporter: R,
 ) -> Result<StreamWithReport<ChunkStream, R>, CondowError> {
-    download_range(condow, location, range, get_size_mode, reporter.clone())
-        .await
-        .map_err(|err| {
-            reporter.download_failed(None);
-            err
-        })
+    if let Some(context) = context.as_deref() {
+        reporter.download_context(context);
+    }
+
+    download_range(
+        condow,
+        location,
+        range,
+        get_size_mode,
+        priority,
+        context.clone(),
+        reporter.clone(),
+    )
+    .await
+    .map_err(|err| {
+        reporter.download_failed(None);
+        match context {
+            Some(context) => err.with_context(context),
+            None => err,
+        }
+    })
 }
 
 pub async fn download_range<C: CondowClient, DR: Into<DownloadRange>, R: Reporter>(
@@ -32,6 +79,8 @@ pub async fn download_range<C: CondowClient, DR: Into<DownloadRange>, R: Reporte
     location: C::Location,
     range: DR,
     get_size_mode: GetSizeMode,
+    priority: DownloadPriority,
+    context: Option<Arc<DownloadContext>>,
     reporter: R,
 ) -> Result<StreamWithReport<ChunkStream, R>, CondowError> {
     let range: DownloadRange = range.into();
@@ -73,6 +122,9 @@ pub async fn download_range<C: CondowClient, DR: Into<DownloadRange>, R: Reporte
         inclusive_range,
         bytes_hint,
         condow.config.clone(),
+        condow.limits.clone(),
+        priority,
+        context,
         reporter.clone(),
     )
     .await?;
@@ -80,12 +132,16 @@ pub async fn download_range<C: CondowClient, DR: Into<DownloadRange>, R: Reporte
     Ok(StreamWithReport { reporter, stream })
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn download_chunks<C: CondowClient, R: Reporter>(
     client: ClientRetryWrapper<C>,
     location: C::Location,
     range: InclusiveRange,
     bytes_hint: BytesHint,
     config: Config,
+    limits: Limits,
+    priority: DownloadPriority,
+    context: Option<Arc<DownloadContext>>,
     reporter: R,
 ) -> Result<ChunkStream, CondowError> {
     reporter.effective_range(range);
@@ -96,7 +152,13 @@ async fn download_chunks<C: CondowClient, R: Reporter>(
         panic!("n_parts must not be 0. This is a bug");
     }
 
-    let (chunk_stream, sender) = ChunkStream::new(bytes_hint);
+    let (mut chunk_stream, sender) = ChunkStream::new(bytes_hint);
+
+    let budget_account = limits.memory_budget.map(|budget| {
+        let account = budget.account();
+        chunk_stream.set_budget(BudgetConsumer::new(account.clone()));
+        account
+    });
 
     if n_parts > usize::MAX as u64 {
         return Err(CondowError::new_other(
@@ -105,7 +167,7 @@ async fn download_chunks<C: CondowClient, R: Reporter>(
     }
     let n_parts = n_parts as usize;
 
-    tokio::spawn(async move {
+    crate::helpers::spawn(async move {
         download::download_concurrently(
             ranges_stream,
             config.max_concurrency.into_inner().min(n_parts),
@@ -113,6 +175,11 @@ async fn download_chunks<C: CondowClient, R: Reporter>(
             client,
             config,
             location,
+            budget_account,
+            limits
+                .scheduler
+                .map(|scheduler| SlotRequester::new(scheduler, priority)),
+            context,
             reporter,
         )
         .await
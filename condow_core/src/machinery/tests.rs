@@ -28,6 +28,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -62,6 +64,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -97,6 +101,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -132,6 +138,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -170,6 +178,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -206,6 +216,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -244,6 +256,8 @@ mod download {
             NoLocation,
             0..100,
             crate::GetSizeMode::Required,
+            crate::DownloadPriority::default(),
+            None,
             NoReporting,
         )
         .await;
@@ -285,6 +299,9 @@ mod download_chunks {
             range,
             bytes_hint,
             config,
+            Default::default(),
+            Default::default(),
+            None,
             NoReporting,
         )
         .await
@@ -316,6 +333,9 @@ mod download_chunks {
             range,
             bytes_hint,
             config,
+            Default::default(),
+            Default::default(),
+            None,
             NoReporting,
         )
         .await
@@ -347,6 +367,9 @@ mod download_chunks {
             range,
             bytes_hint,
             config,
+            Default::default(),
+            Default::default(),
+            None,
             NoReporting,
         )
         .await
@@ -0,0 +1,248 @@
+//! A global budget for the bytes of downloads held in memory
+//!
+//! Bytes are reserved for a part before it is scheduled for download
+//! and released once they were handed to the consumer of a download.
+//! Since parts are reserved in the order they appear in the BLOB,
+//! a [PartStream](crate::streams::PartStream) waiting for the next part
+//! in order will never wait for a part which did not get a reservation.
+//!
+//! Parts are never larger than the budget (see [Config::validated]).
+//! Since all bytes of a part are reserved before it is enqueued, forwarding
+//! the chunks of a part never exceeds the budget and does not have to wait
+//! for it. Waiting for chunks instead of parts could deadlock a
+//! [PartStream](crate::streams::PartStream) waiting for an earlier part
+//! whose chunks wait for budget held by later parts.
+//!
+//! [Config::validated]: crate::config::Config::validated
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Semaphore;
+
+/// The global memory budget shared by all downloads of a [Condow](crate::Condow)
+#[derive(Clone)]
+pub(crate) struct MemoryBudget {
+    semaphore: Arc<Semaphore>,
+    total_bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(total_bytes: u64) -> Self {
+        let total_bytes = total_bytes.min(Semaphore::MAX_PERMITS as u64);
+        Self {
+            semaphore: Arc::new(Semaphore::new(total_bytes as usize)),
+            total_bytes,
+        }
+    }
+
+    /// The number of bytes currently reserved by all downloads
+    pub fn bytes_in_use(&self) -> u64 {
+        self.total_bytes - self.semaphore.available_permits() as u64
+    }
+
+    /// Creates an account for a single download
+    pub fn account(&self) -> Arc<BudgetAccount> {
+        Arc::new(BudgetAccount {
+            budget: self.clone(),
+            state: Mutex::new(AccountState {
+                reserved: 0,
+                is_closed: false,
+            }),
+        })
+    }
+
+    /// The number of permits for a reservation of `n_bytes`
+    ///
+    /// Panics if the reservation can never be granted. Validation of the
+    /// [Config](crate::config::Config) ensures that no part is larger
+    /// than the budget.
+    fn permits(&self, n_bytes: u64) -> u32 {
+        assert!(
+            n_bytes <= self.total_bytes && n_bytes <= u32::MAX as u64,
+            "reservation of {} bytes exceeds the budget of {} bytes. This is a bug",
+            n_bytes,
+            self.total_bytes
+        );
+        n_bytes as u32
+    }
+}
+
+/// Tracks the bytes reserved by a single download
+///
+/// Once the account is closed all reserved bytes are returned to the
+/// [MemoryBudget] and new reservations will fail.
+pub(crate) struct BudgetAccount {
+    budget: MemoryBudget,
+    state: Mutex<AccountState>,
+}
+
+struct AccountState {
+    reserved: u64,
+    is_closed: bool,
+}
+
+impl BudgetAccount {
+    /// Try to reserve bytes without waiting
+    ///
+    /// Returns the number of bytes reserved or `None` if the budget is exhausted.
+    ///
+    /// Fails if the account was closed.
+    pub fn try_reserve(&self, n_bytes: u64) -> Result<Option<u64>, ()> {
+        let n_permits = self.budget.permits(n_bytes);
+        match self.budget.semaphore.try_acquire_many(n_permits) {
+            Ok(permit) => {
+                permit.forget();
+                self.book(n_permits).map(Some)
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Reserve bytes and wait if the budget is exhausted
+    ///
+    /// Returns the number of bytes reserved.
+    ///
+    /// Fails if the account was closed.
+    pub async fn reserve(&self, n_bytes: u64) -> Result<u64, ()> {
+        let n_permits = self.budget.permits(n_bytes);
+        let permit = self
+            .budget
+            .semaphore
+            .acquire_many(n_permits)
+            .await
+            .map_err(|_| ())?;
+        permit.forget();
+        self.book(n_permits)
+    }
+
+    /// Return bytes which were handed over to the consumer of a download
+    pub fn release(&self, n_bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        let n_bytes = n_bytes.min(state.reserved);
+        state.reserved -= n_bytes;
+        self.budget.semaphore.add_permits(n_bytes as usize);
+    }
+
+    /// Return all reserved bytes and reject further reservations
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.is_closed = true;
+        self.budget.semaphore.add_permits(state.reserved as usize);
+        state.reserved = 0;
+    }
+
+    /// The number of bytes currently reserved by all downloads
+    pub fn bytes_in_use(&self) -> u64 {
+        self.budget.bytes_in_use()
+    }
+
+    fn book(&self, n_permits: u32) -> Result<u64, ()> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_closed {
+            self.budget.semaphore.add_permits(n_permits as usize);
+            return Err(());
+        }
+        state.reserved += n_permits as u64;
+        Ok(n_permits as u64)
+    }
+}
+
+/// Used by the consumer of a download to release bytes
+///
+/// Closes the [BudgetAccount] when dropped.
+pub(crate) struct BudgetConsumer(Arc<BudgetAccount>);
+
+impl BudgetConsumer {
+    pub fn new(account: Arc<BudgetAccount>) -> Self {
+        Self(account)
+    }
+
+    pub fn release(&self, n_bytes: u64) {
+        self.0.release(n_bytes)
+    }
+}
+
+impl Drop for BudgetConsumer {
+    fn drop(&mut self) {
+        self.0.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_and_release() {
+        let budget = MemoryBudget::new(10);
+        let account = budget.account();
+
+        assert_eq!(account.try_reserve(4), Ok(Some(4)));
+        assert_eq!(account.try_reserve(4), Ok(Some(4)));
+        assert_eq!(account.try_reserve(4), Ok(None));
+        assert_eq!(budget.bytes_in_use(), 8);
+
+        account.release(6);
+        assert_eq!(budget.bytes_in_use(), 2);
+        assert_eq!(account.try_reserve(4), Ok(Some(4)));
+        assert_eq!(budget.bytes_in_use(), 6);
+    }
+
+    #[test]
+    fn never_release_more_than_reserved() {
+        let budget = MemoryBudget::new(10);
+        let account = budget.account();
+
+        assert_eq!(account.try_reserve(10), Ok(Some(10)));
+        assert_eq!(budget.bytes_in_use(), 10);
+
+        account.release(100);
+        assert_eq!(budget.bytes_in_use(), 0);
+        assert_eq!(account.try_reserve(10), Ok(Some(10)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_reservation_larger_than_the_budget_is_a_bug() {
+        let budget = MemoryBudget::new(10);
+        let account = budget.account();
+
+        let _ = account.try_reserve(11);
+    }
+
+    #[test]
+    fn closing_returns_everything_and_rejects_reservations() {
+        let budget = MemoryBudget::new(10);
+        let account_a = budget.account();
+        let account_b = budget.account();
+
+        assert_eq!(account_a.try_reserve(3), Ok(Some(3)));
+        assert_eq!(account_b.try_reserve(5), Ok(Some(5)));
+
+        drop(BudgetConsumer::new(Arc::clone(&account_a)));
+
+        assert_eq!(budget.bytes_in_use(), 5);
+        assert_eq!(account_a.try_reserve(1), Err(()));
+        assert_eq!(budget.bytes_in_use(), 5);
+    }
+
+    #[tokio::test]
+    async fn reserve_waits_for_release() {
+        let budget = MemoryBudget::new(10);
+        let account = budget.account();
+
+        assert_eq!(account.reserve(10).await, Ok(10));
+
+        let waiting = {
+            let account = Arc::clone(&account);
+            tokio::spawn(async move { account.reserve(5).await })
+        };
+
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        account.release(5);
+
+        assert_eq!(waiting.await.unwrap(), Ok(5));
+        assert_eq!(budget.bytes_in_use(), 10);
+    }
+}
@@ -2,6 +2,7 @@
 ///
 /// Mostly for interfacing with other libraries.
 pub use bytes_async_reader::*;
+pub use positional_reader::*;
 pub use random_access_reader::*;
 
 mod random_access_reader {
@@ -208,7 +209,7 @@ mod random_access_reader {
                     }
                     task::Poll::Ready(Err(err)) => {
                         self.state = State::Error;
-                        task::Poll::Ready(Err(IoError::new(IoErrorKind::Other, err)))
+                        task::Poll::Ready(Err(IoError::from(err)))
                     }
                     task::Poll::Pending => {
                         self.state = State::GetNewReaderFuture(fut);
@@ -238,7 +239,7 @@ mod random_access_reader {
                         }
                         task::Poll::Ready(Err(err)) => {
                             self.state = State::Error;
-                            task::Poll::Ready(Err(IoError::new(IoErrorKind::Other, err)))
+                            task::Poll::Ready(Err(err))
                         }
                         task::Poll::Pending => {
                             self.state = State::PollingReader(reader);
@@ -279,7 +280,7 @@ mod random_access_reader {
                         // This would go before the start
                         // and is an error by the specification of SeekFrom::End
                         let err = CondowError::new_invalid_range("Seek before start");
-                        return task::Poll::Ready(Err(IoError::new(IoErrorKind::Other, err)));
+                        return task::Poll::Ready(Err(IoError::from(err)));
                     }
                     (this.length as i64 + offset) as u64
                 }
@@ -288,7 +289,7 @@ mod random_access_reader {
                         // This would go before the start
                         // and is an error by the specification of SeekFrom::Current
                         let err = CondowError::new_invalid_range("Seek before start");
-                        return task::Poll::Ready(Err(IoError::new(IoErrorKind::Other, err)));
+                        return task::Poll::Ready(Err(IoError::from(err)));
                     }
                     (this.pos as i64 + offset) as u64
                 }
@@ -519,6 +520,195 @@ mod random_access_reader {
     }
 }
 
+mod positional_reader {
+    use crate::{errors::CondowError, Downloads};
+
+    /// A reader which reads at given offsets
+    ///
+    /// Unlike the [RandomAccessReader](super::RandomAccessReader) this reader does not
+    /// have a position or any other mutable state. It can be cloned
+    /// and reads can be issued concurrently from multiple tasks.
+    ///
+    /// Each read downloads exactly the requested bytes and writes them
+    /// directly into the buffer given by the caller.
+    #[derive(Clone)]
+    pub struct PositionalReader<D, L> {
+        /// Download logic
+        downloader: D,
+        /// Location of the BLOB
+        location: L,
+        /// Total length of the BLOB
+        length: u64,
+    }
+
+    impl<D, L> PositionalReader<D, L>
+    where
+        D: Downloads<L>,
+        L: std::fmt::Debug + std::fmt::Display + Clone + Send + Sync + 'static,
+    {
+        /// Creates a new instance without a given BLOB length
+        ///
+        /// This function will query the size of the BLOB. If the size is already known
+        /// call [PositionalReader::new_with_length]
+        pub async fn new(downloader: D, location: L) -> Result<Self, CondowError> {
+            let length = downloader.get_size(location.clone()).await?;
+            Ok(Self::new_with_length(downloader, location, length))
+        }
+
+        /// Will create a reader with the given known size of the BLOB.
+        pub fn new_with_length(downloader: D, location: L, length: u64) -> Self {
+            Self {
+                downloader,
+                location,
+                length,
+            }
+        }
+
+        /// Total length of the BLOB
+        pub fn length(&self) -> u64 {
+            self.length
+        }
+
+        /// Location of the BLOB
+        pub fn location(&self) -> &L {
+            &self.location
+        }
+
+        /// Read bytes starting at `offset` into `buf`
+        ///
+        /// Reads at most `buf.len()` bytes. Less bytes are read
+        /// if the end of the BLOB is reached.
+        ///
+        /// Returns the number of bytes read which is 0 if `offset`
+        /// is at or after the end of the BLOB.
+        pub async fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, CondowError> {
+            if offset >= self.length || buf.is_empty() {
+                return Ok(0);
+            }
+
+            let n_bytes = (self.length - offset).min(buf.len() as u64);
+
+            self.download_into(offset, &mut buf[..n_bytes as usize])
+                .await
+        }
+
+        /// Read exactly `buf.len()` bytes starting at `offset` into `buf`
+        ///
+        /// Fails if the BLOB does not contain enough bytes after `offset`.
+        pub async fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), CondowError> {
+            if buf.is_empty() {
+                return Ok(());
+            }
+
+            let end_excl = offset.checked_add(buf.len() as u64);
+            if end_excl
+                .map(|end_excl| end_excl > self.length)
+                .unwrap_or(true)
+            {
+                return Err(CondowError::new_invalid_range(format!(
+                    "can not read {} bytes at offset {} from a BLOB of length {}",
+                    buf.len(),
+                    offset,
+                    self.length
+                )));
+            }
+
+            let n_bytes_read = self.download_into(offset, buf).await?;
+
+            if n_bytes_read != buf.len() {
+                return Err(CondowError::new_io(format!(
+                    "expected to read {} bytes but got {}",
+                    buf.len(),
+                    n_bytes_read
+                )));
+            }
+
+            Ok(())
+        }
+
+        async fn download_into(&self, offset: u64, buf: &mut [u8]) -> Result<usize, CondowError> {
+            let end_excl = offset + buf.len() as u64;
+            self.downloader
+                .download_chunks(self.location.clone(), offset..end_excl)
+                .await?
+                .write_buffer(buf)
+                .await
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::{
+            condow_client::NoLocation, errors::CondowErrorKind, test_utils::TestDownloader,
+        };
+
+        use super::*;
+
+        #[tokio::test]
+        async fn read_at() {
+            let downloader = TestDownloader::new_with_blob((0..10).collect());
+            let reader = PositionalReader::new(downloader, NoLocation).await.unwrap();
+
+            let mut buf = [0u8; 3];
+            assert_eq!(reader.read_at(0, &mut buf).await.unwrap(), 3);
+            assert_eq!(buf, [0, 1, 2]);
+
+            assert_eq!(reader.read_at(5, &mut buf).await.unwrap(), 3);
+            assert_eq!(buf, [5, 6, 7]);
+
+            let mut buf = [0u8; 5];
+            assert_eq!(reader.read_at(8, &mut buf).await.unwrap(), 2);
+            assert_eq!(&buf[..2], [8, 9]);
+
+            assert_eq!(reader.read_at(10, &mut buf).await.unwrap(), 0);
+            assert_eq!(reader.read_at(1_000, &mut buf).await.unwrap(), 0);
+        }
+
+        #[tokio::test]
+        async fn read_exact_at() {
+            let downloader = TestDownloader::new_with_blob((0..10).collect());
+            let reader = PositionalReader::new(downloader, NoLocation).await.unwrap();
+
+            let mut buf = [0u8; 4];
+            reader.read_exact_at(6, &mut buf).await.unwrap();
+            assert_eq!(buf, [6, 7, 8, 9]);
+
+            let err = reader.read_exact_at(7, &mut buf).await.unwrap_err();
+            assert_eq!(err.kind(), CondowErrorKind::InvalidRange);
+
+            let err = reader.read_exact_at(u64::MAX, &mut buf).await.unwrap_err();
+            assert_eq!(err.kind(), CondowErrorKind::InvalidRange);
+        }
+
+        #[tokio::test]
+        async fn concurrent_reads_from_clones() {
+            let blob: Vec<u8> = (0..255).collect();
+            let downloader = TestDownloader::new_with_blob(blob.clone());
+            let reader = PositionalReader::new(downloader, NoLocation).await.unwrap();
+
+            let handles: Vec<_> = (0..blob.len() as u64)
+                .step_by(10)
+                .map(|offset| {
+                    let reader = reader.clone();
+                    tokio::spawn(async move {
+                        let mut buf = vec![0u8; 20];
+                        let n = reader.read_at(offset, &mut buf).await.unwrap();
+                        buf.truncate(n);
+                        (offset, buf)
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                let (offset, buf) = handle.await.unwrap();
+                let start = offset as usize;
+                let end_excl = (start + 20).min(blob.len());
+                assert_eq!(buf, blob[start..end_excl], "offset: {}", offset);
+            }
+        }
+    }
+}
+
 mod bytes_async_reader {
     use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
     use std::pin::Pin;
@@ -577,7 +767,7 @@ mod bytes_async_reader {
                     }
                     task::Poll::Ready(Some(Err(err))) => {
                         self.state = State::Error;
-                        task::Poll::Ready(Err(IoError::new(IoErrorKind::Other, err)))
+                        task::Poll::Ready(Err(IoError::from(err)))
                     }
                     task::Poll::Ready(None) => {
                         self.state = State::Finished;
@@ -684,6 +874,28 @@ mod bytes_async_reader {
         assert_eq!(dest_buf, &[6, 7, 8,]);
     }
 
+    #[tokio::test]
+    async fn test_read_from_stream_surfaces_io_error_kind() {
+        use futures::io::AsyncReadExt as _;
+        let timed_out = std::io::Error::new(IoErrorKind::TimedOut, "too slow");
+        let bytes_stream: Vec<Result<Bytes, CondowError>> =
+            vec![Ok(vec![0_u8, 1, 2].into()), Err(timed_out.into())];
+        let bytes_stream = futures::stream::iter(bytes_stream.into_iter());
+        let mut reader = BytesAsyncReader::new(bytes_stream);
+        let dest_buf: &mut [u8; 3] = &mut [42; 3];
+
+        let bytes_written = reader.read(dest_buf).await.unwrap();
+        assert_eq!(bytes_written, 3, "bytes_written");
+        let err = reader.read(dest_buf).await.unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::TimedOut);
+
+        let bytes_stream: Vec<Result<Bytes, CondowError>> =
+            vec![Err(CondowError::new_not_found("gone"))];
+        let mut reader = BytesAsyncReader::new(futures::stream::iter(bytes_stream.into_iter()));
+        let err = reader.read(dest_buf).await.unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::NotFound);
+    }
+
     #[tokio::test]
     async fn test_read_from_stream_chunk_larger_than_destination_buffer() {
         use futures::io::AsyncReadExt as _;
@@ -0,0 +1,439 @@
+//! Aggregating the reports of all downloads of a session
+//!
+//! An [AggregatingReporterFactory] creates a [SimpleReporter] for each download
+//! and folds its report into session wide statistics once the download
+//! finished. Errors reported are counted by their kind. A [SessionReport]
+//! combines the finished downloads with snapshots of the running ones.
+//!
+//! The [AggregatingReporterFactory] is intended to be used with a
+//! [DownloadSession](crate::DownloadSession) whose
+//! [session_report](crate::DownloadSession::session_report) returns a
+//! [SessionReport].
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    errors::{CondowError, CondowErrorKind},
+    InclusiveRange,
+};
+
+use super::{CompositeReporter, Reporter, ReporterFactory, SimpleReport, SimpleReporter};
+
+/// The [Reporter] created by an [AggregatingReporterFactory]
+pub type AggregatingReporter = CompositeReporter<SimpleReporter, SessionCollector>;
+
+/// Creates [SimpleReporter]s and aggregates their reports into a [SessionReport]
+///
+/// Only the reporters of running downloads are kept. Downloads which never
+/// complete or fail, e.g. because their stream was dropped, are folded into
+/// the session once all clones of their [SessionCollector] were dropped.
+pub struct AggregatingReporterFactory {
+    skip_first_chunk_timings: bool,
+    session: Arc<Mutex<Session>>,
+}
+
+impl AggregatingReporterFactory {
+    /// Create a new factory
+    ///
+    /// See [SimpleReporterFactory::new](super::SimpleReporterFactory::new)
+    /// for `skip_first_chunk_timings`.
+    pub fn new(skip_first_chunk_timings: bool) -> Self {
+        Self {
+            skip_first_chunk_timings,
+            session: Default::default(),
+        }
+    }
+
+    /// Aggregates the reports of all downloads so far
+    pub fn session_report(&self) -> SessionReport {
+        let session = self.session.lock().unwrap();
+
+        let mut aggregate = session.finished.clone();
+        session
+            .running
+            .values()
+            .for_each(|reporter| aggregate.add(&reporter.report()));
+
+        aggregate.into_session_report(session.errors_by_kind.clone())
+    }
+}
+
+impl Default for AggregatingReporterFactory {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl ReporterFactory for AggregatingReporterFactory {
+    type ReporterType = AggregatingReporter;
+
+    fn make(&self, location: &dyn fmt::Display) -> Self::ReporterType {
+        let reporter = SimpleReporter::new(location, self.skip_first_chunk_timings);
+
+        let mut session = self.session.lock().unwrap();
+        let id = session.next_id;
+        session.next_id += 1;
+        session.running.insert(id, reporter.clone());
+
+        CompositeReporter(
+            reporter,
+            SessionCollector(Arc::new(CollectorInner {
+                id,
+                session: Arc::clone(&self.session),
+            })),
+        )
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    next_id: u64,
+    running: BTreeMap<u64, SimpleReporter>,
+    finished: Aggregate,
+    errors_by_kind: BTreeMap<CondowErrorKind, usize>,
+}
+
+/// Reports merged into one another
+#[derive(Default, Clone)]
+struct Aggregate {
+    n_downloads: usize,
+    n_finished: usize,
+    n_failed: usize,
+    totals: Option<SimpleReport>,
+    download_times: Vec<Duration>,
+    bytes_per_second: Vec<u64>,
+}
+
+impl Aggregate {
+    fn add(&mut self, report: &SimpleReport) {
+        self.n_downloads += 1;
+        self.n_finished += report.is_finished as usize;
+        self.n_failed += report.is_failed as usize;
+        match self.totals.as_mut() {
+            Some(totals) => totals.merge(report),
+            None => self.totals = Some(report.clone()),
+        }
+        self.download_times.push(report.download_time);
+        self.bytes_per_second.push(report.bytes_per_second);
+    }
+
+    fn into_session_report(
+        self,
+        errors_by_kind: BTreeMap<CondowErrorKind, usize>,
+    ) -> SessionReport {
+        SessionReport {
+            n_downloads: self.n_downloads,
+            n_finished: self.n_finished,
+            n_failed: self.n_failed,
+            totals: self.totals,
+            download_time: Percentiles::from_values(self.download_times),
+            bytes_per_second: Percentiles::from_values(self.bytes_per_second),
+            errors_by_kind,
+        }
+    }
+}
+
+/// Counts errors by their [CondowErrorKind] and folds the report of
+/// the download into the session once it completed or failed
+///
+/// Counts every error passed to [Reporter::retry_attempt],
+/// [Reporter::size_request_failed] and [Reporter::part_failed].
+///
+/// A download neither completed nor failed is folded into the session
+/// when the last clone is dropped.
+#[derive(Clone)]
+pub struct SessionCollector(Arc<CollectorInner>);
+
+struct CollectorInner {
+    id: u64,
+    session: Arc<Mutex<Session>>,
+}
+
+impl SessionCollector {
+    fn count(&self, error: &CondowError) {
+        *self
+            .0
+            .session
+            .lock()
+            .unwrap()
+            .errors_by_kind
+            .entry(error.kind())
+            .or_default() += 1;
+    }
+}
+
+impl CollectorInner {
+    fn finish(&self) {
+        let mut session = self.session.lock().unwrap();
+        if let Some(reporter) = session.running.remove(&self.id) {
+            session.finished.add(&reporter.report());
+        }
+    }
+}
+
+impl Drop for CollectorInner {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl Reporter for SessionCollector {
+    fn download_completed(&self, _time: Duration) {
+        self.0.finish();
+    }
+
+    fn download_failed(&self, _time: Option<Duration>) {
+        self.0.finish();
+    }
+
+    fn retry_attempt(&self, _location: &dyn fmt::Display, error: &CondowError, _next_in: Duration) {
+        self.count(error);
+    }
+
+    fn size_request_failed(
+        &self,
+        _location: &dyn fmt::Display,
+        error: &CondowError,
+        _time: Duration,
+    ) {
+        self.count(error);
+    }
+
+    fn part_failed(&self, error: &CondowError, _part_index: u64, _range: &InclusiveRange) {
+        self.count(error);
+    }
+}
+
+/// Statistics of all downloads of a session
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionReport {
+    pub n_downloads: usize,
+    pub n_finished: usize,
+    pub n_failed: usize,
+    /// All reports merged. See [SimpleReport::merge].
+    ///
+    /// `None` if there were no downloads.
+    pub totals: Option<SimpleReport>,
+    /// `None` if there were no downloads.
+    pub download_time: Option<Percentiles<Duration>>,
+    /// `None` if there were no downloads.
+    pub bytes_per_second: Option<Percentiles<u64>>,
+    pub errors_by_kind: BTreeMap<CondowErrorKind, usize>,
+}
+
+impl SessionReport {
+    /// Aggregate the given reports
+    pub fn from_reports(
+        reports: &[SimpleReport],
+        errors_by_kind: BTreeMap<CondowErrorKind, usize>,
+    ) -> Self {
+        let mut aggregate = Aggregate::default();
+        reports.iter().for_each(|report| aggregate.add(report));
+        aggregate.into_session_report(errors_by_kind)
+    }
+}
+
+/// Percentiles using the nearest rank method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Percentiles<T> {
+    pub min: T,
+    pub p50: T,
+    pub p90: T,
+    pub p99: T,
+    pub max: T,
+}
+
+impl<T: Ord + Copy> Percentiles<T> {
+    /// Returns `None` if there are no values
+    pub fn from_values<I: IntoIterator<Item = T>>(values: I) -> Option<Self> {
+        let mut values = values.into_iter().collect::<Vec<_>>();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+
+        let rank = |p: usize| {
+            let idx = (values.len() * p).div_ceil(100).max(1) - 1;
+            values[idx]
+        };
+
+        Some(Self {
+            min: values[0],
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        config::{Config, RetryConfig},
+        test_utils::TestCondowClient,
+        Condow,
+    };
+
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        assert_eq!(Percentiles::<u64>::from_values(vec![]), None);
+
+        let p = Percentiles::from_values((1..=100u64).rev()).unwrap();
+        assert_eq!(
+            p,
+            Percentiles {
+                min: 1,
+                p50: 50,
+                p90: 90,
+                p99: 99,
+                max: 100
+            }
+        );
+
+        let p = Percentiles::from_values(vec![7u64]).unwrap();
+        assert_eq!((p.min, p.p50, p.p99, p.max), (7, 7, 7, 7));
+    }
+
+    #[test]
+    fn finished_downloads_are_folded_into_the_session() {
+        let factory = AggregatingReporterFactory::default();
+
+        let finished = factory.make(&"finished");
+        finished.download_started();
+        finished.part_completed(0, 1, 10, Duration::from_millis(1));
+        finished.download_completed(Duration::from_millis(1));
+        let running = factory.make(&"running");
+        running.part_completed(0, 1, 5, Duration::from_millis(1));
+
+        assert_eq!(factory.session.lock().unwrap().running.len(), 1);
+        let report = factory.session_report();
+        assert_eq!(report.n_downloads, 2);
+        assert_eq!(report.n_finished, 1);
+        assert_eq!(report.totals.unwrap().n_bytes_received, 15);
+
+        running.download_failed(None);
+
+        assert!(factory.session.lock().unwrap().running.is_empty());
+        let report = factory.session_report();
+        assert_eq!(report.n_downloads, 2);
+        assert_eq!(report.n_finished, 2);
+        assert_eq!(report.n_failed, 1);
+    }
+
+    #[test]
+    fn abandoned_downloads_are_folded_into_the_session() {
+        let factory = AggregatingReporterFactory::default();
+
+        let abandoned = factory.make(&"abandoned");
+        abandoned.part_completed(0, 1, 5, Duration::from_millis(1));
+        let clone = abandoned.clone();
+        drop(abandoned);
+
+        assert_eq!(factory.session.lock().unwrap().running.len(), 1);
+
+        drop(clone);
+
+        assert!(factory.session.lock().unwrap().running.is_empty());
+        let report = factory.session_report();
+        assert_eq!(report.n_downloads, 1);
+        assert_eq!(report.n_finished, 0);
+        assert_eq!(report.n_failed, 0);
+        assert_eq!(report.totals.unwrap().n_bytes_received, 5);
+    }
+
+    #[tokio::test]
+    async fn dropping_a_stream_leaves_no_running_downloads() {
+        let condow = Condow::new(
+            TestCondowClient::new().max_chunk_size(1).max_jitter_ms(5),
+            Config::default().part_size_bytes(10).max_concurrency(1),
+        )
+        .unwrap();
+        let factory = AggregatingReporterFactory::default();
+        let session = Arc::clone(&factory.session);
+        let session_downloads = condow.download_session(factory);
+
+        let mut stream = session_downloads.download(NoLocation, ..).await.unwrap();
+        let _ = stream.next().await.unwrap().unwrap();
+        assert_eq!(session.lock().unwrap().running.len(), 1);
+
+        drop(stream);
+
+        for _ in 0..100 {
+            if session.lock().unwrap().running.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(session.lock().unwrap().running.is_empty());
+        assert_eq!(session_downloads.session_report().n_downloads, 1);
+    }
+
+    #[test]
+    fn throughput_without_download_time_is_zero() {
+        let mut report = SimpleReporter::default().report();
+        report.n_bytes_received = 100;
+        report.download_time = Duration::ZERO;
+
+        report.merge(&report.clone());
+
+        assert_eq!(report.bytes_per_second, 0);
+        assert_eq!(report.megabytes_per_second, 0.0);
+    }
+
+    #[tokio::test]
+    async fn session_report() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(10)
+            .responses()
+            .failure(CondowError::new_remote("remote failed"))
+            .success()
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(50)
+                    .retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let session = condow.download_session(AggregatingReporterFactory::default());
+
+        for _ in 0..3 {
+            let result = session
+                .download(NoLocation, ..)
+                .await
+                .unwrap()
+                .into_vec()
+                .await
+                .unwrap();
+            assert_eq!(result, blob);
+        }
+
+        let report = session.session_report();
+        assert_eq!(report.n_downloads, 3);
+        assert_eq!(report.n_finished, 3);
+        assert_eq!(report.n_failed, 0);
+        let totals = report.totals.unwrap();
+        assert_eq!(totals.n_bytes_received, 300);
+        assert_eq!(totals.n_parts_received, 6);
+        assert_eq!(totals.n_retries, 1);
+        assert_eq!(
+            report.errors_by_kind.get(&CondowErrorKind::Remote),
+            Some(&1)
+        );
+        assert!(report.download_time.is_some());
+    }
+}
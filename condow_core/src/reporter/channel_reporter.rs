@@ -0,0 +1,486 @@
+//! Reporting via a channel
+//!
+//! A [ChannelReporter] turns each call on the [Reporter] into a
+//! [ReportEvent] and sends it to a [ReportEventReceiver]. This allows
+//! events to be consumed asynchronously without slowing down the download.
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
+use tokio::sync::mpsc;
+
+use crate::{
+    errors::{CondowError, CondowErrorKind, IoError},
+    DownloadContext, InclusiveRange,
+};
+
+use super::Reporter;
+
+/// An event corresponding to a method of the [Reporter] trait
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportEvent {
+    DownloadContext(DownloadContext),
+    EffectiveRange(InclusiveRange),
+    DownloadStarted,
+    DownloadCompleted {
+        time: Duration,
+    },
+    DownloadFailed {
+        time: Option<Duration>,
+    },
+    RetryAttempt {
+        location: String,
+        error: String,
+        error_kind: CondowErrorKind,
+        next_in: Duration,
+    },
+    StreamResumeAttempt {
+        location: String,
+        error: String,
+        orig_range: InclusiveRange,
+        remaining_range: InclusiveRange,
+    },
+    SizeRequestStarted {
+        location: String,
+    },
+    SizeRequestCompleted {
+        location: String,
+        size: u64,
+        time: Duration,
+    },
+    SizeRequestFailed {
+        location: String,
+        error: String,
+        error_kind: CondowErrorKind,
+        time: Duration,
+    },
+    RequestStarted {
+        location: String,
+        attempt: usize,
+    },
+    RequestCompleted {
+        location: String,
+        attempt: usize,
+        latency: Duration,
+        time_to_first_byte: Duration,
+    },
+    RequestFailed {
+        location: String,
+        attempt: usize,
+        error: String,
+        error_kind: CondowErrorKind,
+        time: Duration,
+    },
+    PanicDetected {
+        msg: String,
+    },
+    QueueFull,
+    MemoryBudgetExhausted {
+        part_index: u64,
+        n_bytes: u64,
+    },
+    MemoryReserved {
+        part_index: u64,
+        n_bytes: u64,
+        bytes_in_use: u64,
+        waited: Duration,
+    },
+    ChunkCompleted {
+        part_index: u64,
+        chunk_index: usize,
+        n_bytes: usize,
+        time: Duration,
+    },
+    PartStarted {
+        part_index: u64,
+        range: InclusiveRange,
+    },
+    PartCompleted {
+        part_index: u64,
+        n_chunks: usize,
+        n_bytes: u64,
+        time: Duration,
+    },
+    PartFailed {
+        error: String,
+        error_kind: CondowErrorKind,
+        part_index: u64,
+        range: InclusiveRange,
+    },
+}
+
+/// A [Reporter] which sends [ReportEvent]s over a channel
+///
+/// Sending never blocks the download. If the channel is bounded and full,
+/// the event is dropped and counted. Events are also discarded once the
+/// [ReportEventReceiver] was dropped.
+#[derive(Clone)]
+pub struct ChannelReporter {
+    sender: EventSender,
+    n_dropped: Arc<AtomicU64>,
+}
+
+impl ChannelReporter {
+    /// Creates a [ChannelReporter] which drops events if more than
+    /// `capacity` events are buffered
+    pub fn bounded(capacity: NonZeroUsize) -> (Self, ReportEventReceiver) {
+        let (tx, rx) = mpsc::channel(capacity.get());
+        Self::create(EventSender::Bounded(tx), EventReceiver::Bounded(rx))
+    }
+
+    /// Creates a [ChannelReporter] which never drops events
+    ///
+    /// The buffered events can grow without limit if they are not consumed.
+    pub fn unbounded() -> (Self, ReportEventReceiver) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self::create(EventSender::Unbounded(tx), EventReceiver::Unbounded(rx))
+    }
+
+    /// The number of events dropped because the channel was full
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::SeqCst)
+    }
+
+    fn create(sender: EventSender, receiver: EventReceiver) -> (Self, ReportEventReceiver) {
+        let n_dropped = Arc::new(AtomicU64::new(0));
+        let reporter = Self {
+            sender,
+            n_dropped: Arc::clone(&n_dropped),
+        };
+        let receiver = ReportEventReceiver {
+            receiver,
+            n_dropped,
+        };
+        (reporter, receiver)
+    }
+
+    fn send(&self, event: ReportEvent) {
+        match &self.sender {
+            EventSender::Bounded(tx) => {
+                if let Err(mpsc::error::TrySendError::Full(_)) = tx.try_send(event) {
+                    self.n_dropped.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            EventSender::Unbounded(tx) => {
+                let _ = tx.send(event);
+            }
+        }
+    }
+}
+
+impl Reporter for ChannelReporter {
+    fn download_context(&self, context: &DownloadContext) {
+        self.send(ReportEvent::DownloadContext(context.clone()));
+    }
+
+    fn effective_range(&self, range: InclusiveRange) {
+        self.send(ReportEvent::EffectiveRange(range));
+    }
+
+    fn download_started(&self) {
+        self.send(ReportEvent::DownloadStarted);
+    }
+
+    fn download_completed(&self, time: Duration) {
+        self.send(ReportEvent::DownloadCompleted { time });
+    }
+
+    fn download_failed(&self, time: Option<Duration>) {
+        self.send(ReportEvent::DownloadFailed { time });
+    }
+
+    fn retry_attempt(&self, location: &dyn fmt::Display, error: &CondowError, next_in: Duration) {
+        self.send(ReportEvent::RetryAttempt {
+            location: location.to_string(),
+            error: error.to_string(),
+            error_kind: error.kind(),
+            next_in,
+        });
+    }
+
+    fn stream_resume_attempt(
+        &self,
+        location: &dyn fmt::Display,
+        error: &IoError,
+        orig_range: InclusiveRange,
+        remaining_range: InclusiveRange,
+    ) {
+        self.send(ReportEvent::StreamResumeAttempt {
+            location: location.to_string(),
+            error: error.to_string(),
+            orig_range,
+            remaining_range,
+        });
+    }
+
+    fn size_request_started(&self, location: &dyn fmt::Display) {
+        self.send(ReportEvent::SizeRequestStarted {
+            location: location.to_string(),
+        });
+    }
+
+    fn size_request_completed(&self, location: &dyn fmt::Display, size: u64, time: Duration) {
+        self.send(ReportEvent::SizeRequestCompleted {
+            location: location.to_string(),
+            size,
+            time,
+        });
+    }
+
+    fn size_request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        self.send(ReportEvent::SizeRequestFailed {
+            location: location.to_string(),
+            error: error.to_string(),
+            error_kind: error.kind(),
+            time,
+        });
+    }
+
+    fn request_started(&self, location: &dyn fmt::Display, attempt: usize) {
+        self.send(ReportEvent::RequestStarted {
+            location: location.to_string(),
+            attempt,
+        });
+    }
+
+    fn request_completed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        latency: Duration,
+        time_to_first_byte: Duration,
+    ) {
+        self.send(ReportEvent::RequestCompleted {
+            location: location.to_string(),
+            attempt,
+            latency,
+            time_to_first_byte,
+        });
+    }
+
+    fn request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        self.send(ReportEvent::RequestFailed {
+            location: location.to_string(),
+            attempt,
+            error: error.to_string(),
+            error_kind: error.kind(),
+            time,
+        });
+    }
+
+    fn panic_detected(&self, msg: &str) {
+        self.send(ReportEvent::PanicDetected {
+            msg: msg.to_string(),
+        });
+    }
+
+    fn queue_full(&self) {
+        self.send(ReportEvent::QueueFull);
+    }
+
+    fn memory_budget_exhausted(&self, part_index: u64, n_bytes: u64) {
+        self.send(ReportEvent::MemoryBudgetExhausted {
+            part_index,
+            n_bytes,
+        });
+    }
+
+    fn memory_reserved(&self, part_index: u64, n_bytes: u64, bytes_in_use: u64, waited: Duration) {
+        self.send(ReportEvent::MemoryReserved {
+            part_index,
+            n_bytes,
+            bytes_in_use,
+            waited,
+        });
+    }
+
+    fn chunk_completed(&self, part_index: u64, chunk_index: usize, n_bytes: usize, time: Duration) {
+        self.send(ReportEvent::ChunkCompleted {
+            part_index,
+            chunk_index,
+            n_bytes,
+            time,
+        });
+    }
+
+    fn part_started(&self, part_index: u64, range: InclusiveRange) {
+        self.send(ReportEvent::PartStarted { part_index, range });
+    }
+
+    fn part_completed(&self, part_index: u64, n_chunks: usize, n_bytes: u64, time: Duration) {
+        self.send(ReportEvent::PartCompleted {
+            part_index,
+            n_chunks,
+            n_bytes,
+            time,
+        });
+    }
+
+    fn part_failed(&self, error: &CondowError, part_index: u64, range: &InclusiveRange) {
+        self.send(ReportEvent::PartFailed {
+            error: error.to_string(),
+            error_kind: error.kind(),
+            part_index,
+            range: *range,
+        });
+    }
+}
+
+/// Receives the [ReportEvent]s of a [ChannelReporter]
+///
+/// The stream ends once all clones of the [ChannelReporter] were dropped.
+pub struct ReportEventReceiver {
+    receiver: EventReceiver,
+    n_dropped: Arc<AtomicU64>,
+}
+
+impl ReportEventReceiver {
+    /// Receive the next event
+    ///
+    /// Returns `None` once all clones of the [ChannelReporter] were dropped
+    /// and all buffered events were received.
+    pub async fn recv(&mut self) -> Option<ReportEvent> {
+        match &mut self.receiver {
+            EventReceiver::Bounded(rx) => rx.recv().await,
+            EventReceiver::Unbounded(rx) => rx.recv().await,
+        }
+    }
+
+    /// Receive the next event if one is buffered
+    pub fn try_recv(&mut self) -> Option<ReportEvent> {
+        match &mut self.receiver {
+            EventReceiver::Bounded(rx) => rx.try_recv().ok(),
+            EventReceiver::Unbounded(rx) => rx.try_recv().ok(),
+        }
+    }
+
+    /// The number of events dropped because the channel was full
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for ReportEventReceiver {
+    type Item = ReportEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.receiver {
+            EventReceiver::Bounded(rx) => rx.poll_recv(cx),
+            EventReceiver::Unbounded(rx) => rx.poll_recv(cx),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum EventSender {
+    Bounded(mpsc::Sender<ReportEvent>),
+    Unbounded(mpsc::UnboundedSender<ReportEvent>),
+}
+
+enum EventReceiver {
+    Bounded(mpsc::Receiver<ReportEvent>),
+    Unbounded(mpsc::UnboundedReceiver<ReportEvent>),
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        config::Config,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn receives_all_events_of_a_download() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(50)
+            .finish()
+            .condow(Config::default().part_size_bytes(50).max_concurrency(1))
+            .unwrap();
+
+        let (reporter, receiver) = ChannelReporter::unbounded();
+
+        let result = condow
+            .downloader()
+            .download_wrep(NoLocation, .., reporter)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap();
+        assert_eq!(result, blob);
+
+        let events = receiver.collect::<Vec<_>>().await;
+
+        assert!(matches!(events[0], ReportEvent::SizeRequestStarted { .. }));
+        assert_eq!(
+            events[2],
+            ReportEvent::EffectiveRange(InclusiveRange(0, 99))
+        );
+        assert_eq!(events[3], ReportEvent::DownloadStarted);
+        assert!(matches!(
+            events.last(),
+            Some(ReportEvent::DownloadCompleted { .. })
+        ));
+        let n_parts_completed = events
+            .iter()
+            .filter(|e| matches!(e, ReportEvent::PartCompleted { .. }))
+            .count();
+        assert_eq!(n_parts_completed, 2);
+    }
+
+    #[tokio::test]
+    async fn a_full_channel_drops_events() {
+        let (reporter, mut receiver) = ChannelReporter::bounded(NonZeroUsize::new(2).unwrap());
+
+        reporter.queue_full();
+        reporter.download_started();
+        reporter.panic_detected("boom");
+        reporter.clone().queue_full();
+
+        assert_eq!(reporter.n_dropped(), 2);
+        assert_eq!(receiver.n_dropped(), 2);
+
+        drop(reporter);
+
+        assert_eq!(receiver.recv().await, Some(ReportEvent::QueueFull));
+        assert_eq!(receiver.try_recv(), Some(ReportEvent::DownloadStarted));
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[test]
+    fn a_dropped_receiver_is_not_counted() {
+        let (reporter, receiver) = ChannelReporter::bounded(NonZeroUsize::new(1).unwrap());
+        drop(receiver);
+
+        reporter.queue_full();
+        reporter.queue_full();
+
+        assert_eq!(reporter.n_dropped(), 0);
+    }
+}
@@ -0,0 +1,486 @@
+//! Collecting metrics with histograms
+//!
+//! A [MetricsReporterFactory] aggregates the measurements of all
+//! [MetricsReporter]s it created. The metrics are grouped by a label
+//! derived from the location of a download. The mapping from a location
+//! to a label is supplied by the user so that the number of labels stays low.
+//!
+//! The collected metrics can be rendered in the
+//! [Prometheus text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+//! via [MetricsReporterFactory::encode_text].
+//!
+//! Requires the feature `metrics`.
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    errors::{CondowError, IoError},
+    InclusiveRange,
+};
+
+use super::{Reporter, ReporterFactory};
+
+/// Upper bounds of the buckets for latencies in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Upper bounds of the buckets for durations of whole downloads in seconds
+pub const DOWNLOAD_DURATION_BUCKETS: &[f64] = &[
+    0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+];
+
+/// Upper bounds of the buckets for chunk sizes in bytes
+pub const CHUNK_SIZE_BUCKETS: &[f64] = &[
+    1_024.0,
+    4_096.0,
+    16_384.0,
+    65_536.0,
+    262_144.0,
+    1_048_576.0,
+    4_194_304.0,
+    16_777_216.0,
+];
+
+/// Upper bounds of the buckets for throughput in bytes per second
+pub const THROUGHPUT_BUCKETS: &[f64] = &[
+    100_000.0,
+    1_000_000.0,
+    10_000_000.0,
+    50_000_000.0,
+    100_000_000.0,
+    250_000_000.0,
+    500_000_000.0,
+    1_000_000_000.0,
+    10_000_000_000.0,
+];
+
+/// The label used if no mapping from locations to labels was given
+pub const DEFAULT_LABEL: &str = "default";
+
+type LocationLabeler = dyn Fn(&dyn fmt::Display) -> String + Send + Sync + 'static;
+
+/// Name, help text and accessor of a metric
+type MetricDef<T> = (&'static str, &'static str, fn(&LabelMetrics) -> &T);
+
+/// Creates [MetricsReporter]s and collects their metrics
+///
+/// Clones share the collected metrics.
+#[derive(Clone)]
+pub struct MetricsReporterFactory {
+    labeler: Arc<LocationLabeler>,
+    metrics: Arc<Mutex<BTreeMap<String, Arc<LabelMetrics>>>>,
+}
+
+impl MetricsReporterFactory {
+    /// Creates a factory which puts all downloads under the label [DEFAULT_LABEL]
+    pub fn new() -> Self {
+        Self::with_location_labels(|_| DEFAULT_LABEL.to_string())
+    }
+
+    /// Creates a factory which maps the location of a download to a label
+    ///
+    /// The mapping should only return a small number of distinct labels
+    /// since metrics are kept for each label.
+    pub fn with_location_labels<F>(labeler: F) -> Self
+    where
+        F: Fn(&dyn fmt::Display) -> String + Send + Sync + 'static,
+    {
+        Self {
+            labeler: Arc::new(labeler),
+            metrics: Default::default(),
+        }
+    }
+
+    /// Renders all collected metrics in the Prometheus text exposition format
+    pub fn encode_text(&self) -> String {
+        let metrics = self.metrics.lock().unwrap();
+        let mut out = String::new();
+
+        let histograms: [MetricDef<Histogram>; 5] = [
+            (
+                "condow_part_latency_seconds",
+                "Time to download a complete part",
+                |m| &m.part_latency,
+            ),
+            (
+                "condow_time_to_first_byte_seconds",
+                "Time from sending a request until the first bytes were received",
+                |m| &m.time_to_first_byte,
+            ),
+            (
+                "condow_chunk_size_bytes",
+                "Sizes of the received chunks",
+                |m| &m.chunk_size,
+            ),
+            (
+                "condow_download_duration_seconds",
+                "Time to complete a download",
+                |m| &m.download_duration,
+            ),
+            (
+                "condow_download_throughput_bytes_per_second",
+                "Throughput of completed downloads",
+                |m| &m.throughput,
+            ),
+        ];
+
+        for (name, help, get) in histograms {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} histogram", name);
+            for (label, label_metrics) in metrics.iter() {
+                get(label_metrics).encode(&mut out, name, label);
+            }
+        }
+
+        let counters: [MetricDef<AtomicU64>; 6] = [
+            ("condow_retries_total", "Retry attempts", |m| &m.n_retries),
+            (
+                "condow_stream_resumes_total",
+                "Attempts to resume a broken stream",
+                |m| &m.n_stream_resumes,
+            ),
+            ("condow_panics_total", "Detected panics", |m| &m.n_panics),
+            (
+                "condow_queue_full_total",
+                "Occurrences of all queues being full",
+                |m| &m.n_queue_full,
+            ),
+            ("condow_part_failures_total", "Failed parts", |m| {
+                &m.n_part_failures
+            }),
+            ("condow_download_failures_total", "Failed downloads", |m| {
+                &m.n_download_failures
+            }),
+        ];
+
+        for (name, help, get) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (label, label_metrics) in metrics.iter() {
+                let _ = writeln!(
+                    out,
+                    "{}{{location=\"{}\"}} {}",
+                    name,
+                    escape_label_value(label),
+                    get(label_metrics).load(Ordering::SeqCst)
+                );
+            }
+        }
+
+        out
+    }
+
+    fn label_metrics(&self, location: &dyn fmt::Display) -> Arc<LabelMetrics> {
+        let label = (self.labeler)(location);
+        let mut metrics = self.metrics.lock().unwrap();
+        Arc::clone(metrics.entry(label).or_default())
+    }
+}
+
+impl Default for MetricsReporterFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReporterFactory for MetricsReporterFactory {
+    type ReporterType = MetricsReporter;
+
+    fn make(&self, location: &dyn fmt::Display) -> Self::ReporterType {
+        MetricsReporter {
+            metrics: self.label_metrics(location),
+            n_bytes_received: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+/// A [Reporter] which records into the metrics of its [MetricsReporterFactory]
+#[derive(Clone)]
+pub struct MetricsReporter {
+    metrics: Arc<LabelMetrics>,
+    n_bytes_received: Arc<AtomicU64>,
+}
+
+impl Reporter for MetricsReporter {
+    fn download_completed(&self, time: Duration) {
+        let metrics = self.metrics.as_ref();
+        let secs = time.as_secs_f64();
+        metrics.download_duration.observe(secs);
+        if secs > 0.0 {
+            let n_bytes = self.n_bytes_received.load(Ordering::SeqCst);
+            metrics.throughput.observe(n_bytes as f64 / secs);
+        }
+    }
+
+    fn download_failed(&self, _time: Option<Duration>) {
+        self.metrics
+            .n_download_failures
+            .fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn retry_attempt(
+        &self,
+        _location: &dyn fmt::Display,
+        _error: &CondowError,
+        _next_in: Duration,
+    ) {
+        self.metrics.n_retries.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn stream_resume_attempt(
+        &self,
+        _location: &dyn fmt::Display,
+        _error: &IoError,
+        _orig_range: InclusiveRange,
+        _remaining_range: InclusiveRange,
+    ) {
+        self.metrics.n_stream_resumes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn panic_detected(&self, _msg: &str) {
+        self.metrics.n_panics.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn queue_full(&self) {
+        self.metrics.n_queue_full.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn chunk_completed(
+        &self,
+        _part_index: u64,
+        _chunk_index: usize,
+        n_bytes: usize,
+        _time: Duration,
+    ) {
+        let metrics = self.metrics.as_ref();
+        self.n_bytes_received
+            .fetch_add(n_bytes as u64, Ordering::SeqCst);
+        metrics.chunk_size.observe(n_bytes as f64);
+    }
+
+    fn request_completed(
+        &self,
+        _location: &dyn fmt::Display,
+        _attempt: usize,
+        _latency: Duration,
+        time_to_first_byte: Duration,
+    ) {
+        self.metrics
+            .time_to_first_byte
+            .observe(time_to_first_byte.as_secs_f64());
+    }
+
+    fn part_completed(&self, _part_index: u64, _n_chunks: usize, _n_bytes: u64, time: Duration) {
+        self.metrics.part_latency.observe(time.as_secs_f64());
+    }
+
+    fn part_failed(&self, _error: &CondowError, _part_index: u64, _range: &InclusiveRange) {
+        self.metrics.n_part_failures.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Metrics of all downloads with the same label
+struct LabelMetrics {
+    part_latency: Histogram,
+    time_to_first_byte: Histogram,
+    chunk_size: Histogram,
+    download_duration: Histogram,
+    throughput: Histogram,
+    n_retries: AtomicU64,
+    n_stream_resumes: AtomicU64,
+    n_panics: AtomicU64,
+    n_queue_full: AtomicU64,
+    n_part_failures: AtomicU64,
+    n_download_failures: AtomicU64,
+}
+
+impl Default for LabelMetrics {
+    fn default() -> Self {
+        Self {
+            part_latency: Histogram::new(LATENCY_BUCKETS),
+            time_to_first_byte: Histogram::new(LATENCY_BUCKETS),
+            chunk_size: Histogram::new(CHUNK_SIZE_BUCKETS),
+            download_duration: Histogram::new(DOWNLOAD_DURATION_BUCKETS),
+            throughput: Histogram::new(THROUGHPUT_BUCKETS),
+            n_retries: AtomicU64::new(0),
+            n_stream_resumes: AtomicU64::new(0),
+            n_panics: AtomicU64::new(0),
+            n_queue_full: AtomicU64::new(0),
+            n_part_failures: AtomicU64::new(0),
+            n_download_failures: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A histogram with fixed buckets
+struct Histogram {
+    bounds: &'static [f64],
+    state: Mutex<HistogramState>,
+}
+
+struct HistogramState {
+    /// Not cumulative. The last bucket is `+Inf`.
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            state: Mutex::new(HistogramState {
+                bucket_counts: vec![0; bounds.len() + 1],
+                sum: 0.0,
+                count: 0,
+            }),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        let idx = self
+            .bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(self.bounds.len());
+        let mut state = self.state.lock().unwrap();
+        state.bucket_counts[idx] += 1;
+        state.sum += value;
+        state.count += 1;
+    }
+
+    fn encode(&self, out: &mut String, name: &str, label: &str) {
+        let label = escape_label_value(label);
+        let state = self.state.lock().unwrap();
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(state.bucket_counts.iter()) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{location=\"{}\",le=\"{}\"}} {}",
+                name, label, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{location=\"{}\",le=\"+Inf\"}} {}",
+            name, label, state.count
+        );
+        let _ = writeln!(out, "{}_sum{{location=\"{}\"}} {}", name, label, state.sum);
+        let _ = writeln!(
+            out,
+            "{}_count{{location=\"{}\"}} {}",
+            name, label, state.count
+        );
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        config::{Config, RetryConfig},
+    };
+
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 2.0]);
+        histogram.observe(0.5);
+        histogram.observe(1.0);
+        histogram.observe(1.5);
+        histogram.observe(3.0);
+
+        let mut out = String::new();
+        histogram.encode(&mut out, "h", "a");
+
+        let expected = "h_bucket{location=\"a\",le=\"1\"} 2\n\
+                        h_bucket{location=\"a\",le=\"2\"} 3\n\
+                        h_bucket{location=\"a\",le=\"+Inf\"} 4\n\
+                        h_sum{location=\"a\"} 6\n\
+                        h_count{location=\"a\"} 4\n";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn reporters_of_the_same_label_share_metrics() {
+        let factory = MetricsReporterFactory::with_location_labels(|location| {
+            location.to_string().split('/').next().unwrap().to_string()
+        });
+
+        factory.make(&"bucket_a/1").queue_full();
+        factory.make(&"bucket_a/2").queue_full();
+        factory.make(&"bucket_b/1").panic_detected("boom");
+
+        let text = factory.encode_text();
+        assert!(text.contains("condow_queue_full_total{location=\"bucket_a\"} 2\n"));
+        assert!(text.contains("condow_queue_full_total{location=\"bucket_b\"} 0\n"));
+        assert!(text.contains("condow_panics_total{location=\"bucket_b\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn records_a_download() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(10)
+            .responses()
+            .failure(CondowError::new_remote("remote failed"))
+            .success()
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(50)
+                    .max_concurrency(1)
+                    .retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let factory = MetricsReporterFactory::new();
+        let result = condow
+            .downloader_with_reporting(factory.clone())
+            .download_rep(NoLocation, ..)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap();
+        assert_eq!(result, blob);
+
+        let text = factory.encode_text();
+        assert!(text.contains("condow_part_latency_seconds_count{location=\"default\"} 2\n"));
+        assert!(text.contains("condow_time_to_first_byte_seconds_count{location=\"default\"} 2\n"));
+        assert!(text.contains("condow_chunk_size_bytes_count{location=\"default\"} 10\n"));
+        assert!(text.contains("condow_chunk_size_bytes_sum{location=\"default\"} 100\n"));
+        assert!(text.contains("condow_download_duration_seconds_count{location=\"default\"} 1\n"));
+        assert!(text.contains("condow_retries_total{location=\"default\"} 1\n"));
+        assert!(text.contains("condow_download_failures_total{location=\"default\"} 0\n"));
+    }
+}
@@ -7,10 +7,27 @@ use std::{fmt, time::Duration};
 
 use crate::{
     errors::{CondowError, IoError},
-    InclusiveRange,
+    DownloadContext, InclusiveRange,
 };
 
+pub use aggregating_reporter::{
+    AggregatingReporter, AggregatingReporterFactory, Percentiles, SessionCollector, SessionReport,
+};
+pub use channel_reporter::{ChannelReporter, ReportEvent, ReportEventReceiver};
+#[cfg(feature = "metrics")]
+pub use metrics_reporter::{MetricsReporter, MetricsReporterFactory};
+pub use progress_reporter::{Progress, ProgressReporter};
 pub use simple_reporter::*;
+#[cfg(feature = "tracing")]
+pub use tracing_reporter::{TracingReporter, TracingReporterFactory};
+
+mod aggregating_reporter;
+mod channel_reporter;
+#[cfg(feature = "metrics")]
+pub mod metrics_reporter;
+mod progress_reporter;
+#[cfg(feature = "tracing")]
+mod tracing_reporter;
 
 pub trait ReporterFactory: Send + Sync + 'static {
     type ReporterType: Reporter;
@@ -30,6 +47,12 @@ pub trait ReporterFactory: Send + Sync + 'static {
 /// downloading too much with measuring.
 #[allow(unused_variables)]
 pub trait Reporter: Clone + Send + Sync + 'static {
+    /// The [DownloadContext] given for the download
+    ///
+    /// **This is the first method called on a [Reporter] if a context was given.**
+    /// It is not called otherwise.
+    fn download_context(&self, context: &DownloadContext) {}
+
     fn effective_range(&self, range: InclusiveRange) {}
     /// The actual IO started
     fn download_started(&self) {}
@@ -60,6 +83,55 @@ pub trait Reporter: Clone + Send + Sync + 'static {
     ) {
     }
 
+    /// A request for the size of a BLOB started
+    fn size_request_started(&self, location: &dyn fmt::Display) {}
+
+    /// A request for the size of a BLOB completed
+    ///
+    /// `time` includes all retries.
+    fn size_request_completed(&self, location: &dyn fmt::Display, size: u64, time: Duration) {}
+
+    /// A request for the size of a BLOB failed
+    ///
+    /// `time` includes all retries.
+    fn size_request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        error: &CondowError,
+        time: Duration,
+    ) {
+    }
+
+    /// A request for a stream of bytes to the [crate::condow_client::CondowClient] started
+    ///
+    /// `attempt` starts with 1 and is increased on each retry
+    /// and on each request made to resume a broken stream.
+    fn request_started(&self, location: &dyn fmt::Display, attempt: usize) {}
+
+    /// A request for a stream of bytes completed with the first bytes received
+    ///
+    /// `latency` is the time until the client returned the stream and
+    /// `time_to_first_byte` the time until the first bytes were received
+    /// (or the stream ended).
+    fn request_completed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        latency: Duration,
+        time_to_first_byte: Duration,
+    ) {
+    }
+
+    /// A request for a stream of bytes failed before any bytes were received
+    fn request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        error: &CondowError,
+        time: Duration,
+    ) {
+    }
+
     /// A panic was detected
     ///
     /// Unless from a bug in this library it is most likely caused by the [crate::condow_client::CondowClient] implementation
@@ -68,6 +140,18 @@ pub trait Reporter: Clone + Send + Sync + 'static {
     /// All queues are full so no new request could be scheduled
     fn queue_full(&self) {}
 
+    /// The memory budget is exhausted and scheduling the part has to wait
+    ///
+    /// `n_bytes` is the number of bytes requested for the part.
+    fn memory_budget_exhausted(&self, part_index: u64, n_bytes: u64) {}
+
+    /// Memory of the budget was reserved for a part
+    ///
+    /// `bytes_in_use` is the number of bytes reserved by all downloads
+    /// sharing the budget after the reservation. `waited` is the time it
+    /// took to get the reservation.
+    fn memory_reserved(&self, part_index: u64, n_bytes: u64, bytes_in_use: u64, waited: Duration) {}
+
     /// A part was completed
     fn chunk_completed(&self, part_index: u64, chunk_index: usize, n_bytes: usize, time: Duration) {
     }
@@ -101,6 +185,11 @@ impl ReporterFactory for NoReporting {
 pub struct CompositeReporter<RA: Reporter, RB: Reporter>(pub RA, pub RB);
 
 impl<RA: Reporter, RB: Reporter> Reporter for CompositeReporter<RA, RB> {
+    fn download_context(&self, context: &DownloadContext) {
+        self.0.download_context(context);
+        self.1.download_context(context);
+    }
+
     fn effective_range(&self, range: InclusiveRange) {
         self.0.effective_range(range);
         self.1.effective_range(range);
@@ -139,6 +228,55 @@ impl<RA: Reporter, RB: Reporter> Reporter for CompositeReporter<RA, RB> {
             .stream_resume_attempt(location, error, orig_range, remaining_range);
     }
 
+    fn size_request_started(&self, location: &dyn fmt::Display) {
+        self.0.size_request_started(location);
+        self.1.size_request_started(location);
+    }
+
+    fn size_request_completed(&self, location: &dyn fmt::Display, size: u64, time: Duration) {
+        self.0.size_request_completed(location, size, time);
+        self.1.size_request_completed(location, size, time);
+    }
+
+    fn size_request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        self.0.size_request_failed(location, error, time);
+        self.1.size_request_failed(location, error, time);
+    }
+
+    fn request_started(&self, location: &dyn fmt::Display, attempt: usize) {
+        self.0.request_started(location, attempt);
+        self.1.request_started(location, attempt);
+    }
+
+    fn request_completed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        latency: Duration,
+        time_to_first_byte: Duration,
+    ) {
+        self.0
+            .request_completed(location, attempt, latency, time_to_first_byte);
+        self.1
+            .request_completed(location, attempt, latency, time_to_first_byte);
+    }
+
+    fn request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        self.0.request_failed(location, attempt, error, time);
+        self.1.request_failed(location, attempt, error, time);
+    }
+
     fn panic_detected(&self, msg: &str) {
         self.0.panic_detected(msg);
         self.1.panic_detected(msg);
@@ -149,6 +287,18 @@ impl<RA: Reporter, RB: Reporter> Reporter for CompositeReporter<RA, RB> {
         self.1.queue_full();
     }
 
+    fn memory_budget_exhausted(&self, part_index: u64, n_bytes: u64) {
+        self.0.memory_budget_exhausted(part_index, n_bytes);
+        self.1.memory_budget_exhausted(part_index, n_bytes);
+    }
+
+    fn memory_reserved(&self, part_index: u64, n_bytes: u64, bytes_in_use: u64, waited: Duration) {
+        self.0
+            .memory_reserved(part_index, n_bytes, bytes_in_use, waited);
+        self.1
+            .memory_reserved(part_index, n_bytes, bytes_in_use, waited);
+    }
+
     fn chunk_completed(
         &self,
         part_index: u64,
@@ -271,14 +421,8 @@ mod simple_reporter {
                 } else {
                     Instant::now() - *inner.download_started_at.lock().unwrap()
                 };
-            let n_bytes_received = inner.n_bytes_received.load(Ordering::SeqCst);
-            let bytes_per_second_f64 = if n_bytes_received > 0 {
-                n_bytes_received as f64 / download_time.as_secs_f64()
-            } else {
-                0.0
-            };
 
-            SimpleReport {
+            let mut report = SimpleReport {
                 location: self.inner.location.as_ref().clone(),
                 effective_range: *inner.effective_range.lock().unwrap(),
                 is_finished: self.is_download_finished(),
@@ -287,13 +431,18 @@ mod simple_reporter {
                 n_stream_resume_attempts: inner.n_resume_stream_attempts.load(Ordering::SeqCst),
                 n_panics: inner.n_panics_detected.load(Ordering::SeqCst),
                 download_time,
-                bytes_per_second: bytes_per_second_f64 as u64,
-                megabytes_per_second: bytes_per_second_f64 / 1_000_000.0,
-                mebibytes_per_second: bytes_per_second_f64 / 1_048_576.0,
-                gigabits_per_second: (bytes_per_second_f64 * 8.0) / 1_000_000_000.0,
-                gibibits_per_second: (bytes_per_second_f64 * 8.0) / 1_073_741_824.0,
+                bytes_per_second: 0,
+                megabytes_per_second: 0.0,
+                mebibytes_per_second: 0.0,
+                gigabits_per_second: 0.0,
+                gibibits_per_second: 0.0,
                 n_queue_full: inner.n_queue_full.load(Ordering::SeqCst),
-                n_bytes_received,
+                n_memory_budget_exhausted: inner.n_memory_budget_exhausted.load(Ordering::SeqCst),
+                memory_budget_wait_time: Duration::from_micros(
+                    inner.memory_budget_wait_us.load(Ordering::SeqCst),
+                ),
+                max_memory_bytes_in_use: inner.max_memory_bytes_in_use.load(Ordering::SeqCst),
+                n_bytes_received: inner.n_bytes_received.load(Ordering::SeqCst),
                 n_chunks_received: inner.n_chunks_received.load(Ordering::SeqCst),
                 n_parts_received: inner.n_parts_received.load(Ordering::SeqCst),
                 min_chunk_bytes: inner.min_chunk_bytes.load(Ordering::SeqCst),
@@ -306,7 +455,9 @@ mod simple_reporter {
                 max_chunks_per_part: inner.max_chunks_per_part.load(Ordering::SeqCst),
                 min_part_time: Duration::from_micros(inner.min_part_us.load(Ordering::SeqCst)),
                 max_part_time: Duration::from_micros(inner.max_part_us.load(Ordering::SeqCst)),
-            }
+            };
+            report.set_throughput();
+            report
         }
     }
 
@@ -317,6 +468,7 @@ mod simple_reporter {
     }
 
     #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SimpleReport {
         pub location: String,
         pub effective_range: Option<InclusiveRange>,
@@ -335,6 +487,13 @@ mod simple_reporter {
         pub gigabits_per_second: f64,
         pub gibibits_per_second: f64,
         pub n_queue_full: usize,
+        /// Number of times a part had to wait for the memory budget
+        pub n_memory_budget_exhausted: usize,
+        /// Total time spent waiting for the memory budget
+        pub memory_budget_wait_time: Duration,
+        /// Maximum of bytes reserved from the memory budget by all downloads
+        /// as seen by this download
+        pub max_memory_bytes_in_use: u64,
         pub n_bytes_received: u64,
         pub n_chunks_received: u64,
         pub n_parts_received: u64,
@@ -350,6 +509,68 @@ mod simple_reporter {
         pub max_part_time: Duration,
     }
 
+    impl SimpleReport {
+        /// Merge another report into this one
+        ///
+        /// Counters and download times are summed up, minimums and maximums
+        /// are combined and the throughput is calculated from the summed
+        /// bytes and download times. Location and effective range are only
+        /// kept if they are equal in both reports. Otherwise the location
+        /// becomes `"*"` and the effective range `None`.
+        pub fn merge(&mut self, other: &SimpleReport) {
+            if self.location != other.location {
+                self.location = "*".to_string();
+            }
+            if self.effective_range != other.effective_range {
+                self.effective_range = None;
+            }
+            self.is_finished &= other.is_finished;
+            self.is_failed |= other.is_failed;
+            self.n_retries += other.n_retries;
+            self.n_stream_resume_attempts += other.n_stream_resume_attempts;
+            self.n_panics += other.n_panics;
+            self.download_time += other.download_time;
+            self.n_queue_full += other.n_queue_full;
+            self.n_memory_budget_exhausted += other.n_memory_budget_exhausted;
+            self.memory_budget_wait_time += other.memory_budget_wait_time;
+            self.max_memory_bytes_in_use = self
+                .max_memory_bytes_in_use
+                .max(other.max_memory_bytes_in_use);
+            self.n_bytes_received += other.n_bytes_received;
+            self.n_chunks_received += other.n_chunks_received;
+            self.n_parts_received += other.n_parts_received;
+            self.min_chunk_bytes = self.min_chunk_bytes.min(other.min_chunk_bytes);
+            self.max_chunk_bytes = self.max_chunk_bytes.max(other.max_chunk_bytes);
+            self.min_chunk_time = self.min_chunk_time.min(other.min_chunk_time);
+            self.max_chunk_time = self.max_chunk_time.max(other.max_chunk_time);
+            self.min_part_bytes = self.min_part_bytes.min(other.min_part_bytes);
+            self.max_part_bytes = self.max_part_bytes.max(other.max_part_bytes);
+            self.min_chunks_per_part = self.min_chunks_per_part.min(other.min_chunks_per_part);
+            self.max_chunks_per_part = self.max_chunks_per_part.max(other.max_chunks_per_part);
+            self.min_part_time = self.min_part_time.min(other.min_part_time);
+            self.max_part_time = self.max_part_time.max(other.max_part_time);
+            self.set_throughput();
+        }
+
+        /// Calculates the throughput from the bytes received and the download time
+        ///
+        /// The throughput is 0 if no bytes were received or no time passed.
+        fn set_throughput(&mut self) {
+            let secs = self.download_time.as_secs_f64();
+            let bytes_per_second_f64 = if self.n_bytes_received > 0 && secs > 0.0 {
+                self.n_bytes_received as f64 / secs
+            } else {
+                0.0
+            };
+
+            self.bytes_per_second = bytes_per_second_f64 as u64;
+            self.megabytes_per_second = bytes_per_second_f64 / 1_000_000.0;
+            self.mebibytes_per_second = bytes_per_second_f64 / 1_048_576.0;
+            self.gigabits_per_second = (bytes_per_second_f64 * 8.0) / 1_000_000_000.0;
+            self.gibibits_per_second = (bytes_per_second_f64 * 8.0) / 1_073_741_824.0;
+        }
+    }
+
     impl Reporter for SimpleReporter {
         fn effective_range(&self, effective_range: InclusiveRange) {
             *self.inner.effective_range.lock().unwrap() = Some(effective_range);
@@ -397,6 +618,28 @@ mod simple_reporter {
             self.inner.n_queue_full.fetch_add(1, Ordering::SeqCst);
         }
 
+        fn memory_budget_exhausted(&self, _part_index: u64, _n_bytes: u64) {
+            self.inner
+                .n_memory_budget_exhausted
+                .fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn memory_reserved(
+            &self,
+            _part_index: u64,
+            _n_bytes: u64,
+            bytes_in_use: u64,
+            waited: Duration,
+        ) {
+            let inner = self.inner.as_ref();
+            inner
+                .memory_budget_wait_us
+                .fetch_add(waited.as_micros() as u64, Ordering::SeqCst);
+            inner
+                .max_memory_bytes_in_use
+                .fetch_max(bytes_in_use, Ordering::SeqCst);
+        }
+
         fn chunk_completed(
             &self,
             _part_index: u64,
@@ -440,6 +683,9 @@ mod simple_reporter {
         download_finished_at: Mutex<Option<Instant>>,
         is_failed: AtomicBool,
         n_queue_full: AtomicUsize,
+        n_memory_budget_exhausted: AtomicUsize,
+        memory_budget_wait_us: AtomicU64,
+        max_memory_bytes_in_use: AtomicU64,
         n_bytes_received: AtomicU64,
         n_chunks_received: AtomicU64,
         n_parts_received: AtomicU64,
@@ -473,6 +719,9 @@ mod simple_reporter {
                 n_chunks_received: AtomicU64::new(0),
                 n_parts_received: AtomicU64::new(0),
                 n_queue_full: AtomicUsize::new(0),
+                n_memory_budget_exhausted: AtomicUsize::new(0),
+                memory_budget_wait_us: AtomicU64::new(0),
+                max_memory_bytes_in_use: AtomicU64::new(0),
                 min_chunk_bytes: AtomicUsize::new(usize::MAX),
                 max_chunk_bytes: AtomicUsize::new(0),
                 min_chunk_us: AtomicU64::new(u64::MAX),
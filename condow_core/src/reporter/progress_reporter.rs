@@ -0,0 +1,429 @@
+//! Tracking the progress of a download
+//!
+//! A [ProgressReporter] keeps a [Progress] which can be observed
+//! via a [tokio::sync::watch::Receiver]. The receiver is notified
+//! whenever the progress changes.
+//!
+//! The values of [Progress] map directly onto progress bars like those
+//! of `indicatif`:
+//!
+//! ```ignore
+//! let bar = indicatif::ProgressBar::new(0);
+//! while progress_rx.changed().await.is_ok() {
+//!     let progress = *progress_rx.borrow();
+//!     if let Some(length) = progress.length() {
+//!         bar.set_length(length);
+//!     }
+//!     bar.set_position(progress.position());
+//! }
+//! ```
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+
+use crate::{errors::CondowError, InclusiveRange};
+
+use super::Reporter;
+
+/// A snapshot of the progress of a download
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Bytes received so far
+    pub bytes_done: u64,
+    /// Bytes expected in total
+    ///
+    /// `None` until the effective range of the download is known.
+    pub bytes_total: Option<u64>,
+    /// Parts completely received
+    pub parts_done: u64,
+    /// Parts expected in total
+    ///
+    /// `None` until the effective range of the download is known
+    /// or if the part size is unknown.
+    pub parts_total: Option<u64>,
+    /// Time elapsed since the download started
+    pub elapsed: Duration,
+    /// The average throughput since the download started
+    pub bytes_per_second: f64,
+    /// The throughput of the last few seconds
+    ///
+    /// An exponentially weighted moving average with a time constant of
+    /// 5 seconds. Early in a download this is the average throughput.
+    pub current_bytes_per_second: f64,
+    /// `true` once the download completed successfully
+    pub is_finished: bool,
+    /// `true` once the download failed
+    pub is_failed: bool,
+}
+
+impl Progress {
+    /// The position to be displayed by a progress bar (bytes done)
+    pub fn position(&self) -> u64 {
+        self.bytes_done
+    }
+
+    /// The length to be displayed by a progress bar (bytes total)
+    pub fn length(&self) -> Option<u64> {
+        self.bytes_total
+    }
+
+    /// The fraction of bytes received between `0.0` and `1.0`
+    pub fn fraction(&self) -> Option<f64> {
+        match self.bytes_total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes_done as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+
+    /// The bytes still to be received
+    pub fn bytes_remaining(&self) -> Option<u64> {
+        self.bytes_total
+            .map(|total| total.saturating_sub(self.bytes_done))
+    }
+
+    /// The estimated time until the download completes
+    ///
+    /// Based on the current throughput. `None` if the total is not
+    /// known or nothing has been received yet.
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.bytes_remaining()?;
+        if remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        if self.current_bytes_per_second <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            remaining as f64 / self.current_bytes_per_second,
+        ))
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            bytes_done: 0,
+            bytes_total: None,
+            parts_done: 0,
+            parts_total: None,
+            elapsed: Duration::ZERO,
+            bytes_per_second: 0.0,
+            current_bytes_per_second: 0.0,
+            is_finished: false,
+            is_failed: false,
+        }
+    }
+}
+
+/// A [Reporter] which tracks the [Progress] of a single download
+///
+/// Clones share the same progress.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: watch::Sender<Progress>,
+    part_size: Option<u64>,
+    started_at: Mutex<Option<Instant>>,
+    throughput: Mutex<Throughput>,
+}
+
+impl ProgressReporter {
+    /// Creates a new [ProgressReporter]
+    ///
+    /// The total number of parts will not be known.
+    pub fn new() -> Self {
+        Self::create(None)
+    }
+
+    /// Creates a new [ProgressReporter] which also calculates
+    /// the total number of parts from the part size
+    pub fn with_part_size(part_size: u64) -> Self {
+        Self::create(Some(part_size))
+    }
+
+    /// Returns a receiver which is notified whenever the progress changes
+    pub fn subscribe(&self) -> watch::Receiver<Progress> {
+        self.inner.sender.subscribe()
+    }
+
+    /// Returns the current progress
+    pub fn progress(&self) -> Progress {
+        *self.inner.sender.borrow()
+    }
+
+    fn create(part_size: Option<u64>) -> Self {
+        let (sender, _) = watch::channel(Progress::default());
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                part_size: part_size.filter(|&size| size > 0),
+                started_at: Mutex::new(None),
+                throughput: Mutex::new(Throughput::default()),
+            }),
+        }
+    }
+
+    fn update<F: FnOnce(&mut Progress)>(&self, f: F) {
+        let now = Instant::now();
+        let started_at = *self.inner.started_at.lock().unwrap();
+        let current_bytes_per_second = started_at.map(|started_at| {
+            self.inner
+                .throughput
+                .lock()
+                .unwrap()
+                .bytes_per_second(started_at, now)
+        });
+        self.inner.sender.send_modify(|progress| {
+            f(progress);
+            if let Some(started_at) = started_at {
+                let elapsed = now.saturating_duration_since(started_at);
+                progress.elapsed = elapsed;
+                let secs = elapsed.as_secs_f64();
+                if secs > 0.0 {
+                    progress.bytes_per_second = progress.bytes_done as f64 / secs;
+                }
+            }
+            if let Some(current_bytes_per_second) = current_bytes_per_second {
+                progress.current_bytes_per_second = current_bytes_per_second;
+            }
+        });
+    }
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for ProgressReporter {
+    fn effective_range(&self, range: InclusiveRange) {
+        let part_size = self.inner.part_size;
+        self.update(|progress| {
+            progress.bytes_total = Some(range.len());
+            progress.parts_total = part_size.map(|size| range.len().div_ceil(size));
+        });
+    }
+
+    fn download_started(&self) {
+        *self.inner.started_at.lock().unwrap() = Some(Instant::now());
+        self.update(|_| {});
+    }
+
+    fn download_completed(&self, _time: Duration) {
+        self.update(|progress| progress.is_finished = true);
+    }
+
+    fn download_failed(&self, _time: Option<Duration>) {
+        self.update(|progress| progress.is_failed = true);
+    }
+
+    fn chunk_completed(
+        &self,
+        _part_index: u64,
+        _chunk_index: usize,
+        n_bytes: usize,
+        _time: Duration,
+    ) {
+        self.inner
+            .throughput
+            .lock()
+            .unwrap()
+            .record(n_bytes as u64, Instant::now());
+        self.update(|progress| progress.bytes_done += n_bytes as u64);
+    }
+
+    fn part_completed(&self, _part_index: u64, _n_chunks: usize, _n_bytes: u64, _time: Duration) {
+        self.update(|progress| progress.parts_done += 1);
+    }
+
+    fn part_failed(&self, _error: &CondowError, _part_index: u64, _range: &InclusiveRange) {
+        self.update(|progress| progress.is_failed = true);
+    }
+}
+
+/// The time constant of [Progress::current_bytes_per_second]
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+/// An exponentially weighted moving average of the throughput
+///
+/// The bytes received decay exponentially with [THROUGHPUT_WINDOW]
+/// as the time constant.
+#[derive(Default)]
+struct Throughput {
+    decayed_bytes: f64,
+    updated_at: Option<Instant>,
+}
+
+impl Throughput {
+    fn record(&mut self, n_bytes: u64, now: Instant) {
+        self.decay(now);
+        self.decayed_bytes += n_bytes as f64;
+    }
+
+    /// The throughput at `now` of a download started at `started_at`
+    ///
+    /// Corrected for the part of the window before the download started.
+    fn bytes_per_second(&mut self, started_at: Instant, now: Instant) -> f64 {
+        self.decay(now);
+        let window = THROUGHPUT_WINDOW.as_secs_f64();
+        let elapsed = now.saturating_duration_since(started_at).as_secs_f64();
+        let filled = 1.0 - (-elapsed / window).exp();
+        if filled <= 0.0 {
+            return 0.0;
+        }
+        self.decayed_bytes / window / filled
+    }
+
+    fn decay(&mut self, now: Instant) {
+        if let Some(updated_at) = self.updated_at {
+            let secs = now.saturating_duration_since(updated_at).as_secs_f64();
+            self.decayed_bytes *= (-secs / THROUGHPUT_WINDOW.as_secs_f64()).exp();
+        }
+        self.updated_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        config::Config,
+        reporter::AggregatingReporterFactory,
+    };
+
+    use super::*;
+
+    #[test]
+    fn eta_and_fraction() {
+        let progress = Progress {
+            bytes_done: 25,
+            bytes_total: Some(100),
+            bytes_per_second: 1.0,
+            current_bytes_per_second: 25.0,
+            ..Default::default()
+        };
+
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(progress.bytes_remaining(), Some(75));
+        assert_eq!(progress.eta(), Some(Duration::from_secs(3)));
+
+        let progress = Progress::default();
+        assert_eq!(progress.fraction(), None);
+        assert_eq!(progress.eta(), None);
+    }
+
+    #[test]
+    fn current_throughput_follows_changes() {
+        let started_at = Instant::now();
+        let mut throughput = Throughput::default();
+
+        let at = |millis: u64| started_at + Duration::from_millis(millis);
+
+        // 1000 bytes per second for 60 seconds
+        for n in 1..=600 {
+            throughput.record(100, at(n * 100));
+        }
+        let rate = throughput.bytes_per_second(started_at, at(60_000));
+        assert!((rate - 1_000.0).abs() < 50.0, "{}", rate);
+
+        // 100 bytes per second for 30 seconds
+        for n in 1..=30 {
+            throughput.record(100, at(60_000 + n * 1_000));
+        }
+        // Sampled right after 100 bytes were received
+        let rate = throughput.bytes_per_second(started_at, at(90_000));
+        assert!((rate - 100.0).abs() < 20.0, "{}", rate);
+    }
+
+    #[test]
+    fn current_throughput_is_the_average_at_the_start() {
+        let started_at = Instant::now();
+        let mut throughput = Throughput::default();
+
+        throughput.record(100, started_at + Duration::from_millis(50));
+        let rate = throughput.bytes_per_second(started_at, started_at + Duration::from_millis(100));
+
+        assert!((rate - 1_000.0).abs() < 10.0, "{}", rate);
+    }
+
+    #[test]
+    fn tracks_bytes_and_parts() {
+        let reporter = ProgressReporter::with_part_size(10);
+        let rx = reporter.subscribe();
+
+        reporter.effective_range(InclusiveRange(0, 24));
+        reporter.download_started();
+        reporter.chunk_completed(0, 0, 10, Duration::ZERO);
+        reporter.part_completed(0, 1, 10, Duration::ZERO);
+        reporter.chunk_completed(1, 0, 5, Duration::ZERO);
+
+        let progress = *rx.borrow();
+        assert_eq!(progress.bytes_done, 15);
+        assert_eq!(progress.bytes_total, Some(25));
+        assert_eq!(progress.parts_done, 1);
+        assert_eq!(progress.parts_total, Some(3));
+        assert!(!progress.is_finished);
+
+        reporter.download_completed(Duration::ZERO);
+        assert!(reporter.progress().is_finished);
+    }
+
+    #[tokio::test]
+    async fn download_with_progress() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(7)
+            .finish()
+            .condow(Config::default().part_size_bytes(30))
+            .unwrap();
+
+        let (stream, progress) = condow
+            .downloader()
+            .download_with_progress(NoLocation, 10..)
+            .await
+            .unwrap();
+
+        let result = stream.into_vec().await.unwrap();
+        assert_eq!(result, blob[10..]);
+
+        let progress = *progress.borrow();
+        assert_eq!(progress.bytes_done, 90);
+        assert_eq!(progress.bytes_total, Some(90));
+        assert_eq!(progress.parts_done, 3);
+        assert_eq!(progress.parts_total, Some(3));
+        assert!(progress.is_finished);
+        assert_eq!(progress.eta(), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn download_with_progress_is_reported() {
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(7)
+            .finish()
+            .condow(Config::default().part_size_bytes(30))
+            .unwrap();
+        let factory = Arc::new(AggregatingReporterFactory::default());
+
+        let (stream, _progress) = condow
+            .downloader_with_reporting_arc(Arc::clone(&factory))
+            .download_with_progress(NoLocation, ..)
+            .await
+            .unwrap();
+        let _ = stream.into_vec().await.unwrap();
+
+        let report = factory.session_report();
+        assert_eq!(report.n_downloads, 1);
+        assert_eq!(report.totals.unwrap().n_bytes_received, 100);
+    }
+}
@@ -0,0 +1,449 @@
+//! Reporting via [tracing]
+//!
+//! A [TracingReporter] opens a span for each download which is a child
+//! of the span current when the [TracingReporter] was created. Each part
+//! gets its own span as a child of the download span.
+//!
+//! Events are emitted within these spans.
+//!
+//! Requires the feature `tracing`.
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::{debug, error, field, info, warn, Span};
+
+use crate::{
+    errors::{CondowError, IoError},
+    DownloadContext, InclusiveRange,
+};
+
+use super::{Reporter, ReporterFactory};
+
+/// Creates [TracingReporter]s
+///
+/// The span of a download will be a child of the span
+/// current when [ReporterFactory::make] is called.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingReporterFactory;
+
+impl TracingReporterFactory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ReporterFactory for TracingReporterFactory {
+    type ReporterType = TracingReporter;
+
+    fn make(&self, location: &dyn fmt::Display) -> Self::ReporterType {
+        TracingReporter::new(location)
+    }
+}
+
+/// A [Reporter] which creates spans and events via [tracing]
+///
+/// The span of the download is named `condow_download` and has the
+/// fields `location`, `effective_range`, `request_id` and `context`. The spans of the parts
+/// are named `condow_part` and have the fields `part_index` and `range`.
+#[derive(Clone)]
+pub struct TracingReporter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    download_span: Span,
+    part_spans: Mutex<HashMap<u64, Span>>,
+}
+
+impl TracingReporter {
+    /// Creates a new [TracingReporter] with the download span
+    /// being a child of the current span.
+    pub fn new(location: &dyn fmt::Display) -> Self {
+        let download_span = tracing::info_span!(
+            "condow_download",
+            location = %location,
+            effective_range = field::Empty,
+            request_id = field::Empty,
+            context = field::Empty,
+        );
+
+        Self {
+            inner: Arc::new(Inner {
+                download_span,
+                part_spans: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// The span of the download
+    ///
+    /// Can be used to instrument the consumption of the download.
+    pub fn span(&self) -> &Span {
+        &self.inner.download_span
+    }
+
+    fn take_part_span(&self, part_index: u64) -> Option<Span> {
+        self.inner.part_spans.lock().unwrap().remove(&part_index)
+    }
+}
+
+impl Reporter for TracingReporter {
+    fn download_context(&self, context: &DownloadContext) {
+        let span = &self.inner.download_span;
+        if let Some(request_id) = context.request_id() {
+            span.record("request_id", request_id);
+        }
+        span.record("context", field::display(context));
+    }
+
+    fn effective_range(&self, range: InclusiveRange) {
+        self.inner
+            .download_span
+            .record("effective_range", field::display(range));
+    }
+
+    fn download_started(&self) {
+        debug!(parent: &self.inner.download_span, "download started");
+    }
+
+    fn download_completed(&self, time: Duration) {
+        info!(
+            parent: &self.inner.download_span,
+            time_ms = time.as_millis() as u64,
+            "download completed"
+        );
+    }
+
+    fn download_failed(&self, time: Option<Duration>) {
+        error!(
+            parent: &self.inner.download_span,
+            time_ms = time.map(|t| t.as_millis() as u64),
+            "download failed"
+        );
+    }
+
+    fn retry_attempt(&self, location: &dyn fmt::Display, error: &CondowError, next_in: Duration) {
+        warn!(
+            parent: &self.inner.download_span,
+            location = %location,
+            error = %error,
+            error_kind = ?error.kind(),
+            next_in_ms = next_in.as_millis() as u64,
+            "retry attempt"
+        );
+    }
+
+    fn stream_resume_attempt(
+        &self,
+        location: &dyn fmt::Display,
+        error: &IoError,
+        orig_range: InclusiveRange,
+        remaining_range: InclusiveRange,
+    ) {
+        warn!(
+            parent: &self.inner.download_span,
+            location = %location,
+            error = %error,
+            orig_range = %orig_range,
+            remaining_range = %remaining_range,
+            "stream resume attempt"
+        );
+    }
+
+    fn size_request_completed(&self, location: &dyn fmt::Display, size: u64, time: Duration) {
+        debug!(
+            parent: &self.inner.download_span,
+            location = %location,
+            size,
+            time_ms = time.as_millis() as u64,
+            "size request completed"
+        );
+    }
+
+    fn size_request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        warn!(
+            parent: &self.inner.download_span,
+            location = %location,
+            error = %error,
+            error_kind = ?error.kind(),
+            time_ms = time.as_millis() as u64,
+            "size request failed"
+        );
+    }
+
+    fn request_completed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        latency: Duration,
+        time_to_first_byte: Duration,
+    ) {
+        debug!(
+            parent: &self.inner.download_span,
+            location = %location,
+            attempt,
+            latency_ms = latency.as_millis() as u64,
+            time_to_first_byte_ms = time_to_first_byte.as_millis() as u64,
+            "request completed"
+        );
+    }
+
+    fn request_failed(
+        &self,
+        location: &dyn fmt::Display,
+        attempt: usize,
+        error: &CondowError,
+        time: Duration,
+    ) {
+        warn!(
+            parent: &self.inner.download_span,
+            location = %location,
+            attempt,
+            error = %error,
+            error_kind = ?error.kind(),
+            time_ms = time.as_millis() as u64,
+            "request failed"
+        );
+    }
+
+    fn panic_detected(&self, msg: &str) {
+        error!(parent: &self.inner.download_span, msg, "panic detected");
+    }
+
+    fn queue_full(&self) {
+        debug!(parent: &self.inner.download_span, "queue full");
+    }
+
+    fn memory_budget_exhausted(&self, part_index: u64, n_bytes: u64) {
+        debug!(
+            parent: &self.inner.download_span,
+            part_index,
+            n_bytes,
+            "memory budget exhausted"
+        );
+    }
+
+    fn part_started(&self, part_index: u64, range: InclusiveRange) {
+        let span = tracing::debug_span!(
+            parent: &self.inner.download_span,
+            "condow_part",
+            part_index,
+            range = %range,
+        );
+
+        self.inner
+            .part_spans
+            .lock()
+            .unwrap()
+            .insert(part_index, span);
+    }
+
+    fn part_completed(&self, part_index: u64, n_chunks: usize, n_bytes: u64, time: Duration) {
+        if let Some(span) = self.take_part_span(part_index) {
+            debug!(
+                parent: &span,
+                n_chunks,
+                n_bytes,
+                time_ms = time.as_millis() as u64,
+                "part completed"
+            );
+        }
+    }
+
+    fn part_failed(&self, error: &CondowError, part_index: u64, range: &InclusiveRange) {
+        let span = self
+            .take_part_span(part_index)
+            .unwrap_or_else(|| self.inner.download_span.clone());
+        warn!(
+            parent: &span,
+            part_index,
+            range = %range,
+            error = %error,
+            error_kind = ?error.kind(),
+            "part failed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{
+        span::{Attributes, Id},
+        Event, Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        config::{Config, RetryConfig},
+        errors::CondowError,
+    };
+
+    use super::*;
+
+    /// Records spans and events as "<parent span>/<name>"
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn entries(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl<S> Layer<S> for Recorder
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let parent = ctx
+                .span(id)
+                .and_then(|span| span.parent())
+                .map(|parent| parent.name())
+                .unwrap_or("none");
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}/{}", parent, attrs.metadata().name()));
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let parent = ctx
+                .event_span(event)
+                .map(|span| span.name())
+                .unwrap_or("none");
+            let mut message = String::new();
+            event.record(&mut MessageVisitor(&mut message));
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}/{}", parent, message));
+        }
+    }
+
+    struct MessageVisitor<'a>(&'a mut String);
+
+    impl<'a> field::Visit for MessageVisitor<'a> {
+        fn record_debug(&mut self, field: &field::Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0.push_str(&format!("{:?}", value));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn spans_and_events() {
+        let recorder = Recorder::default();
+        let _guard = tracing_subscriber::registry()
+            .with(recorder.clone())
+            .set_default();
+
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(7)
+            .responses()
+            .failure(CondowError::new_remote("remote failed"))
+            .success()
+            .finish()
+            .condow(
+                Config::default()
+                    .part_size_bytes(50)
+                    .max_concurrency(1)
+                    .retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0)),
+            )
+            .unwrap();
+
+        let downloader = condow.downloader_with_reporting(TracingReporterFactory::new());
+
+        let request_span = tracing::info_span!("request");
+        let result = {
+            let _entered = request_span.enter();
+            downloader
+                .download_rep(NoLocation, ..)
+                .await
+                .unwrap()
+                .into_parts()
+                .0
+        };
+        let result = result.into_vec().await.unwrap();
+        assert_eq!(result, blob);
+
+        let entries = recorder.entries();
+        assert!(entries.contains(&"request/condow_download".to_string()));
+        assert!(entries.contains(&"condow_download/retry attempt".to_string()));
+        assert!(entries.contains(&"condow_download/download completed".to_string()));
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| *e == "condow_download/condow_part")
+                .count(),
+            2
+        );
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| *e == "condow_part/part completed")
+                .count(),
+            2
+        );
+    }
+
+    /// Emits events without an explicit parent from within the spawned tasks
+    #[derive(Clone)]
+    struct ContextualReporter;
+
+    impl Reporter for ContextualReporter {
+        fn part_started(&self, _part_index: u64, _range: InclusiveRange) {
+            tracing::info!("part started");
+        }
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_inherit_the_callers_span() {
+        let recorder = Recorder::default();
+        let _guard = tracing_subscriber::registry()
+            .with(recorder.clone())
+            .set_default();
+
+        let blob = (0u8..100).collect::<Vec<_>>();
+        let condow = FailingClientSimulatorBuilder::default()
+            .blob(blob.clone())
+            .chunk_size(7)
+            .finish()
+            .condow(Config::default().part_size_bytes(50))
+            .unwrap();
+
+        let request_span = tracing::info_span!("request");
+        let chunk_stream = {
+            let _entered = request_span.enter();
+            condow
+                .downloader()
+                .download_chunks_wrep(NoLocation, .., ContextualReporter)
+                .await
+                .unwrap()
+        };
+        let result = chunk_stream.into_vec().await.unwrap();
+        assert_eq!(result, blob);
+
+        let entries = recorder.entries();
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| *e == "request/part started")
+                .count(),
+            2
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
 
 use anyhow::{bail, Error as AnyError};
 use bytes::Bytes;
@@ -356,11 +362,18 @@ where
         R: Reporter,
     {
         let (client, config) = self.inner.as_ref();
-        if let Some(config) = config {
-            retry_get_size(client, location, config, reporter).await
+        reporter.size_request_started(&location);
+        let started_at = Instant::now();
+        let result = if let Some(config) = config {
+            retry_get_size(client, location.clone(), config, reporter).await
         } else {
-            Ok(client.get_size(location).await?)
+            client.get_size(location.clone()).await
+        };
+        match &result {
+            Ok(size) => reporter.size_request_completed(&location, *size, started_at.elapsed()),
+            Err(err) => reporter.size_request_failed(&location, err, started_at.elapsed()),
         }
+        result
     }
 
     pub async fn download<R: Reporter>(
@@ -373,7 +386,9 @@ where
         if let Some(config) = config {
             retry_download(client, location, spec, config, reporter).await
         } else {
-            Ok(client.download(location, spec).await?)
+            request_download(client, location.clone(), spec, 1, reporter)
+                .await
+                .map_err(|err| err.with_location(&location).with_attempts(1))
         }
     }
 }
@@ -399,11 +414,14 @@ where
     C: CondowClient,
     R: Reporter,
 {
+    let mut attempts = Attempts::default();
+
     // The first attempt
+    attempts.n_attempts += 1;
     let mut last_err = match client.get_size(location.clone()).await {
         Ok(v) => return Ok(v),
         Err(err) if err.is_retryable() => err,
-        Err(err) => return Err(err),
+        Err(err) => return Err(attempts.into_error(&location, err)),
     };
 
     // Retries if the first attempt failed
@@ -413,14 +431,40 @@ where
 
         tokio::time::sleep(delay).await;
 
-        last_err = match client.get_size(location.clone()).await {
+        attempts.n_attempts += 1;
+        let err = match client.get_size(location.clone()).await {
             Ok(v) => return Ok(v),
-            Err(err) if err.is_retryable() => err,
-            Err(err) => return Err(err),
+            Err(err) => err,
         };
+        attempts.failed(std::mem::replace(&mut last_err, err));
+        if !last_err.is_retryable() {
+            break;
+        }
     }
 
-    return Err(last_err);
+    Err(attempts.into_error(&location, last_err))
+}
+
+/// Keeps track of the attempts made for a request
+/// and the errors of the failed ones
+#[derive(Default)]
+struct Attempts {
+    n_attempts: usize,
+    errors: Vec<CondowError>,
+}
+
+impl Attempts {
+    fn failed(&mut self, err: CondowError) {
+        self.errors.push(err);
+    }
+
+    /// Attach the location, the attempts made and the
+    /// errors of the preceding attempts to the final error
+    fn into_error(self, location: &dyn fmt::Display, err: CondowError) -> CondowError {
+        err.with_location(location)
+            .with_attempts(self.n_attempts)
+            .with_retry_errors(self.errors)
+    }
 }
 
 /// Retries on attempts to get a stream.
@@ -438,9 +482,22 @@ where
     C: CondowClient,
     R: Reporter,
 {
+    let mut attempts = Attempts::default();
+
     // The initial stream for the whole download
-    let (stream, bytes_hint) =
-        retry_download_get_stream(client, location.clone(), spec, config, reporter).await?;
+    let (stream, bytes_hint) = match retry_download_get_stream(
+        client,
+        location.clone(),
+        spec,
+        config,
+        reporter,
+        &mut attempts,
+    )
+    .await
+    {
+        Ok(stream_and_hint) => stream_and_hint,
+        Err(err) => return Err(attempts.into_error(&location, err)),
+    };
 
     // Only if we have an length we can try to continue broken streams
     // because we can only download whole BLOBs or ranges. We use a range for
@@ -466,14 +523,17 @@ where
 
     // Now we try to complete the stream by requesting new streams with the remaining
     // bytes if a stream broke
-    tokio::spawn(loop_retry_complete_stream(
+    crate::helpers::spawn(loop_retry_complete_stream(
         stream,
-        location.clone(),
         original_range,
-        client.clone(),
         next_elem_tx,
-        config.clone(),
-        reporter.clone(),
+        StreamResumeState {
+            client: client.clone(),
+            location,
+            config: config.clone(),
+            reporter: reporter.clone(),
+            attempts,
+        },
     ));
 
     Ok((Box::pin(output_stream_rx), bytes_hint))
@@ -502,27 +562,42 @@ where
             self.reporter.panic_detected("panicked while retrying");
             let _ = self
                 .next_elem_tx
-                .unbounded_send(Err(IoError("panicked while retrying".to_string())));
+                .unbounded_send(Err(IoError::new("panicked while retrying")));
         }
     }
 }
 
+/// Everything needed to request new streams for a download
+/// and the attempts made so far
+struct StreamResumeState<C: CondowClient, R> {
+    client: C,
+    location: C::Location,
+    config: RetryConfig,
+    reporter: R,
+    attempts: Attempts,
+}
+
 /// Tries to complete the given stream.
 ///
 /// If a stream breaks it tries to complete the `original_range` by
 /// requesting new stream for the remainder of `original_range`
 async fn loop_retry_complete_stream<C, R>(
     mut stream: BytesStream,
-    location: C::Location,
     original_range: InclusiveRange,
-    client: C,
     next_elem_tx: mpsc::UnboundedSender<Result<Bytes, IoError>>,
-    config: RetryConfig,
-    reporter: R,
+    state: StreamResumeState<C, R>,
 ) where
     C: CondowClient,
     R: Reporter,
 {
+    let StreamResumeState {
+        client,
+        location,
+        config,
+        reporter,
+        mut attempts,
+    } = state;
+
     let mut panic_guard = RetryLoopPanicGuard {
         completed_without_panic: false,
         next_elem_tx: next_elem_tx.clone(),
@@ -536,6 +611,9 @@ async fn loop_retry_complete_stream<C, R>(
     loop {
         if let Err((stream_io_error, bytes_read)) = try_consume_stream(stream, &next_elem_tx).await
         {
+            // The final error keeps the kind of the error which broke the stream
+            let io_kind = stream_io_error.kind();
+
             if bytes_read > 0 {
                 // we start right after where the previous one ended
                 remaining_range.0 += bytes_read;
@@ -547,11 +625,14 @@ async fn loop_retry_complete_stream<C, R>(
             }
 
             if n_times_made_no_progress >= config.max_stream_resume_attempts.into_inner() {
-                let _ = next_elem_tx.unbounded_send(Err(IoError(format!(
+                let msg = format!(
                     "failed to make progress on the stream {} times \
                     with the last error being \"{}\"",
                     n_times_made_no_progress, stream_io_error
-                ))));
+                );
+                attempts.failed(stream_io_error.into());
+                let err = final_stream_error(msg, io_kind, attempts, &location);
+                let _ = next_elem_tx.unbounded_send(Err(err));
                 break;
             }
 
@@ -562,19 +643,31 @@ async fn loop_retry_complete_stream<C, R>(
                 original_range,
                 remaining_range,
             );
-            match retry_download_get_stream(&client, location.clone(), new_spec, &config, &reporter)
-                .await
+            let stream_io_error_msg = stream_io_error.to_string();
+            attempts.failed(stream_io_error.into());
+            match retry_download_get_stream(
+                &client,
+                location.clone(),
+                new_spec,
+                &config,
+                &reporter,
+                &mut attempts,
+            )
+            .await
             {
                 Ok((new_stream, _)) => {
                     stream = new_stream;
                 }
                 Err(err_new_stream) => {
                     // we must send the final error over the stream
-                    let _ = next_elem_tx.unbounded_send(Err(IoError(format!(
+                    let msg = format!(
                         "failed to create a new stream with error \"{}\"\
                          after previous stream broke with \"{}\"",
-                        err_new_stream, stream_io_error
-                    ))));
+                        err_new_stream, stream_io_error_msg
+                    );
+                    attempts.failed(err_new_stream);
+                    let err = final_stream_error(msg, io_kind, attempts, &location);
+                    let _ = next_elem_tx.unbounded_send(Err(err));
                     break;
                 }
             }
@@ -588,6 +681,25 @@ async fn loop_retry_complete_stream<C, R>(
     panic_guard.completed_without_panic = true;
 }
 
+/// Creates the error to be sent if a stream could not be completed
+///
+/// The source of the [IoError] is a [CondowError] with the details
+/// of all attempts made.
+fn final_stream_error(
+    msg: String,
+    io_kind: Option<io::ErrorKind>,
+    attempts: Attempts,
+    location: &dyn fmt::Display,
+) -> IoError {
+    let err = IoError::new(msg);
+    let err = match io_kind {
+        Some(io_kind) => err.with_kind(io_kind),
+        None => err,
+    };
+    let details = attempts.into_error(location, err.to_condow_error());
+    err.with_source(details)
+}
+
 /// Consume a stream until it is finished or broken.
 ///
 /// If it finished [Ok] will be returned otherwise an [Err] containing
@@ -618,19 +730,35 @@ async fn try_consume_stream<St: Stream<Item = Result<Bytes, IoError>>>(
 }
 
 /// Retries to get a new stream for the given download spec.
+///
+/// All attempts are tracked with `attempts`. The errors of all failed
+/// attempts except the one returned are added to `attempts`.
+///
+/// The attempts reported for the requests continue to count
+/// the attempts made before for the download.
 async fn retry_download_get_stream<C, R>(
     client: &C,
     location: C::Location,
     spec: DownloadSpec,
     config: &RetryConfig,
     reporter: &R,
+    attempts: &mut Attempts,
 ) -> Result<(BytesStream, BytesHint), CondowError>
 where
     C: CondowClient,
     R: Reporter,
 {
     // The first attempt
-    let mut last_err = match client.download(location.clone(), spec).await {
+    attempts.n_attempts += 1;
+    let mut last_err = match request_download(
+        client,
+        location.clone(),
+        spec,
+        attempts.n_attempts,
+        reporter,
+    )
+    .await
+    {
         Ok(stream_and_hint) => return Ok(stream_and_hint),
         Err(err) if err.is_retryable() => err,
         Err(err) => return Err(err),
@@ -643,12 +771,111 @@ where
 
         tokio::time::sleep(delay).await;
 
-        last_err = match client.download(location.clone(), spec).await {
+        attempts.n_attempts += 1;
+        let err = match request_download(
+            client,
+            location.clone(),
+            spec,
+            attempts.n_attempts,
+            reporter,
+        )
+        .await
+        {
             Ok(stream_and_hint) => return Ok(stream_and_hint),
-            Err(err) if err.is_retryable() => err,
-            Err(err) => return Err(err),
+            Err(err) => err,
         };
+        attempts.failed(std::mem::replace(&mut last_err, err));
+        if !last_err.is_retryable() {
+            break;
+        }
     }
 
-    return Err(last_err);
+    Err(last_err)
+}
+
+/// Requests a stream from the client and reports on the request
+///
+/// The request is completed once the first bytes were received.
+async fn request_download<C, R>(
+    client: &C,
+    location: C::Location,
+    spec: DownloadSpec,
+    attempt: usize,
+    reporter: &R,
+) -> Result<(BytesStream, BytesHint), CondowError>
+where
+    C: CondowClient,
+    R: Reporter,
+{
+    reporter.request_started(&location, attempt);
+    let started_at = Instant::now();
+
+    match client.download(location.clone(), spec).await {
+        Ok((stream, bytes_hint)) => {
+            let stream = RequestReportingStream {
+                stream,
+                state: Some(Box::new(RequestState {
+                    location,
+                    attempt,
+                    started_at,
+                    latency: started_at.elapsed(),
+                    reporter: reporter.clone(),
+                })),
+            };
+            Ok((Box::pin(stream), bytes_hint))
+        }
+        Err(err) => {
+            reporter.request_failed(&location, attempt, &err, started_at.elapsed());
+            Err(err)
+        }
+    }
+}
+
+/// Reports the completion of a request once the first bytes were received
+struct RequestReportingStream<L, R> {
+    stream: BytesStream,
+    /// `None` once the request was reported as completed or failed
+    state: Option<Box<RequestState<L, R>>>,
+}
+
+struct RequestState<L, R> {
+    location: L,
+    attempt: usize,
+    started_at: Instant,
+    latency: Duration,
+    reporter: R,
+}
+
+impl<L, R> Stream for RequestReportingStream<L, R>
+where
+    L: std::fmt::Display,
+    R: Reporter,
+{
+    type Item = Result<Bytes, IoError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let next = self.stream.as_mut().poll_next(cx);
+
+        if let Poll::Ready(item) = &next {
+            if let Some(state) = self.state.take() {
+                let time_to_first_byte = state.started_at.elapsed();
+                match item {
+                    Some(Err(err)) => state.reporter.request_failed(
+                        &state.location,
+                        state.attempt,
+                        &err.to_condow_error(),
+                        time_to_first_byte,
+                    ),
+                    _ => state.reporter.request_completed(
+                        &state.location,
+                        state.attempt,
+                        state.latency,
+                        time_to_first_byte,
+                    ),
+                }
+            }
+        }
+
+        next
+    }
 }
@@ -751,7 +751,7 @@ mod try_consume_stream {
                 return Ok(Bytes::from(bytes));
             }
 
-            Err(IoError("bang!".to_string()))
+            Err(IoError::new("bang!"))
         });
 
         let stream = stream::iter(items).boxed() as BytesStream;
@@ -809,6 +809,7 @@ mod loop_retry_complete_stream {
         retry::{
             loop_retry_complete_stream,
             tests::{NON_RETRYABLE, RETRYABLE},
+            Attempts, StreamResumeState,
         },
         InclusiveRange,
     };
@@ -1121,12 +1122,15 @@ mod loop_retry_complete_stream {
 
         tokio::spawn(loop_retry_complete_stream(
             initial_stream,
-            NoLocation,
             original_range,
-            client,
             next_elem_tx,
-            config,
-            probe.clone(),
+            StreamResumeState {
+                client,
+                location: NoLocation,
+                config,
+                reporter: probe.clone(),
+                attempts: Attempts::default(),
+            },
         ));
 
         let mut received = Vec::new();
@@ -1167,7 +1171,10 @@ mod retry_download_get_stream {
 
     use futures::{stream, FutureExt};
 
-    use crate::{condow_client::NoLocation, errors::CondowErrorKind};
+    use crate::{
+        condow_client::{failing_client_simulator::FailingClientSimulatorBuilder, NoLocation},
+        errors::CondowErrorKind,
+    };
 
     use super::*;
 
@@ -1312,6 +1319,46 @@ mod retry_download_get_stream {
         assert_eq!(result, Ok(2));
     }
 
+    #[tokio::test]
+    async fn reported_attempts_continue_the_attempts_made() {
+        #[derive(Clone, Default)]
+        struct Probe(Arc<Mutex<Vec<usize>>>);
+
+        impl Reporter for Probe {
+            fn request_started(&self, _location: &dyn std::fmt::Display, attempt: usize) {
+                self.0.lock().unwrap().push(attempt);
+            }
+        }
+
+        let client = FailingClientSimulatorBuilder::default()
+            .blob_static(b"0123456789")
+            .responses()
+            .failure(RETRYABLE)
+            .success()
+            .finish();
+        let config = RetryConfig::default().max_attempts(1).max_delay_ms(0);
+        let probe = Probe::default();
+        // The original request and a failed request to resume a broken stream
+        let mut attempts = Attempts {
+            n_attempts: 2,
+            ..Attempts::default()
+        };
+
+        let (_stream, _) = retry_download_get_stream(
+            &client,
+            NoLocation,
+            DownloadSpec::Complete,
+            &config,
+            &probe,
+            &mut attempts,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*probe.0.lock().unwrap(), vec![3, 4]);
+        assert_eq!(attempts.n_attempts, 4);
+    }
+
     /// Simulates a call to a client
     ///
     /// `fails` are the errors to be returned before a success is delivered
@@ -1387,6 +1434,7 @@ mod retry_download_get_stream {
             DownloadSpec::Complete,
             &config,
             &probe,
+            &mut Attempts::default(),
         )
         .await
         {
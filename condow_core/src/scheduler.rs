@@ -0,0 +1,271 @@
+//! Schedules the parts of all downloads sharing a limited number of slots
+//!
+//! A slot is held while a part is downloaded. Waiting parts of
+//! [DownloadPriority::High] downloads get a free slot before those of
+//! [DownloadPriority::Low] downloads unless low priority downloads hold
+//! less slots than their guaranteed minimum share.
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+use crate::{errors::CondowError, DownloadPriority};
+
+/// Shared by all downloads of a [Condow](crate::Condow) and its clones
+#[derive(Clone)]
+pub(crate) struct Scheduler {
+    state: Arc<Mutex<State>>,
+}
+
+impl Scheduler {
+    /// Create a new scheduler
+    ///
+    /// `low_priority_min_share_percent` is the share of the slots
+    /// guaranteed to low priority downloads if they are waiting.
+    /// At least one slot is guaranteed if the share is not 0.
+    pub fn new(n_slots: usize, low_priority_min_share_percent: u8) -> Self {
+        let share = low_priority_min_share_percent.min(100) as usize;
+        let min_low_slots = (n_slots * share).div_ceil(100);
+
+        Self {
+            state: Arc::new(Mutex::new(State {
+                n_slots,
+                min_low_slots,
+                in_use_high: 0,
+                in_use_low: 0,
+                waiting_high: VecDeque::new(),
+                waiting_low: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Wait for a free slot
+    pub async fn acquire(&self, priority: DownloadPriority) -> Result<Slot, CondowError> {
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            match priority {
+                DownloadPriority::High => state.waiting_high.push_back(tx),
+                DownloadPriority::Low => state.waiting_low.push_back(tx),
+            }
+            self.dispatch(&mut state);
+        }
+
+        rx.await
+            .map_err(|_| CondowError::new_other("scheduler dropped a waiting part"))
+    }
+
+    /// The number of slots currently in use by high and low priority downloads
+    #[cfg(test)]
+    fn in_use(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.in_use_high, state.in_use_low)
+    }
+
+    fn release(&self, priority: DownloadPriority) {
+        let mut state = self.state.lock().unwrap();
+        match priority {
+            DownloadPriority::High => state.in_use_high -= 1,
+            DownloadPriority::Low => state.in_use_low -= 1,
+        }
+        self.dispatch(&mut state);
+    }
+
+    /// Hand out free slots to waiting parts
+    fn dispatch(&self, state: &mut State) {
+        while state.in_use_high + state.in_use_low < state.n_slots {
+            let (priority, tx) = if let Some(next) = state.next_waiting() {
+                next
+            } else {
+                return;
+            };
+
+            match priority {
+                DownloadPriority::High => state.in_use_high += 1,
+                DownloadPriority::Low => state.in_use_low += 1,
+            }
+
+            let slot = Slot {
+                scheduler: Some(self.clone()),
+                priority,
+            };
+
+            if let Err(mut slot) = tx.send(slot) {
+                // The waiting part is gone. Do not release
+                // the slot via drop since we hold the lock.
+                slot.scheduler = None;
+                match priority {
+                    DownloadPriority::High => state.in_use_high -= 1,
+                    DownloadPriority::Low => state.in_use_low -= 1,
+                }
+            }
+        }
+    }
+}
+
+struct State {
+    n_slots: usize,
+    min_low_slots: usize,
+    in_use_high: usize,
+    in_use_low: usize,
+    waiting_high: VecDeque<oneshot::Sender<Slot>>,
+    waiting_low: VecDeque<oneshot::Sender<Slot>>,
+}
+
+impl State {
+    fn next_waiting(&mut self) -> Option<(DownloadPriority, oneshot::Sender<Slot>)> {
+        self.waiting_high.retain(|tx| !tx.is_closed());
+        self.waiting_low.retain(|tx| !tx.is_closed());
+
+        let low_first = self.in_use_low < self.min_low_slots || self.waiting_high.is_empty();
+
+        if low_first {
+            if let Some(tx) = self.waiting_low.pop_front() {
+                return Some((DownloadPriority::Low, tx));
+            }
+        }
+
+        self.waiting_high
+            .pop_front()
+            .map(|tx| (DownloadPriority::High, tx))
+    }
+}
+
+/// A slot for downloading a part
+///
+/// The slot is returned to the [Scheduler] when dropped.
+pub(crate) struct Slot {
+    scheduler: Option<Scheduler>,
+    priority: DownloadPriority,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release(self.priority);
+        }
+    }
+}
+
+/// Acquires slots for the parts of a single download
+#[derive(Clone)]
+pub(crate) struct SlotRequester {
+    scheduler: Scheduler,
+    priority: DownloadPriority,
+}
+
+impl SlotRequester {
+    pub fn new(scheduler: Scheduler, priority: DownloadPriority) -> Self {
+        Self {
+            scheduler,
+            priority,
+        }
+    }
+
+    pub async fn acquire(&self) -> Result<Slot, CondowError> {
+        self.scheduler.acquire(self.priority).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn slots_are_limited() {
+        let scheduler = Scheduler::new(2, 0);
+
+        let slot_a = scheduler.acquire(DownloadPriority::High).await.unwrap();
+        let _slot_b = scheduler.acquire(DownloadPriority::Low).await.unwrap();
+
+        let mut waiting = Box::pin(scheduler.acquire(DownloadPriority::High));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        drop(slot_a);
+
+        let _slot_c = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(scheduler.in_use(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn high_priority_is_dispatched_first() {
+        let scheduler = Scheduler::new(1, 0);
+
+        let slot = scheduler.acquire(DownloadPriority::High).await.unwrap();
+
+        let low = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire(DownloadPriority::Low).await.map(drop) }
+        });
+        tokio::task::yield_now().await;
+        let high = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move {
+                let slot = scheduler.acquire(DownloadPriority::High).await.unwrap();
+                let in_use = scheduler.in_use();
+                drop(slot);
+                in_use
+            }
+        });
+        tokio::task::yield_now().await;
+
+        drop(slot);
+
+        assert_eq!(high.await.unwrap(), (1, 0));
+        low.await.unwrap().unwrap();
+        assert_eq!(scheduler.in_use(), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn low_priority_gets_its_minimum_share() {
+        // 10% of 4 slots is rounded up to 1 slot
+        let scheduler = Scheduler::new(4, 10);
+
+        let mut high_slots = Vec::new();
+        for _ in 0..4 {
+            high_slots.push(scheduler.acquire(DownloadPriority::High).await.unwrap());
+        }
+
+        let mut low_waiting = Box::pin(scheduler.acquire(DownloadPriority::Low));
+        assert!((&mut low_waiting).now_or_never().is_none());
+        let mut high_waiting = Box::pin(scheduler.acquire(DownloadPriority::High));
+        assert!((&mut high_waiting).now_or_never().is_none());
+
+        high_slots.pop();
+
+        let _low_slot = (&mut low_waiting).now_or_never().unwrap().unwrap();
+        assert!((&mut high_waiting).now_or_never().is_none());
+        assert_eq!(scheduler.in_use(), (3, 1));
+
+        high_slots.pop();
+        let _high_slot = high_waiting.now_or_never().unwrap().unwrap();
+        assert_eq!(scheduler.in_use(), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_waiter_does_not_leak_a_slot() {
+        let scheduler = Scheduler::new(1, 0);
+
+        let slot = scheduler.acquire(DownloadPriority::High).await.unwrap();
+
+        let mut waiting = Box::pin(scheduler.acquire(DownloadPriority::High));
+        assert!((&mut waiting).now_or_never().is_none());
+        drop(waiting);
+
+        drop(slot);
+        assert_eq!(scheduler.in_use(), (0, 0));
+
+        let _slot = scheduler.acquire(DownloadPriority::Low).await.unwrap();
+        assert_eq!(scheduler.in_use(), (0, 1));
+    }
+}
@@ -7,7 +7,7 @@ use bytes::Bytes;
 use futures::{channel::mpsc, ready, Stream, StreamExt};
 use pin_project_lite::pin_project;
 
-use crate::errors::CondowError;
+use crate::{errors::CondowError, memory_budget::BudgetConsumer};
 
 use super::{BytesHint, PartStream};
 
@@ -64,6 +64,7 @@ pin_project! {
         receiver: mpsc::UnboundedReceiver<ChunkStreamItem>,
         is_closed: bool,
         is_fresh: bool,
+        budget: Option<BudgetConsumer>,
     }
 }
 
@@ -76,6 +77,7 @@ impl ChunkStream {
             receiver,
             is_closed: false,
             is_fresh: true,
+            budget: None,
         };
 
         (me, tx)
@@ -96,6 +98,16 @@ impl ChunkStream {
         self.bytes_hint
     }
 
+    /// Release bytes from the memory budget once they were pulled from this stream
+    pub(crate) fn set_budget(&mut self, budget: BudgetConsumer) {
+        self.budget = Some(budget);
+    }
+
+    /// Take over releasing bytes from the memory budget
+    pub(crate) fn take_budget(&mut self) -> Option<BudgetConsumer> {
+        self.budget.take()
+    }
+
     /// Returns `true`, if this stream was not iterated before
     pub fn is_fresh(&self) -> bool {
         self.is_fresh
@@ -273,16 +285,21 @@ impl Stream for ChunkStream {
         match next {
             Some(Ok(chunk_item)) => {
                 this.bytes_hint.reduce_by(chunk_item.len() as u64);
+                if let Some(budget) = this.budget {
+                    budget.release(chunk_item.len() as u64);
+                }
                 Poll::Ready(Some(Ok(chunk_item)))
             }
             Some(Err(err)) => {
                 *this.is_closed = true;
                 this.receiver.close();
                 *this.bytes_hint = BytesHint::new_exact(0);
+                *this.budget = None;
                 Poll::Ready(Some(Err(err)))
             }
             None => {
                 *this.is_closed = true;
+                *this.budget = None;
                 Poll::Ready(None)
             }
         }
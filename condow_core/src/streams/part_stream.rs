@@ -8,7 +8,7 @@ use bytes::Bytes;
 use futures::{ready, stream, Stream, StreamExt, TryStreamExt};
 use pin_project_lite::pin_project;
 
-use crate::errors::CondowError;
+use crate::{errors::CondowError, memory_budget::BudgetConsumer};
 
 use super::{BytesHint, ChunkStream, ChunkStreamItem};
 
@@ -60,7 +60,8 @@ pin_project! {
         stream: St,
         is_closed: bool,
         next_part_idx: u64,
-        collected_parts: HashMap<u64, PartEntry>
+        collected_parts: HashMap<u64, PartEntry>,
+        budget: Option<BudgetConsumer>,
     }
 }
 
@@ -81,6 +82,7 @@ where
             is_closed: false,
             next_part_idx: 0,
             collected_parts: HashMap::default(),
+            budget: None,
         }
     }
 
@@ -166,14 +168,18 @@ impl PartStream<ChunkStream> {
     /// Create a new [PartStream] from the given [ChunkStream]
     ///
     /// Will fail if the [ChunkStream] was already iterated.
-    pub fn from_chunk_stream(chunk_stream: ChunkStream) -> Result<Self, CondowError> {
+    pub fn from_chunk_stream(mut chunk_stream: ChunkStream) -> Result<Self, CondowError> {
         if !chunk_stream.is_fresh() {
             return Err(CondowError::new_other(
                 "chunk stream already iterated".to_string(),
             ));
         }
         let bytes_hint = chunk_stream.bytes_hint();
-        Ok(Self::new(chunk_stream, bytes_hint))
+        // Parts are held back for reordering. Release the bytes once a part was yielded.
+        let budget = chunk_stream.take_budget();
+        let mut me = Self::new(chunk_stream, bytes_hint);
+        me.budget = budget;
+        Ok(me)
     }
 }
 
@@ -196,6 +202,9 @@ impl<St: Stream<Item = ChunkStreamItem>> Stream for PartStream<St> {
                 {
                     this.bytes_hint.reduce_by(chunk.len() as u64);
                     *this.next_part_idx += 1;
+                    if let Some(budget) = this.budget {
+                        budget.release(chunk.len() as u64);
+                    }
                     Poll::Ready(Some(Ok(Part {
                         part_index: chunk.part_index,
                         blob_offset: chunk.blob_offset,
@@ -225,9 +234,12 @@ impl<St: Stream<Item = ChunkStreamItem>> Stream for PartStream<St> {
                                 chunks,
                                 ..
                             } = this.collected_parts.remove(this.next_part_idx).unwrap();
-                            this.bytes_hint
-                                .reduce_by(chunks.iter().map(|c| c.len() as u64).sum());
+                            let n_bytes = chunks.iter().map(|c| c.len() as u64).sum();
+                            this.bytes_hint.reduce_by(n_bytes);
                             *this.next_part_idx += 1;
+                            if let Some(budget) = this.budget {
+                                budget.release(n_bytes);
+                            }
                             Poll::Ready(Some(Ok(Part {
                                 part_index,
                                 blob_offset: file_offset,
@@ -247,13 +259,17 @@ impl<St: Stream<Item = ChunkStreamItem>> Stream for PartStream<St> {
             Some(Err(err)) => {
                 *this.is_closed = true;
                 *this.bytes_hint = BytesHint::new_exact(0);
+                *this.budget = None;
                 Poll::Ready(Some(Err(err)))
             }
             None => {
                 if let Some(next) = this.collected_parts.remove(this.next_part_idx) {
                     *this.next_part_idx += 1;
-                    this.bytes_hint
-                        .reduce_by(next.chunks.iter().map(|c| c.len() as u64).sum());
+                    let n_bytes = next.chunks.iter().map(|c| c.len() as u64).sum();
+                    this.bytes_hint.reduce_by(n_bytes);
+                    if let Some(budget) = this.budget {
+                        budget.release(n_bytes);
+                    }
                     Poll::Ready(Some(Ok(Part {
                         part_index: next.part_index,
                         blob_offset: next.blob_offset,
@@ -263,6 +279,7 @@ impl<St: Stream<Item = ChunkStreamItem>> Stream for PartStream<St> {
                 } else {
                     *this.is_closed = true;
                     *this.bytes_hint = BytesHint::new_exact(0);
+                    *this.budget = None;
                     Poll::Ready(None)
                 }
             }
@@ -13,19 +13,24 @@
 //! # async {
 //! let condow = FsClient::condow(Config::default()).unwrap();
 //!
-//! let location = String::from("my_file");
+//! let location = FsLocation::from("my_file");
 //!
 //! let stream = condow.download(location, 23..46).await.unwrap();
 //! let downloaded_bytes: Vec<u8> = stream.into_vec().await.unwrap();
 //! # };
 //! # ()
 //! ```
+//!
+//! Use [FsClient::with_root] to confine all downloads to a directory.
 
-use std::io::SeekFrom;
+use std::{
+    io::{self, SeekFrom},
+    path::Path,
+};
 
 use anyhow::Error as AnyError;
-use bytes::Bytes;
-use condow_core::config::Config;
+use bytes::BytesMut;
+use condow_core::config::{Config, Mebi};
 use futures::future::BoxFuture;
 use futures::StreamExt;
 use tokio::fs;
@@ -33,29 +38,101 @@ use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 use condow_core::{
     condow_client::{CondowClient, DownloadSpec},
-    errors::CondowError,
+    errors::{CondowError, IoError},
     streams::{BytesHint, BytesStream},
 };
 
 pub use condow_core::*;
-
+pub use location::FsLocation;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapClient;
+
+use location::Sandbox;
+
+mod location;
+#[cfg(feature = "mmap")]
+mod mmap;
+
+/// Reads files from the local file system
+///
+/// Files are streamed in chunks of at most `chunk_size` bytes.
+///
+/// # Root
+///
+/// A client created with [FsClient::with_root] only serves files within
+/// the root directory. Locations are resolved relative to the root.
+/// Absolute locations, locations containing `..` and locations
+/// whose symbolic links resolve to paths outside of the root
+/// are rejected with [AccessDenied](errors::CondowErrorKind::AccessDenied).
 #[derive(Clone)]
-pub struct FsClient;
+pub struct FsClient {
+    chunk_size: usize,
+    sandbox: Sandbox,
+}
 
 impl FsClient {
+    /// Creates a client with a chunk size of 4 MiB
+    pub fn new() -> Self {
+        Self {
+            chunk_size: Mebi(4).value() as usize,
+            sandbox: Sandbox::default(),
+        }
+    }
+
+    /// Creates a client with a chunk size of 4 MiB which only
+    /// serves files within the directory `root`
+    ///
+    /// Fails if `root` does not exist or is not a directory.
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<Self, CondowError> {
+        Ok(Self {
+            sandbox: Sandbox::with_root(root)?,
+            ..Self::new()
+        })
+    }
+
+    /// The canonicalized root directory if the client has one
+    pub fn root(&self) -> Option<&Path> {
+        self.sandbox.root()
+    }
+
+    /// Set the maximum size of the chunks read from a file
+    ///
+    /// A value of 0 will be treated as 1.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
     /// Create a concurrent downloader from this adapter and the given [Config]
     pub fn condow(config: Config) -> Result<Condow<Self>, AnyError> {
-        Condow::new(FsClient, config)
+        Self::new().into_condow(config)
+    }
+
+    /// Create a concurrent downloader from this configured adapter and the given [Config]
+    pub fn into_condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
     }
 }
 
+impl Default for FsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A missing file is [NotFound](errors::CondowErrorKind::NotFound) and
+/// a file which may not be read is [AccessDenied](errors::CondowErrorKind::AccessDenied).
 impl CondowClient for FsClient {
-    type Location = String;
+    type Location = FsLocation;
 
     fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let sandbox = self.sandbox.clone();
         let f = async move {
-            let file = fs::File::open(location.as_str()).await?;
-            let len = file.metadata().await?.len();
+            let path = sandbox.resolve(&location).await?;
+            let len = fs::metadata(path)
+                .await
+                .map_err(|err| io_error(err, &location))?
+                .len();
 
             Ok(len)
         };
@@ -68,41 +145,67 @@ impl CondowClient for FsClient {
         location: Self::Location,
         spec: DownloadSpec,
     ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let chunk_size = self.chunk_size;
+        let sandbox = self.sandbox.clone();
         let f = async move {
-            let bytes = match spec {
-                DownloadSpec::Complete => fs::read(location.as_str()).await?,
+            let path = sandbox.resolve(&location).await?;
+            let mut file = fs::File::open(path)
+                .await
+                .map_err(|err| io_error(err, &location))?;
+            let file_len = file
+                .metadata()
+                .await
+                .map_err(|err| io_error(err, &location))?
+                .len();
+
+            let (start, n_bytes) = match spec {
+                DownloadSpec::Complete => (0, file_len),
                 DownloadSpec::Range(range) => {
-                    let mut file = fs::File::open(location.as_str()).await?;
-                    file.seek(SeekFrom::Start(range.start())).await?;
-
-                    let n_bytes_to_read = range.len();
-
-                    if n_bytes_to_read > usize::MAX as u64 {
-                        return Err(CondowError::new_other(
-                            "usize overflow while casting from u64",
-                        ));
-                    }
-
-                    let mut buffer = vec![0; n_bytes_to_read as usize];
-
-                    let n_bytes_read = file.read_exact(&mut buffer).await?;
-
-                    if n_bytes_read as u64 != n_bytes_to_read {
-                        return Err(CondowError::new_io(format!(
-                            "not enough bytes read (expected {} got {})",
-                            n_bytes_to_read, n_bytes_read
+                    if range.end_incl() >= file_len {
+                        return Err(CondowError::new_invalid_range(format!(
+                            "max upper bound is {} but {} was requested",
+                            file_len.saturating_sub(1),
+                            range.end_incl()
                         )));
                     }
-
-                    buffer
+                    file.seek(SeekFrom::Start(range.start()))
+                        .await
+                        .map_err(|err| io_error(err, &location))?;
+                    (range.start(), range.len())
                 }
             };
 
-            let bytes = Bytes::from(bytes);
+            let bytes_hint = BytesHint::new_exact(n_bytes);
 
-            let bytes_hint = BytesHint::new_exact(bytes.len() as u64);
+            let stream =
+                futures::stream::unfold(Some((file, start, n_bytes)), move |state| async move {
+                    let (mut file, offset, n_bytes_left) = state?;
+                    if n_bytes_left == 0 {
+                        return None;
+                    }
 
-            let stream = futures::stream::once(futures::future::ready(Ok(bytes)));
+                    let n_bytes_to_read = (chunk_size as u64).min(n_bytes_left) as usize;
+                    match read_chunk(&mut file, n_bytes_to_read).await {
+                        Ok(bytes) if bytes.len() == n_bytes_to_read => {
+                            let n_read = bytes.len() as u64;
+                            Some((
+                                Ok(bytes.freeze()),
+                                Some((file, offset + n_read, n_bytes_left - n_read)),
+                            ))
+                        }
+                        Ok(bytes) => {
+                            let err = IoError::new(format!(
+                                "file truncated: expected {} more bytes at offset {} but got {}",
+                                n_bytes_to_read,
+                                offset,
+                                bytes.len()
+                            ))
+                            .with_kind(io::ErrorKind::UnexpectedEof);
+                            Some((Err(err), None))
+                        }
+                        Err(err) => Some((Err(IoError::from(err)), None)),
+                    }
+                });
 
             Ok((stream.boxed(), bytes_hint))
         };
@@ -110,3 +213,42 @@ impl CondowClient for FsClient {
         Box::pin(f)
     }
 }
+
+/// Maps an [io::Error] which occurred while accessing the file at `location`
+///
+/// [io::ErrorKind::NotFound] and [io::ErrorKind::PermissionDenied] become
+/// [NotFound](errors::CondowErrorKind::NotFound) and
+/// [AccessDenied](errors::CondowErrorKind::AccessDenied).
+/// The message does not contain the resolved path.
+pub(crate) fn io_error(err: io::Error, location: &FsLocation) -> CondowError {
+    let io_kind = err.kind();
+    let error = match io_kind {
+        io::ErrorKind::NotFound => {
+            CondowError::new_not_found(format!("file '{}' not found", location))
+        }
+        io::ErrorKind::PermissionDenied => {
+            CondowError::new_access_denied(format!("access to file '{}' denied", location))
+        }
+        _ => CondowError::new_io(format!("I/O error on file '{}': {}", location, err)),
+    };
+
+    error
+        .with_location(location)
+        .with_io_kind(io_kind)
+        .with_source(err)
+}
+
+/// Reads until `n_bytes` were read or the end of the file was reached
+async fn read_chunk(file: &mut fs::File, n_bytes: usize) -> io::Result<BytesMut> {
+    let mut buffer = BytesMut::with_capacity(n_bytes);
+    while buffer.len() < n_bytes {
+        let n_read = (&mut *file)
+            .take((n_bytes - buffer.len()) as u64)
+            .read_buf(&mut buffer)
+            .await?;
+        if n_read == 0 {
+            break;
+        }
+    }
+    Ok(buffer)
+}
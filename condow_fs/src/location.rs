@@ -0,0 +1,146 @@
+//! Locations of files and resolving them against a root directory
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+use condow_core::errors::CondowError;
+use tokio::fs;
+
+use crate::io_error;
+
+/// Path to a file on the local file system
+///
+/// If the client was created with a root directory the path
+/// must be relative and is resolved against the root.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FsLocation(PathBuf);
+
+impl FsLocation {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self(path.into())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Turn this into the wrapped [PathBuf]
+    pub fn into_inner(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl fmt::Display for FsLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.display())
+    }
+}
+
+impl AsRef<Path> for FsLocation {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<PathBuf> for FsLocation {
+    fn from(path: PathBuf) -> Self {
+        Self(path)
+    }
+}
+
+impl From<&Path> for FsLocation {
+    fn from(path: &Path) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<String> for FsLocation {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&str> for FsLocation {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+/// Resolves [FsLocation]s to the paths of the files to open
+///
+/// Without a root locations are used as they are.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sandbox {
+    root: Option<PathBuf>,
+}
+
+impl Sandbox {
+    /// Confine all locations to the given directory
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<Self, CondowError> {
+        let root = root.as_ref();
+        let location = FsLocation::from(root);
+        let canonical_root = std::fs::canonicalize(root).map_err(|err| io_error(err, &location))?;
+
+        if !canonical_root.is_dir() {
+            return Err(CondowError::new_other(format!(
+                "root '{}' is not a directory",
+                location
+            )));
+        }
+
+        Ok(Self {
+            root: Some(canonical_root),
+        })
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Returns the path of the file to open
+    ///
+    /// With a root the location must be relative and may not contain `..`.
+    /// Symbolic links are followed but the resolved path must still be
+    /// within the root.
+    pub async fn resolve(&self, location: &FsLocation) -> Result<PathBuf, CondowError> {
+        let root = match self.root {
+            Some(ref root) => root,
+            None => return Ok(location.path().to_path_buf()),
+        };
+
+        for component in location.path().components() {
+            match component {
+                Component::Normal(_) | Component::CurDir => {}
+                Component::ParentDir => {
+                    return Err(CondowError::new_access_denied(format!(
+                        "location '{}' must not contain '..'",
+                        location
+                    ))
+                    .with_location(location))
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(CondowError::new_access_denied(format!(
+                        "location '{}' must be relative to the root",
+                        location
+                    ))
+                    .with_location(location))
+                }
+            }
+        }
+
+        let path = fs::canonicalize(root.join(location.path()))
+            .await
+            .map_err(|err| io_error(err, location))?;
+
+        if !path.starts_with(root) {
+            return Err(CondowError::new_access_denied(format!(
+                "location '{}' resolves to a path outside of the root",
+                location
+            ))
+            .with_location(location));
+        }
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,315 @@
+//! Downloading from memory mapped files
+//!
+//! Requires the feature `mmap`.
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::Error as AnyError;
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream, StreamExt};
+use memmap2::Mmap;
+use tokio::fs;
+
+use condow_core::{
+    condow_client::{CondowClient, DownloadSpec},
+    config::{Config, Mebi},
+    errors::CondowError,
+    streams::{BytesHint, BytesStream},
+    Condow,
+};
+
+use crate::{io_error, FsLocation, Sandbox};
+
+/// Reads files via memory mappings
+///
+/// Files are mapped once and the mappings are kept in a cache with
+/// a bounded number of entries. The least recently used mapping is
+/// evicted first. A mapping stays alive as long as [Bytes] referring
+/// to it are alive even if it was evicted from the cache.
+///
+/// The downloaded [Bytes] are backed by the mapping. No data is copied.
+///
+/// # Truncation
+///
+/// Before each request the metadata of the file is compared with the
+/// metadata of the file when it was mapped. If the file was truncated
+/// the request fails with a [CondowError] and the mapping is evicted.
+/// If the file grew, was modified or was replaced by another file
+/// (e.g. renamed over) it is mapped again.
+///
+/// Truncating a file while [Bytes] backed by its mapping are still
+/// being read can not be detected and will crash the process (`SIGBUS`
+/// on unix). Only use this client for files which are not truncated
+/// while being read.
+///
+/// # Root
+///
+/// Locations are confined to a root directory like they are
+/// for [FsClient::with_root](crate::FsClient::with_root).
+#[derive(Clone)]
+pub struct MmapClient {
+    chunk_size: usize,
+    max_mappings: usize,
+    sandbox: Sandbox,
+    cache: Arc<Mutex<MappingCache>>,
+}
+
+impl MmapClient {
+    /// Creates a client with a chunk size of 4 MiB
+    /// which caches up to 64 mappings
+    pub fn new() -> Self {
+        Self {
+            chunk_size: Mebi(4).value() as usize,
+            max_mappings: 64,
+            sandbox: Sandbox::default(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Creates a client like [MmapClient::new] which only
+    /// serves files within the directory `root`
+    ///
+    /// Fails if `root` does not exist or is not a directory.
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<Self, CondowError> {
+        Ok(Self {
+            sandbox: Sandbox::with_root(root)?,
+            ..Self::new()
+        })
+    }
+
+    /// The canonicalized root directory if the client has one
+    pub fn root(&self) -> Option<&Path> {
+        self.sandbox.root()
+    }
+
+    /// Set the maximum size of the chunks of a download
+    ///
+    /// A value of 0 will be treated as 1.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Set the maximum number of mappings kept in the cache
+    ///
+    /// A value of 0 will be treated as 1.
+    pub fn max_mappings(mut self, max_mappings: usize) -> Self {
+        self.max_mappings = max_mappings.max(1);
+        self
+    }
+
+    /// Create a concurrent downloader from this adapter and the given [Config]
+    pub fn into_condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
+    }
+
+    /// The number of mappings currently cached
+    pub fn n_cached_mappings(&self) -> usize {
+        self.cache.lock().unwrap().mappings.len()
+    }
+
+    /// Returns a mapping of the current version of the file
+    async fn mapping(&self, location: &FsLocation) -> Result<Bytes, CondowError> {
+        let path = self.sandbox.resolve(location).await?;
+        let version = FileVersion::new(
+            &fs::metadata(&path)
+                .await
+                .map_err(|err| io_error(err, location))?,
+        );
+
+        let cached = self.cache.lock().unwrap().get(&path);
+        if let Some((mapping, mapped_version)) = cached {
+            if version == mapped_version {
+                return Ok(mapping);
+            }
+            let mapping_len = mapping.len() as u64;
+            if version.is_same_file(&mapped_version) && version.len < mapping_len {
+                self.cache.lock().unwrap().remove(&path);
+                return Err(CondowError::new_io(format!(
+                    "file '{}' was truncated from {} to {} bytes",
+                    location, mapping_len, version.len
+                ))
+                .with_location(location)
+                .with_io_kind(io::ErrorKind::UnexpectedEof));
+            }
+        }
+
+        let file = fs::File::open(&path)
+            .await
+            .map_err(|err| io_error(err, location))?;
+        // The file might have been replaced since the metadata was read
+        let version = FileVersion::new(
+            &file
+                .metadata()
+                .await
+                .map_err(|err| io_error(err, location))?,
+        );
+        let file = file.into_std().await;
+        // SAFETY: The mapping is only read from. Truncation of the file is
+        // checked before each request. See the docs of [MmapClient].
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|err| io_error(err, location))?;
+        let mapping = Bytes::from_owner(mmap);
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(path, mapping.clone(), version, self.max_mappings);
+
+        Ok(mapping)
+    }
+}
+
+impl Default for MmapClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CondowClient for MmapClient {
+    type Location = FsLocation;
+
+    /// Returns the size of the file taken from its metadata
+    ///
+    /// This is the size of the mapping a subsequent download uses.
+    fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let path = client.sandbox.resolve(&location).await?;
+            Ok(fs::metadata(&path)
+                .await
+                .map_err(|err| io_error(err, &location))?
+                .len())
+        };
+
+        Box::pin(f)
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let mapping = client.mapping(&location).await?;
+
+            let bytes = match spec {
+                DownloadSpec::Complete => mapping,
+                DownloadSpec::Range(range) => {
+                    if range.end_incl() >= mapping.len() as u64 {
+                        return Err(CondowError::new_invalid_range(format!(
+                            "max upper bound is {} but {} was requested",
+                            mapping.len().saturating_sub(1),
+                            range.end_incl()
+                        )));
+                    }
+                    mapping.slice(range.start() as usize..=range.end_incl() as usize)
+                }
+            };
+
+            let bytes_hint = BytesHint::new_exact(bytes.len() as u64);
+
+            let chunk_size = client.chunk_size;
+            let chunks = (0..bytes.len())
+                .step_by(chunk_size)
+                .map(move |start| Ok(bytes.slice(start..(start + chunk_size).min(bytes.len()))));
+
+            Ok((stream::iter(chunks).boxed(), bytes_hint))
+        };
+
+        Box::pin(f)
+    }
+}
+
+/// The metadata of a file identifying the contents a mapping was created from
+///
+/// A file replaced by another one has a different inode even if
+/// size and modification time are the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    #[cfg(unix)]
+    dev: u64,
+    #[cfg(unix)]
+    ino: u64,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileVersion {
+    fn new(metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        Self {
+            #[cfg(unix)]
+            dev: metadata.dev(),
+            #[cfg(unix)]
+            ino: metadata.ino(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        }
+    }
+
+    /// Returns `true` if both versions are of the same file on disk
+    /// which might have been modified in between
+    ///
+    /// Without inodes the file is assumed to be the same.
+    fn is_same_file(&self, other: &Self) -> bool {
+        #[cfg(unix)]
+        {
+            self.dev == other.dev && self.ino == other.ino
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = other;
+            true
+        }
+    }
+}
+
+/// Mappings by resolved path with a counter for the least recent use
+#[derive(Default)]
+struct MappingCache {
+    mappings: HashMap<PathBuf, (Bytes, FileVersion, u64)>,
+    n_accesses: u64,
+}
+
+impl MappingCache {
+    fn get(&mut self, path: &Path) -> Option<(Bytes, FileVersion)> {
+        self.n_accesses += 1;
+        let n_accesses = self.n_accesses;
+        self.mappings
+            .get_mut(path)
+            .map(|(mapping, version, last_used)| {
+                *last_used = n_accesses;
+                (mapping.clone(), *version)
+            })
+    }
+
+    fn insert(&mut self, path: PathBuf, mapping: Bytes, version: FileVersion, max_mappings: usize) {
+        self.n_accesses += 1;
+        self.mappings
+            .insert(path, (mapping, version, self.n_accesses));
+
+        while self.mappings.len() > max_mappings {
+            let least_recently_used = self
+                .mappings
+                .iter()
+                .min_by_key(|(_, (_, _, last_used))| *last_used)
+                .map(|(path, _)| path.clone());
+            match least_recently_used {
+                Some(path) => self.mappings.remove(&path),
+                None => break,
+            };
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        self.mappings.remove(path);
+    }
+}
@@ -1,14 +1,26 @@
-use condow_fs::{Condow, FsClient};
+use condow_fs::{
+    condow_client::{CondowClient, DownloadSpec},
+    errors::CondowErrorKind,
+    Condow, FsClient, FsLocation, InclusiveRange,
+};
+use futures::StreamExt;
 
 fn create_condow_condow() -> Condow<FsClient> {
     FsClient::condow(Default::default()).unwrap()
 }
 
-fn get_test_file_path() -> String {
-    format!(
-        "{}/tests/test_data",
-        std::env::current_dir().unwrap().display()
-    )
+fn get_test_file_path() -> FsLocation {
+    get_test_dir().join("test_data").into()
+}
+
+fn get_test_dir() -> std::path::PathBuf {
+    std::env::current_dir().unwrap().join("tests")
+}
+
+fn temp_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("condow_fs_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.display().to_string()
 }
 
 #[tokio::test]
@@ -85,3 +97,354 @@ async fn download_from_to() {
 
     assert_eq!(&data[..], b"bcdefghijk");
 }
+
+#[tokio::test]
+async fn missing_file_is_not_found() {
+    let condow = create_condow_condow();
+
+    let err = condow
+        .get_size(FsLocation::from(format!(
+            "{}_missing",
+            get_test_file_path()
+        )))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.kind(), CondowErrorKind::NotFound);
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::NotFound));
+}
+
+#[tokio::test]
+async fn streams_in_chunks() {
+    let client = FsClient::new().chunk_size(5);
+
+    let (stream, bytes_hint) = client
+        .download(
+            get_test_file_path(),
+            DownloadSpec::Range(InclusiveRange(1, 12)),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(bytes_hint.exact(), Some(12));
+
+    let chunks = stream.map(|chunk| chunk.unwrap()).collect::<Vec<_>>().await;
+    let chunk_lens = chunks.iter().map(|c| c.len()).collect::<Vec<_>>();
+    assert_eq!(chunk_lens, [5, 5, 2]);
+    assert_eq!(chunks.concat(), b"bcdefghijklm");
+}
+
+#[tokio::test]
+async fn complete_download_is_chunked() {
+    let condow = FsClient::new()
+        .chunk_size(10)
+        .into_condow(Default::default())
+        .unwrap();
+
+    let chunk_lens = condow
+        .download_chunks(get_test_file_path(), ..)
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap().bytes.len())
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(chunk_lens, [10, 10, 6]);
+}
+
+#[tokio::test]
+async fn range_beyond_end_of_file_is_invalid() {
+    let result = FsClient::new()
+        .download(
+            get_test_file_path(),
+            DownloadSpec::Range(InclusiveRange(20, 26)),
+        )
+        .await;
+
+    assert_eq!(
+        result.err().map(|err| err.kind()),
+        Some(CondowErrorKind::InvalidRange)
+    );
+}
+
+mod root {
+    use condow_fs::{errors::CondowErrorKind, FsClient, FsLocation};
+
+    use super::{get_test_dir, temp_dir};
+
+    #[tokio::test]
+    async fn locations_are_relative_to_the_root() {
+        let condow = FsClient::with_root(get_test_dir())
+            .unwrap()
+            .into_condow(Default::default())
+            .unwrap();
+
+        let data = condow
+            .download(FsLocation::from("test_data"), 1..4)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap();
+
+        assert_eq!(&data[..], b"bcd");
+    }
+
+    #[tokio::test]
+    async fn missing_root_is_not_found() {
+        let err = FsClient::with_root(get_test_dir().join("missing"))
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), CondowErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_found() {
+        let condow = FsClient::with_root(get_test_dir())
+            .unwrap()
+            .into_condow(Default::default())
+            .unwrap();
+
+        let err = condow
+            .get_size(FsLocation::from("missing"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), CondowErrorKind::NotFound);
+        assert_eq!(err.msg(), "file 'missing' not found");
+    }
+
+    #[tokio::test]
+    async fn parent_dir_is_rejected() {
+        let condow = FsClient::with_root(get_test_dir())
+            .unwrap()
+            .into_condow(Default::default())
+            .unwrap();
+
+        let err = condow
+            .download(FsLocation::from("../tests/test_data"), ..)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), CondowErrorKind::AccessDenied);
+    }
+
+    #[tokio::test]
+    async fn absolute_location_is_rejected() {
+        let condow = FsClient::with_root(get_test_dir())
+            .unwrap()
+            .into_condow(Default::default())
+            .unwrap();
+
+        let err = condow
+            .get_size(super::get_test_file_path())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), CondowErrorKind::AccessDenied);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_must_not_leave_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("root_symlinks");
+        let root = format!("{}/root", dir);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(format!("{}/inside", root), b"inside").unwrap();
+        std::fs::write(format!("{}/outside", dir), b"outside").unwrap();
+        symlink(format!("{}/inside", root), format!("{}/to_inside", root)).unwrap();
+        symlink(format!("{}/outside", dir), format!("{}/to_outside", root)).unwrap();
+
+        let condow = FsClient::with_root(&root)
+            .unwrap()
+            .into_condow(Default::default())
+            .unwrap();
+
+        let size = condow
+            .get_size(FsLocation::from("to_inside"))
+            .await
+            .unwrap();
+        assert_eq!(size, 6);
+
+        let err = condow
+            .get_size(FsLocation::from("to_outside"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), CondowErrorKind::AccessDenied);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[cfg(feature = "mmap")]
+mod mmap {
+    use condow_fs::{
+        condow_client::{CondowClient, DownloadSpec},
+        errors::CondowErrorKind,
+        InclusiveRange, MmapClient,
+    };
+    use futures::StreamExt;
+
+    use super::{get_test_file_path, temp_dir};
+
+    #[tokio::test]
+    async fn download_full() {
+        let condow = MmapClient::new().into_condow(Default::default()).unwrap();
+
+        let data = condow
+            .download(get_test_file_path(), ..)
+            .await
+            .unwrap()
+            .into_vec()
+            .await
+            .unwrap();
+
+        assert_eq!(&data[..], b"abcdefghijklmnopqrstuvwxyz");
+    }
+
+    #[tokio::test]
+    async fn streams_in_chunks() {
+        let client = MmapClient::new().chunk_size(5);
+
+        let (stream, bytes_hint) = client
+            .download(
+                get_test_file_path(),
+                DownloadSpec::Range(InclusiveRange(1, 12)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(bytes_hint.exact(), Some(12));
+
+        let chunks = stream.map(|chunk| chunk.unwrap()).collect::<Vec<_>>().await;
+        let chunk_lens = chunks.iter().map(|c| c.len()).collect::<Vec<_>>();
+        assert_eq!(chunk_lens, [5, 5, 2]);
+        assert_eq!(chunks.concat(), b"bcdefghijklm");
+    }
+
+    #[tokio::test]
+    async fn range_beyond_end_of_file_is_invalid() {
+        let result = MmapClient::new()
+            .download(
+                get_test_file_path(),
+                DownloadSpec::Range(InclusiveRange(20, 26)),
+            )
+            .await;
+
+        assert_eq!(
+            result.err().map(|err| err.kind()),
+            Some(CondowErrorKind::InvalidRange)
+        );
+    }
+
+    #[tokio::test]
+    async fn number_of_cached_mappings_is_bounded() {
+        let dir = temp_dir("mmap_cache");
+        let client = MmapClient::new().max_mappings(2);
+
+        for n in 0..4 {
+            let path = format!("{}/{}", dir, n);
+            std::fs::write(&path, b"abc").unwrap();
+            download(&client, &path).await;
+        }
+
+        assert_eq!(client.n_cached_mappings(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn truncated_file_is_detected() {
+        let dir = temp_dir("mmap_truncated");
+        let path = format!("{}/file", dir);
+        std::fs::write(&path, b"abcdefghij").unwrap();
+        let client = MmapClient::new();
+
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"abcdefghij");
+
+        std::fs::write(&path, b"abc").unwrap();
+        let err = client
+            .download(path.as_str().into(), DownloadSpec::Complete)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(client.n_cached_mappings(), 0);
+
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"abc");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn grown_file_is_mapped_again() {
+        let dir = temp_dir("mmap_grown");
+        let path = format!("{}/file", dir);
+        std::fs::write(&path, b"abc").unwrap();
+        let client = MmapClient::new();
+
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"abc");
+
+        std::fs::write(&path, b"abcdef").unwrap();
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"abcdef");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replaced_file_of_same_size_is_mapped_again() {
+        let dir = temp_dir("mmap_replaced");
+        let path = format!("{}/file", dir);
+        std::fs::write(&path, b"abc").unwrap();
+        let client = MmapClient::new();
+
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"abc");
+
+        let replacement = format!("{}/replacement", dir);
+        std::fs::write(&replacement, b"xyz").unwrap();
+        std::fs::rename(&replacement, &path).unwrap();
+        let size = client.get_size(path.as_str().into()).await.unwrap();
+        assert_eq!(size, 3);
+        let data = download(&client, &path).await;
+        assert_eq!(&data[..], b"xyz");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn size_of_grown_file_is_taken_from_the_file() {
+        let dir = temp_dir("mmap_grown_size");
+        let path = format!("{}/file", dir);
+        std::fs::write(&path, b"abc").unwrap();
+        let client = MmapClient::new();
+
+        download(&client, &path).await;
+        std::fs::write(&path, b"abcdef").unwrap();
+
+        let size = client.get_size(path.as_str().into()).await.unwrap();
+        assert_eq!(size, 6);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    async fn download(client: &MmapClient, path: &str) -> Vec<u8> {
+        let (stream, _) = client
+            .download(path.into(), DownloadSpec::Complete)
+            .await
+            .unwrap();
+        stream
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat()
+    }
+}
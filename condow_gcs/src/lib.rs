@@ -0,0 +1,380 @@
+//! # CONcurrent DOWnloads from Google Cloud Storage
+//!
+//! Download speed from GCS can be significantly improved by
+//! downloading parts of the file concurrently. This crate
+//! does exactly that.
+//!
+//! Objects are downloaded via ranged media downloads of the GCS JSON API.
+//!
+//! ```rust, noexec
+//!
+//! use condow_gcs::*;
+//! use condow_gcs::config::Config;
+//!
+//! # async {
+//! let client = GcsClient::new().bearer_token("my_access_token");
+//! let condow = client.condow(Config::default()).unwrap();
+//!
+//! let location = Bucket::new("my_bucket").object("my_object");
+//!
+//! let stream = condow.download(location, 23..46).await.unwrap();
+//! let downloaded_bytes: Vec<u8> = stream.into_vec().await.unwrap();
+//! # };
+//! # ()
+//! ```
+//!
+//! Use [GcsClient::endpoint] to download from e.g. a local `fake-gcs-server`.
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use anyhow::Error as AnyError;
+use futures::future::BoxFuture;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{header, Response};
+use serde::Deserialize;
+
+use condow_core::{
+    condow_client::*,
+    config::Config,
+    errors::CondowError,
+    streams::{BytesHint, BytesStream},
+};
+use condow_http::{http_err_to_condow_err, response_to_condow_err, response_to_download};
+
+pub use condow_core::*;
+
+/// The endpoint of Google Cloud Storage
+pub const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+
+/// Everything except unreserved characters is encoded.
+///
+/// This also encodes the `/` of object names as required by the JSON API.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// GCS bucket name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bucket(String);
+
+impl Bucket {
+    pub fn new<T: Into<String>>(bucket: T) -> Self {
+        Self(bucket.into())
+    }
+
+    pub fn object<O: Into<ObjectName>>(self, name: O) -> GcsLocation {
+        GcsLocation::new(self, name)
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for Bucket {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for Bucket {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Bucket {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// GCS object name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectName(String);
+
+impl ObjectName {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self(name.into())
+    }
+
+    pub fn in_bucket<B: Into<Bucket>>(self, bucket: B) -> GcsLocation {
+        GcsLocation::new(bucket, self)
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for ObjectName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Deref for ObjectName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ObjectName {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<&str> for ObjectName {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+/// Full "path" to a GCS object
+///
+/// Without a generation the live version of the object is downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GcsLocation {
+    bucket: Bucket,
+    object: ObjectName,
+    generation: Option<i64>,
+}
+
+impl GcsLocation {
+    pub fn new<B: Into<Bucket>, O: Into<ObjectName>>(bucket: B, object: O) -> Self {
+        Self {
+            bucket: bucket.into(),
+            object: object.into(),
+            generation: None,
+        }
+    }
+
+    /// Download the given generation of the object
+    pub fn with_generation(mut self, generation: i64) -> Self {
+        self.generation = Some(generation);
+        self
+    }
+
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
+    pub fn object(&self) -> &ObjectName {
+        &self.object
+    }
+
+    pub fn generation(&self) -> Option<i64> {
+        self.generation
+    }
+
+    /// Turn this into its three components
+    pub fn into_inner(self) -> (Bucket, ObjectName, Option<i64>) {
+        (self.bucket, self.object, self.generation)
+    }
+
+    /// URL of the object in the JSON API with the given query parameter
+    fn url(&self, endpoint: &str, query: &str) -> String {
+        let mut url = format!(
+            "{}/storage/v1/b/{}/o/{}?{}",
+            endpoint,
+            utf8_percent_encode(&self.bucket, PATH_SEGMENT),
+            utf8_percent_encode(&self.object, PATH_SEGMENT),
+            query
+        );
+        if let Some(generation) = self.generation {
+            url.push_str(&format!("&generation={}", generation));
+        }
+        url
+    }
+}
+
+impl fmt::Display for GcsLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gs://{}/{}", self.bucket, self.object)?;
+        if let Some(generation) = self.generation {
+            write!(f, "#{}", generation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Provides OAuth 2.0 access tokens which are sent as bearer tokens
+///
+/// A token is requested for each request to GCS so implementors
+/// should cache tokens and refresh them before they expire.
+pub trait TokenProvider: Send + Sync + 'static {
+    fn token(&self) -> BoxFuture<'static, Result<String, CondowError>>;
+}
+
+/// A static token
+impl TokenProvider for String {
+    fn token(&self) -> BoxFuture<'static, Result<String, CondowError>> {
+        let token = self.clone();
+        Box::pin(async move { Ok(token) })
+    }
+}
+
+/// A client for GCS to implement [CondowClient] on
+///
+/// Without a [TokenProvider] requests are anonymous which
+/// only works for publicly readable objects.
+#[derive(Clone)]
+pub struct GcsClient {
+    http: reqwest::Client,
+    endpoint: Arc<str>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+}
+
+impl GcsClient {
+    /// Create a client for the [DEFAULT_ENDPOINT] with a default HTTP client
+    pub fn new() -> Self {
+        Self::from_client(reqwest::Client::new())
+    }
+
+    /// Create a client for the [DEFAULT_ENDPOINT] with the given HTTP client
+    pub fn from_client(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            endpoint: DEFAULT_ENDPOINT.into(),
+            token_provider: None,
+        }
+    }
+
+    /// Set the endpoint requests are sent to
+    ///
+    /// E.g. `http://localhost:4443` for a local `fake-gcs-server`.
+    pub fn endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').into();
+        self
+    }
+
+    /// Send the given access token with each request
+    pub fn bearer_token<T: Into<String>>(self, token: T) -> Self {
+        self.token_provider(token.into())
+    }
+
+    /// Request an access token for each request from the given [TokenProvider]
+    pub fn token_provider<P: TokenProvider>(mut self, token_provider: P) -> Self {
+        self.token_provider = Some(Arc::new(token_provider));
+        self
+    }
+
+    /// Create a concurrent downloader from this adapter and the given [Config]
+    pub fn condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
+    }
+
+    /// Sends a GET request and fails unless the response is a success
+    async fn get(&self, url: String, range: Option<String>) -> Result<Response, CondowError> {
+        let mut request = self.http.get(url);
+        if let Some(token_provider) = self.token_provider.as_ref() {
+            request = request.bearer_auth(token_provider.token().await?);
+        }
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+
+        let response = request.send().await.map_err(http_err_to_condow_err)?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(response_to_condow_err(response).await)
+        }
+    }
+}
+
+impl Default for GcsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CondowClient for GcsClient {
+    type Location = GcsLocation;
+
+    fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let url = location.url(&client.endpoint, "fields=size");
+            let metadata: ObjectMetadata =
+                client.get(url, None).await?.json().await.map_err(|err| {
+                    CondowError::new_other("response had invalid object metadata")
+                        .with_source(err.without_url())
+                })?;
+
+            metadata.size.parse().map_err(|err| {
+                CondowError::new_other(format!("invalid object size '{}'", metadata.size))
+                    .with_source(err)
+            })
+        };
+
+        Box::pin(f)
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let url = location.url(&client.endpoint, "alt=media");
+            let response = client.get(url, spec.http_range_value()).await?;
+
+            response_to_download(response, &spec)
+        };
+
+        Box::pin(f)
+    }
+}
+
+/// The fields of the object metadata we are interested in
+#[derive(Deserialize)]
+struct ObjectMetadata {
+    /// The JSON API encodes 64 bit integers as strings
+    size: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_display() {
+        let location = Bucket::new("bucket").object("dir/object");
+        assert_eq!(location.to_string(), "gs://bucket/dir/object");
+        assert_eq!(
+            location.with_generation(42).to_string(),
+            "gs://bucket/dir/object#42"
+        );
+    }
+
+    #[test]
+    fn location_url_encodes_object_name() {
+        let location = Bucket::new("bucket")
+            .object("dir/a b?.txt")
+            .with_generation(42);
+
+        assert_eq!(
+            location.url("http://localhost", "alt=media"),
+            "http://localhost/storage/v1/b/bucket/o/dir%2Fa%20b%3F.txt?alt=media&generation=42"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use condow_gcs::{
+    condow_client::{CondowClient, DownloadSpec},
+    errors::CondowErrorKind,
+    Bucket, GcsClient, GcsLocation, InclusiveRange,
+};
+use condow_test_server::{adapter_tests, RangeResponder, TestAdapter, DATA};
+use futures::TryStreamExt;
+use wiremock::{
+    matchers::{header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const OBJECT_PATH: &str = "/storage/v1/b/bucket/o/dir%2Fobject";
+
+fn location() -> GcsLocation {
+    Bucket::new("bucket").object("dir/object")
+}
+
+/// Serves metadata and media of the object at [OBJECT_PATH] like GCS
+struct Gcs;
+
+impl TestAdapter for Gcs {
+    type Client = GcsClient;
+
+    fn object_mocks() -> Vec<Mock> {
+        vec![
+            Mock::given(method("GET"))
+                .and(path(OBJECT_PATH))
+                .and(query_param("fields", "size"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(format!(r#"{{"size":"{}"}}"#, DATA.len())),
+                ),
+            Mock::given(method("GET"))
+                .and(path(OBJECT_PATH))
+                .and(query_param("alt", "media"))
+                .respond_with(RangeResponder::new()),
+        ]
+    }
+
+    fn error_mocks(status: u16) -> Vec<Mock> {
+        vec![Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(status).set_body_string("error"))]
+    }
+
+    fn client(server: &MockServer) -> GcsClient {
+        GcsClient::new().endpoint(server.uri())
+    }
+
+    fn location(_server: &MockServer) -> GcsLocation {
+        location()
+    }
+}
+
+adapter_tests!(Gcs);
+
+#[tokio::test]
+async fn generation_is_sent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(OBJECT_PATH))
+        .and(query_param("alt", "media"))
+        .and(query_param("generation", "42"))
+        .respond_with(RangeResponder::new())
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = GcsClient::new().endpoint(server.uri());
+
+    let (stream, _) = client
+        .download(location().with_generation(42), DownloadSpec::Complete)
+        .await
+        .unwrap();
+
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), DATA);
+}
+
+#[tokio::test]
+async fn bearer_token_is_sent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("authorization", "Bearer my_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"size":"3"}"#))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = GcsClient::new()
+        .endpoint(server.uri())
+        .bearer_token("my_token");
+
+    let size = client.get_size(location()).await.unwrap();
+
+    assert_eq!(size, 3);
+}
+
+#[tokio::test]
+async fn ignored_range_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(DATA))
+        .mount(&server)
+        .await;
+    let client = GcsClient::new().endpoint(server.uri());
+
+    let result = client
+        .download(location(), DownloadSpec::Range(InclusiveRange(2, 5)))
+        .await;
+
+    assert_eq!(
+        result.err().map(|err| err.kind()),
+        Some(CondowErrorKind::Other)
+    );
+}
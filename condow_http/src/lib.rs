@@ -0,0 +1,126 @@
+//! # Shared HTTP response handling for condow adapters
+//!
+//! Maps the errors and responses of [reqwest] to [CondowError]s and
+//! [BytesStream]s for adapters which send HTTP requests themselves.
+//!
+//! The URL is removed from every [reqwest::Error] before it becomes part
+//! of a [CondowError] since URLs may contain credentials like SAS tokens
+//! or the signatures of pre-signed URLs.
+use futures::stream::TryStreamExt;
+use reqwest::{Response, StatusCode};
+
+use condow_core::{
+    condow_client::DownloadSpec,
+    errors::{CondowError, IoError},
+    streams::{BytesHint, BytesStream},
+};
+
+/// Errors which occurred before a response was received are retryable
+/// unless the request could not even be built.
+pub fn http_err_to_condow_err(err: reqwest::Error) -> CondowError {
+    let err = err.without_url();
+    if err.is_builder() {
+        CondowError::new_other(format!("invalid request: {}", err)).with_source(err)
+    } else {
+        CondowError::new_io(format!("request failed: {}", err)).with_source(err)
+    }
+}
+
+/// Maps a response which is not a success by its status and body
+pub async fn response_to_condow_err(response: Response) -> CondowError {
+    let status = response.status();
+    let message = response_text(response).await;
+
+    status_to_condow_err(status, message)
+}
+
+/// The body of a response or a note that it could not be read
+pub async fn response_text(response: Response) -> String {
+    response
+        .text()
+        .await
+        .unwrap_or_else(|_| "<<< response body could not be read >>>".to_string())
+}
+
+/// Throttling (429), request timeouts (408) and server errors are retryable
+pub fn status_to_condow_err(status: StatusCode, message: String) -> CondowError {
+    let message = format!("{} - {}", status, message);
+    match status.as_u16() {
+        404 => CondowError::new_not_found(message),
+        401 | 403 => CondowError::new_access_denied(message),
+        416 => CondowError::new_invalid_range(message),
+        408 | 429 => CondowError::new_remote(message),
+        _ => {
+            if status.is_server_error() {
+                CondowError::new_remote(message)
+            } else {
+                CondowError::new_other(message)
+            }
+        }
+    }
+}
+
+/// Streams the body of a successful response to a download
+///
+/// Ranged downloads must have been answered with 206 (Partial Content).
+pub fn response_to_download(
+    response: Response,
+    spec: &DownloadSpec,
+) -> Result<(BytesStream, BytesHint), CondowError> {
+    if let DownloadSpec::Range(_) = spec {
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(CondowError::new_other(format!(
+                "expected status 206 for a ranged download but got {}",
+                response.status()
+            )));
+        }
+    }
+
+    let bytes_hint = response
+        .content_length()
+        .map(BytesHint::new_exact)
+        .unwrap_or_else(BytesHint::new_no_hint);
+
+    let stream: BytesStream = Box::pin(response.bytes_stream().map_err(|err| {
+        let err = err.without_url();
+        IoError::new(err.to_string()).with_source(err)
+    }));
+
+    Ok((stream, bytes_hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_status_codes() {
+        for status in [408, 429, 500, 503] {
+            let err = status_to_condow_err(StatusCode::from_u16(status).unwrap(), String::new());
+            assert!(err.is_retryable(), "{}", status);
+        }
+        for status in [400, 401, 403, 404, 412, 416] {
+            let err = status_to_condow_err(StatusCode::from_u16(status).unwrap(), String::new());
+            assert!(!err.is_retryable(), "{}", status);
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        use condow_core::errors::CondowErrorKind;
+
+        let expected = [
+            (404, CondowErrorKind::NotFound),
+            (401, CondowErrorKind::AccessDenied),
+            (403, CondowErrorKind::AccessDenied),
+            (416, CondowErrorKind::InvalidRange),
+            (400, CondowErrorKind::Other),
+        ];
+
+        for (status, kind) in expected {
+            let err = status_to_condow_err(StatusCode::from_u16(status).unwrap(), "msg".into());
+            assert_eq!(err.kind(), kind, "{}", status);
+            assert!(err.msg().ends_with(" - msg"), "{}", err.msg());
+        }
+    }
+}
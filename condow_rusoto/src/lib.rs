@@ -5,7 +5,10 @@
 //! does exactly that.
 //!
 //! Unlike e.g. the AWS Java SDK this library does not download
-//! the parts as uploaded but ranges.
+//! the parts as uploaded but ranges. Objects uploaded in multiple parts
+//! can also be downloaded by their part numbers with
+//! [S3ClientWrapper::download_uploaded_parts] or a [Condow] created
+//! from [UploadedParts].
 //!
 //! ```rust, noexec
 //!
@@ -23,15 +26,15 @@
 //! # };
 //! # ()
 //! ```
-use std::{
-    fmt,
-    ops::{Deref, DerefMut},
-};
-
+//!
+//! [S3RequestOptions] like SSE-C keys or requester pays can be set on the
+//! [S3ClientWrapper] and overridden per [S3Location].
 use anyhow::Error as AnyError;
 use futures::{future::BoxFuture, stream::TryStreamExt};
 use rusoto_core::{request::BufferedHttpResponse, RusotoError};
-use rusoto_s3::{GetObjectError, GetObjectRequest, HeadObjectError, HeadObjectRequest, S3};
+use rusoto_s3::{
+    GetObjectError, GetObjectOutput, GetObjectRequest, HeadObjectError, HeadObjectRequest, S3,
+};
 
 pub use rusoto_core::Region;
 pub use rusoto_s3::S3Client;
@@ -44,128 +47,18 @@ use condow_core::{
 };
 
 pub use condow_core::*;
+pub use condow_s3::{Bucket, ObjectKey, S3Location, S3RequestOptions};
+pub use uploaded_parts::{UploadedParts, VerifiedChunkStream};
 
-/// S3 bucket name
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Bucket(String);
-
-impl Bucket {
-    pub fn new<T: Into<String>>(bucket: T) -> Self {
-        Self(bucket.into())
-    }
-
-    pub fn object<O: Into<ObjectKey>>(self, key: O) -> S3Location {
-        S3Location(self, key.into())
-    }
-
-    pub fn into_inner(self) -> String {
-        self.0
-    }
-}
-
-impl fmt::Display for Bucket {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl From<&str> for Bucket {
-    fn from(s: &str) -> Self {
-        Self::new(s)
-    }
-}
-
-impl Deref for Bucket {
-    type Target = str;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for Bucket {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-/// S3 object key
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ObjectKey(String);
-
-impl ObjectKey {
-    pub fn new<T: Into<String>>(key: T) -> Self {
-        Self(key.into())
-    }
-
-    pub fn in_bucket<B: Into<Bucket>>(self, bucket: B) -> S3Location {
-        S3Location(bucket.into(), self)
-    }
-
-    pub fn into_inner(self) -> String {
-        self.0
-    }
-}
-
-impl fmt::Display for ObjectKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl Deref for ObjectKey {
-    type Target = str;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for ObjectKey {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl From<&str> for ObjectKey {
-    fn from(s: &str) -> Self {
-        Self::new(s)
-    }
-}
-
-/// Full "path" to an S3 object
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct S3Location(Bucket, ObjectKey);
-
-impl S3Location {
-    pub fn new<B: Into<Bucket>, O: Into<ObjectKey>>(bucket: B, key: O) -> Self {
-        Self(bucket.into(), key.into())
-    }
-
-    pub fn bucket(&self) -> &Bucket {
-        &self.0
-    }
-
-    pub fn key(&self) -> &ObjectKey {
-        &self.1
-    }
-
-    /// Turn this into its two components
-    pub fn into_inner(self) -> (Bucket, ObjectKey) {
-        (self.0, self.1)
-    }
-}
-
-impl fmt::Display for S3Location {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "s3://{}/{}", self.0, self.1)
-    }
-}
+mod uploaded_parts;
 
 /// Just a wrapper around a clietn
 /// to implement the trait [CondowClient](condow_client::CondowClient) on.
 #[derive(Clone)]
-pub struct S3ClientWrapper<C>(C);
+pub struct S3ClientWrapper<C> {
+    client: C,
+    options: S3RequestOptions,
+}
 
 impl S3ClientWrapper<S3Client> {
     /// Create a new wrapper wrapping the default [S3Client](rusoto_s3::S3Client)
@@ -179,7 +72,16 @@ impl S3ClientWrapper<S3Client> {
 impl<C: S3 + Clone + Send + Sync + 'static> S3ClientWrapper<C> {
     /// Create a new wrapper wrapping given an implementor of [S3](rusoto_s3::S3).
     pub fn from_client(client: C) -> Self {
-        Self(client)
+        Self {
+            client,
+            options: S3RequestOptions::default(),
+        }
+    }
+
+    /// Options applied to all requests unless overridden by an [S3Location]
+    pub fn request_options(mut self, options: S3RequestOptions) -> Self {
+        self.options = options;
+        self
     }
 
     /// Create a concurrent downloader from this adapter and the given [Config]
@@ -192,14 +94,10 @@ impl<C: S3 + Clone + Send + Sync + 'static> CondowClient for S3ClientWrapper<C>
     type Location = S3Location;
 
     fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
-        let client = self.0.clone();
+        let client = self.client.clone();
+        let options = self.options.overridden_by(location.request_options());
         let f = async move {
-            let (bucket, object_key) = location.into_inner();
-            let head_object_request = HeadObjectRequest {
-                bucket: bucket.into_inner(),
-                key: object_key.into_inner(),
-                ..Default::default()
-            };
+            let head_object_request = head_object_request(location, options);
 
             let response = client
                 .head_object(head_object_request)
@@ -221,14 +119,12 @@ impl<C: S3 + Clone + Send + Sync + 'static> CondowClient for S3ClientWrapper<C>
         location: Self::Location,
         spec: DownloadSpec,
     ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
-        let client = self.0.clone();
+        let client = self.client.clone();
+        let options = self.options.overridden_by(location.request_options());
         let f = async move {
-            let (bucket, object_key) = location.into_inner();
             let get_object_request = GetObjectRequest {
-                bucket: bucket.into_inner(),
-                key: object_key.into_inner(),
                 range: spec.http_range_value(),
-                ..Default::default()
+                ..get_object_request(location, options)
             };
 
             let response = client
@@ -236,23 +132,63 @@ impl<C: S3 + Clone + Send + Sync + 'static> CondowClient for S3ClientWrapper<C>
                 .await
                 .map_err(get_obj_err_to_download_err)?;
 
-            let bytes_hint = response
-                .content_length
-                .map(|s| BytesHint::new_exact(s as u64))
-                .unwrap_or_else(BytesHint::new_no_hint);
+            get_object_output_to_download(response)
+        };
 
-            let stream = if let Some(stream) = response.body {
-                stream
-            } else {
-                return Err(CondowError::new_other("response had no body"));
-            };
+        Box::pin(f)
+    }
+}
 
-            let stream: BytesStream = Box::pin(stream.map_err(|err| IoError(err.to_string())));
+fn get_object_output_to_download(
+    response: GetObjectOutput,
+) -> Result<(BytesStream, BytesHint), CondowError> {
+    let bytes_hint = response
+        .content_length
+        .map(|s| BytesHint::new_exact(s as u64))
+        .unwrap_or_else(BytesHint::new_no_hint);
 
-            Ok((stream, bytes_hint))
-        };
+    let stream = if let Some(stream) = response.body {
+        stream
+    } else {
+        return Err(CondowError::new_other("response had no body"));
+    };
 
-        Box::pin(f)
+    let stream: BytesStream = Box::pin(stream.map_err(|err| IoError::new(err.to_string())));
+
+    Ok((stream, bytes_hint))
+}
+
+fn head_object_request(location: S3Location, options: S3RequestOptions) -> HeadObjectRequest {
+    let (bucket, object_key, version_id) = location.into_parts();
+    HeadObjectRequest {
+        bucket: bucket.into_inner(),
+        key: object_key.into_inner(),
+        version_id,
+        sse_customer_algorithm: options.sse_customer_algorithm,
+        sse_customer_key: options.sse_customer_key,
+        sse_customer_key_md5: options.sse_customer_key_md5,
+        request_payer: options.request_payer,
+        expected_bucket_owner: options.expected_bucket_owner,
+        if_match: options.if_match,
+        if_unmodified_since: options.if_unmodified_since,
+        ..Default::default()
+    }
+}
+
+fn get_object_request(location: S3Location, options: S3RequestOptions) -> GetObjectRequest {
+    let (bucket, object_key, version_id) = location.into_parts();
+    GetObjectRequest {
+        bucket: bucket.into_inner(),
+        key: object_key.into_inner(),
+        version_id,
+        sse_customer_algorithm: options.sse_customer_algorithm,
+        sse_customer_key: options.sse_customer_key,
+        sse_customer_key_md5: options.sse_customer_key_md5,
+        request_payer: options.request_payer,
+        expected_bucket_owner: options.expected_bucket_owner,
+        if_match: options.if_match,
+        if_unmodified_since: options.if_unmodified_since,
+        ..Default::default()
     }
 }
 
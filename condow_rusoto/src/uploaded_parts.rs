@@ -0,0 +1,431 @@
+//! Downloading objects by the part numbers of their multipart upload
+use std::{
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use anyhow::Error as AnyError;
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt, Stream, StreamExt,
+};
+use rusoto_s3::{GetObjectRequest, HeadObjectRequest, S3};
+
+use condow_core::{
+    condow_client::{CondowClient, DownloadSpec},
+    config::Config,
+    errors::CondowError,
+    streams::{BytesHint, BytesStream, ChunkStream, ChunkStreamItem, PartStream},
+    Condow, InclusiveRange,
+};
+
+use crate::{
+    get_obj_err_to_download_err, get_object_output_to_download, get_object_request,
+    head_obj_err_to_get_size_err, head_object_request, S3ClientWrapper, S3Location,
+};
+
+impl<C: S3 + Clone + Send + Sync + 'static> S3ClientWrapper<C> {
+    /// Discover the parts of the multipart upload of an object
+    ///
+    /// The number of parts and their size are discovered with a "HeadObject"
+    /// request for part number 1. The size of the object is requested with the
+    /// ETag of that response as `If-Match` so that both belong to the same object.
+    /// Objects which were not uploaded in multiple parts have a single part.
+    ///
+    /// All requests for the parts are made with the ETag of the object as `If-Match`
+    /// unless `if_match` was set in the [S3RequestOptions](crate::S3RequestOptions).
+    pub async fn uploaded_parts(
+        &self,
+        location: S3Location,
+    ) -> Result<UploadedParts<C>, CondowError> {
+        let mut options = self.options.overridden_by(location.request_options());
+
+        let head_first_part = HeadObjectRequest {
+            part_number: Some(1),
+            ..head_object_request(location.clone(), options.clone())
+        };
+        let response = self
+            .client
+            .head_object(head_first_part)
+            .await
+            .map_err(|err| head_obj_err_to_get_size_err(err).with_location(&location))?;
+
+        let n_parts = match response.parts_count {
+            None => 1,
+            Some(n_parts) if n_parts > 0 => n_parts as u64,
+            Some(n_parts) => {
+                return Err(
+                    CondowError::new_other(format!("invalid number of parts: {}", n_parts))
+                        .with_location(&location),
+                )
+            }
+        };
+        let part_size = match response.content_length {
+            Some(part_size) if part_size >= 0 => part_size as u64,
+            _ => {
+                return Err(CondowError::new_other("response had no content length")
+                    .with_location(&location))
+            }
+        };
+
+        let is_encrypted = response.sse_customer_algorithm.is_some()
+            || response
+                .server_side_encryption
+                .as_deref()
+                .map(|sse| sse.starts_with("aws:kms"))
+                .unwrap_or(false);
+        let expected_digest = match response.e_tag.as_deref() {
+            Some(e_tag) if !is_encrypted => multipart_digest_from_e_tag(e_tag, n_parts),
+            _ => None,
+        };
+
+        if options.if_match.is_none() {
+            options.if_match = response.e_tag;
+        }
+        let client = S3ClientWrapper {
+            client: self.client.clone(),
+            options,
+        };
+
+        let size = if n_parts == 1 {
+            part_size
+        } else {
+            client
+                .get_size(location.clone())
+                .await
+                .map_err(|err| err.with_location(&location))?
+        };
+
+        Ok(UploadedParts {
+            client,
+            size,
+            part_size,
+            n_parts,
+            expected_digest,
+        })
+    }
+
+    /// Download a whole object by the part numbers of its multipart upload
+    ///
+    /// The parts are discovered with [S3ClientWrapper::uploaded_parts] and
+    /// downloaded by a [Condow] created with [UploadedParts::condow]. Therefore
+    /// the retries, the concurrency and the limits of the [Config] apply.
+    ///
+    /// The returned stream verifies the multipart ETag of the object.
+    /// See [UploadedParts::verify].
+    pub async fn download_uploaded_parts(
+        &self,
+        location: S3Location,
+        config: &Config,
+    ) -> Result<VerifiedChunkStream, CondowError> {
+        let parts = self.uploaded_parts(location.clone()).await?;
+        let condow = parts
+            .clone()
+            .condow(config.clone())
+            .map_err(|err| CondowError::new_other("invalid config").with_source(err))?;
+
+        let chunks = condow.download_chunks(location, ..).await?;
+
+        Ok(parts.verify(chunks))
+    }
+}
+
+/// The parts of an object uploaded in multiple parts
+///
+/// This is a [CondowClient] which downloads each range matching an uploaded part
+/// with a "GetObject" request for its part number. Other ranges (e.g. to resume
+/// a broken stream) are downloaded with a range. All requests are made for the object
+/// the parts were discovered for with [S3ClientWrapper::uploaded_parts].
+///
+/// Only uploads with parts of the same size (except the last one) are supported.
+/// Downloading a part with a different range fails.
+#[derive(Clone)]
+pub struct UploadedParts<C> {
+    client: S3ClientWrapper<C>,
+    size: u64,
+    part_size: u64,
+    n_parts: u64,
+    expected_digest: Option<String>,
+}
+
+impl<C: S3 + Clone + Send + Sync + 'static> UploadedParts<C> {
+    /// The size of the object in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The size of the uploaded parts in bytes
+    ///
+    /// The last part can be smaller.
+    pub fn part_size(&self) -> u64 {
+        self.part_size
+    }
+
+    /// The number of uploaded parts
+    pub fn n_parts(&self) -> u64 {
+        self.n_parts
+    }
+
+    /// Create a concurrent downloader for the parts with the given [Config]
+    ///
+    /// The part size of the [Config] is replaced with the size of the uploaded parts.
+    pub fn condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        let part_size = self.part_size.max(1);
+        Condow::new(self, config.part_size_bytes(part_size))
+    }
+
+    /// Verify the multipart ETag of the object while the chunks are consumed
+    ///
+    /// `chunks` must be a download of the whole object by a [Condow] created
+    /// with [UploadedParts::condow] so that the parts of the download are the
+    /// uploaded parts.
+    ///
+    /// If the ETag is a multipart ETag (`"{md5 of the part digests}-{number of parts}"`)
+    /// a mismatch is the last item of the stream. ETags of objects encrypted with SSE-C
+    /// or SSE-KMS are not verified since they are not derived from the contents.
+    pub fn verify(&self, chunks: ChunkStream) -> VerifiedChunkStream {
+        VerifiedChunkStream {
+            chunks,
+            expected_digest: self.expected_digest.clone(),
+            part_digests: (0..self.n_parts).map(|_| md5::Context::new()).collect(),
+            is_closed: false,
+        }
+    }
+
+    /// The part number of the uploaded part with exactly the given range
+    fn part_number(&self, range: InclusiveRange) -> Option<u64> {
+        let part_index = range.start() / self.part_size.max(1);
+        if part_index >= self.n_parts {
+            return None;
+        }
+
+        let start = part_index * self.part_size;
+        let end_incl = (start + self.part_size).min(self.size).checked_sub(1)?;
+        if range == InclusiveRange(start, end_incl) {
+            Some(part_index + 1)
+        } else {
+            None
+        }
+    }
+
+    fn download_part(
+        &self,
+        location: S3Location,
+        part_number: u64,
+        range: InclusiveRange,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.client.client.clone();
+        let options = self
+            .client
+            .options
+            .overridden_by(location.request_options());
+        let size = self.size;
+        let f = async move {
+            let get_object_request = GetObjectRequest {
+                part_number: Some(part_number as i64),
+                ..get_object_request(location, options)
+            };
+
+            let response = client
+                .get_object(get_object_request)
+                .await
+                .map_err(get_obj_err_to_download_err)?;
+
+            let content_range = response.content_range.as_deref().unwrap_or_default();
+            if parse_content_range(content_range) != Some((range, size)) {
+                return Err(CondowError::new_other(format!(
+                    "part {} has the content range '{}' instead of {} of {} bytes \
+                     (parts of different sizes are not supported)",
+                    part_number, content_range, range, size
+                )));
+            }
+
+            get_object_output_to_download(response)
+        };
+
+        Box::pin(f)
+    }
+}
+
+impl<C: S3 + Clone + Send + Sync + 'static> CondowClient for UploadedParts<C> {
+    type Location = S3Location;
+
+    /// The size discovered with [S3ClientWrapper::uploaded_parts]
+    fn get_size(&self, _location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        future::ready(Ok(self.size)).boxed()
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        if let DownloadSpec::Range(range) = spec {
+            if let Some(part_number) = self.part_number(range) {
+                return self.download_part(location, part_number, range);
+            }
+        }
+
+        self.client.download(location, spec)
+    }
+}
+
+/// A [ChunkStream] of a whole object verifying the multipart ETag of the object
+///
+/// Created with [UploadedParts::verify].
+pub struct VerifiedChunkStream {
+    chunks: ChunkStream,
+    expected_digest: Option<String>,
+    part_digests: Vec<md5::Context>,
+    is_closed: bool,
+}
+
+impl VerifiedChunkStream {
+    /// Hint on the remaining bytes on this stream.
+    pub fn bytes_hint(&self) -> BytesHint {
+        self.chunks.bytes_hint()
+    }
+
+    /// Creates a `Vec<u8>` filled with the bytes of the object.
+    ///
+    /// Fails if there is an error on the stream
+    pub async fn into_vec(self) -> Result<Vec<u8>, CondowError> {
+        let bytes_hint = self.bytes_hint();
+        PartStream::new(self, bytes_hint).into_vec().await
+    }
+}
+
+impl Stream for VerifiedChunkStream {
+    type Item = ChunkStreamItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_closed {
+            return Poll::Ready(None);
+        }
+
+        let next = futures::ready!(self.chunks.poll_next_unpin(cx));
+        match next.as_ref() {
+            Some(Ok(chunk)) => {
+                if let Some(context) = self.part_digests.get_mut(chunk.part_index as usize) {
+                    context.consume(&chunk.bytes);
+                }
+            }
+            Some(Err(_)) => self.is_closed = true,
+            None => {
+                self.is_closed = true;
+                if let Some(expected) = self.expected_digest.take() {
+                    let part_digests = mem::take(&mut self.part_digests)
+                        .into_iter()
+                        .map(md5::Context::compute)
+                        .collect::<Vec<_>>();
+                    if let Err(err) = verify_multipart_digest(&expected, &part_digests) {
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+            }
+        }
+
+        Poll::Ready(next)
+    }
+}
+
+/// The range and the total size of a `Content-Range` like `bytes 0-99/200`
+fn parse_content_range(content_range: &str) -> Option<(InclusiveRange, u64)> {
+    let (range, size) = content_range
+        .trim()
+        .strip_prefix("bytes ")?
+        .split_once('/')?;
+    let (start, end_incl) = range.split_once('-')?;
+
+    Some((
+        InclusiveRange(start.parse().ok()?, end_incl.parse().ok()?),
+        size.parse().ok()?,
+    ))
+}
+
+/// The hex encoded digest of a multipart ETag of an object uploaded in `n_parts`
+///
+/// `None` if the ETag is not a multipart ETag for `n_parts` parts.
+fn multipart_digest_from_e_tag(e_tag: &str, n_parts: u64) -> Option<String> {
+    let (digest, n) = e_tag.trim_matches('"').split_once('-')?;
+
+    let is_digest = digest.len() == 32 && digest.chars().all(|c| c.is_ascii_hexdigit());
+    if is_digest && n.parse::<u64>().ok()? == n_parts {
+        Some(digest.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// The digest of a multipart ETag is the MD5 of the concatenated MD5s of the parts
+fn verify_multipart_digest(
+    expected: &str,
+    part_digests: &[md5::Digest],
+) -> Result<(), CondowError> {
+    let mut context = md5::Context::new();
+    for digest in part_digests {
+        context.consume(digest.0);
+    }
+    let actual = format!("{:x}", context.compute());
+
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(CondowError::new_other(format!(
+            "the downloaded parts do not match the ETag (expected '{}', got '{}')",
+            expected, actual
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_range_is_parsed() {
+        assert_eq!(
+            parse_content_range("bytes 0-99/200"),
+            Some((InclusiveRange(0, 99), 200))
+        );
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some((InclusiveRange(100, 199), 200))
+        );
+        assert_eq!(parse_content_range("bytes */200"), None);
+        assert_eq!(parse_content_range("bytes 0-99/*"), None);
+        assert_eq!(parse_content_range("100-199/200"), None);
+    }
+
+    #[test]
+    fn multipart_e_tag_is_parsed() {
+        let digest = "d41d8cd98f00b204e9800998ecf8427e";
+
+        assert_eq!(
+            multipart_digest_from_e_tag(&format!("\"{}-2\"", digest), 2),
+            Some(digest.to_string())
+        );
+        assert_eq!(
+            multipart_digest_from_e_tag(&format!("\"{}-3\"", digest), 2),
+            None
+        );
+        assert_eq!(
+            multipart_digest_from_e_tag(&format!("\"{}\"", digest), 1),
+            None
+        );
+        assert_eq!(multipart_digest_from_e_tag("\"abc-1\"", 1), None);
+    }
+
+    #[test]
+    fn multipart_digest_is_verified() {
+        let parts = [md5::compute(b"abcde"), md5::compute(b"fgh")];
+        let mut concatenated = Vec::new();
+        concatenated.extend_from_slice(&parts[0].0);
+        concatenated.extend_from_slice(&parts[1].0);
+        let expected = format!("{:x}", md5::compute(&concatenated));
+
+        assert!(verify_multipart_digest(&expected, &parts).is_ok());
+        assert!(verify_multipart_digest(&expected, &parts[..1]).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use condow_rusoto::{
+    config::{Config, RetryConfig},
+    Bucket, Region, S3Client, S3ClientWrapper,
+};
+use rusoto_core::signature::SignedRequest;
+use rusoto_mock::{MockCredentialsProvider, MockRequestDispatcher, MultipleMockRequestDispatcher};
+
+/// The object "abcdefgh" uploaded in the parts "abcde" and "fgh"
+const PARTS: [&str; 2] = ["abcde", "fgh"];
+
+fn multipart_e_tag() -> String {
+    let mut part_digests = Vec::new();
+    for part in PARTS {
+        part_digests.extend_from_slice(&md5::compute(part).0);
+    }
+    format!("\"{:x}-{}\"", md5::compute(&part_digests), PARTS.len())
+}
+
+fn part_number(request: &SignedRequest) -> Option<&str> {
+    request
+        .params
+        .get("partNumber")
+        .and_then(|value| value.as_deref())
+}
+
+fn if_match(request: &SignedRequest) -> Option<String> {
+    request
+        .headers
+        .get("if-match")
+        .map(|values| String::from_utf8(values[0].clone()).unwrap())
+}
+
+/// Responses in the order of the requests of a download with a concurrency of 1
+///
+/// The first request for the second part fails with a retryable error.
+fn responses(e_tag: String) -> Vec<MockRequestDispatcher> {
+    let mut responses = vec![
+        MockRequestDispatcher::default()
+            .with_header("content-length", "5")
+            .with_header("etag", &e_tag)
+            .with_header("x-amz-mp-parts-count", "2")
+            .with_request_checker(|request| {
+                assert_eq!(request.method(), "HEAD");
+                assert_eq!(part_number(request), Some("1"));
+            }),
+        MockRequestDispatcher::default()
+            .with_header("content-length", "8")
+            .with_header("etag", &e_tag)
+            .with_request_checker({
+                let e_tag = e_tag.clone();
+                move |request| {
+                    assert_eq!(request.method(), "HEAD");
+                    assert_eq!(part_number(request), None);
+                    assert_eq!(if_match(request), Some(e_tag.clone()));
+                }
+            }),
+    ];
+
+    let gets = [
+        (206, "1", "bytes 0-4/8", PARTS[0]),
+        (503, "2", "", "<Error><Code>SlowDown</Code></Error>"),
+        (206, "2", "bytes 5-7/8", PARTS[1]),
+    ];
+    for (status, number, content_range, body) in gets {
+        let e_tag = e_tag.clone();
+        let mut response = MockRequestDispatcher::with_status(status)
+            .with_body(body)
+            .with_request_checker(move |request| {
+                assert_eq!(request.method(), "GET");
+                assert_eq!(part_number(request), Some(number));
+                assert_eq!(if_match(request), Some(e_tag.clone()));
+            });
+        if status == 206 {
+            response = response
+                .with_header("content-length", &body.len().to_string())
+                .with_header("content-range", content_range);
+        }
+        responses.push(response);
+    }
+
+    responses
+}
+
+fn client(responses: Vec<MockRequestDispatcher>) -> S3ClientWrapper<S3Client> {
+    let client = S3Client::new_with(
+        MultipleMockRequestDispatcher::new(responses),
+        MockCredentialsProvider,
+        Region::UsEast1,
+    );
+    S3ClientWrapper::from_client(client)
+}
+
+fn config() -> Config {
+    Config::default()
+        .max_concurrency(1)
+        .retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0))
+}
+
+#[tokio::test]
+async fn download_uploaded_parts() {
+    let client = client(responses(multipart_e_tag()));
+
+    let data = client
+        .download_uploaded_parts(Bucket::new("bucket").object("object"), &config())
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, b"abcdefgh");
+}
+
+#[tokio::test]
+async fn download_uploaded_parts_with_mismatching_e_tag() {
+    let e_tag = format!("\"{:x}-2\"", md5::compute("other parts"));
+    let client = client(responses(e_tag));
+
+    let err = client
+        .download_uploaded_parts(Bucket::new("bucket").object("object"), &config())
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap_err();
+
+    assert!(err.msg().contains("do not match the ETag"), "{}", err);
+}
@@ -0,0 +1,526 @@
+//! # Types shared by the S3 adapters of condow
+//!
+//! [Bucket], [ObjectKey], [S3Location] and [S3RequestOptions] are used by
+//! `condow_rusoto` and `condow_aws_sdk` which both re-export them.
+//!
+//! ```rust
+//! use condow_s3::*;
+//!
+//! let location = Bucket::new("my_bucket").object("my_object");
+//!
+//! assert_eq!(location.to_string(), "s3://my_bucket/my_object");
+//!
+//! let location: S3Location = "https://my_bucket.s3.eu-central-1.amazonaws.com/my%20object"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(location, Bucket::new("my_bucket").object("my object"));
+//! ```
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Error as AnyError};
+use percent_encoding::percent_decode_str;
+
+mod request_options;
+
+pub use request_options::S3RequestOptions;
+
+/// S3 bucket name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bucket(String);
+
+impl Bucket {
+    pub fn new<T: Into<String>>(bucket: T) -> Self {
+        Self(bucket.into())
+    }
+
+    pub fn object<O: Into<ObjectKey>>(self, key: O) -> S3Location {
+        S3Location::new(self, key)
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for Bucket {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for Bucket {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Bucket {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// S3 object key
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectKey(String);
+
+impl ObjectKey {
+    pub fn new<T: Into<String>>(key: T) -> Self {
+        Self(key.into())
+    }
+
+    pub fn in_bucket<B: Into<Bucket>>(self, bucket: B) -> S3Location {
+        S3Location::new(bucket, self)
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for ObjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Deref for ObjectKey {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ObjectKey {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<&str> for ObjectKey {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+/// Full "path" to an S3 object
+///
+/// Without a version id the current version of the object is downloaded.
+///
+/// # Parsing
+///
+/// An [S3Location] can be parsed from
+///
+/// * S3 URIs: `s3://bucket/key`
+/// * virtual-hosted-style URLs: `https://bucket.s3.region.amazonaws.com/key`
+/// * path-style URLs: `https://s3.region.amazonaws.com/bucket/key`
+///
+/// Keys of URLs are URL-decoded while keys of S3 URIs are taken as they are.
+/// A version id can be given with the query parameter `versionId`.
+/// In S3 URIs it must be the only query parameter after the last `?`.
+/// Otherwise the `?` is part of the key. Therefore a key ending with
+/// `?versionId=<id>` can not be parsed from an S3 URI.
+///
+/// # Comparison
+///
+/// Locations are compared and hashed by bucket, key and version id.
+/// The [S3RequestOptions] are ignored since they do not identify the
+/// object and may contain secrets like an SSE-C key.
+#[derive(Debug, Clone)]
+pub struct S3Location {
+    bucket: Bucket,
+    key: ObjectKey,
+    version_id: Option<String>,
+    request_options: Option<S3RequestOptions>,
+}
+
+impl S3Location {
+    pub fn new<B: Into<Bucket>, O: Into<ObjectKey>>(bucket: B, key: O) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key: key.into(),
+            version_id: None,
+            request_options: None,
+        }
+    }
+
+    /// Download the given version of the object
+    pub fn with_version_id<T: Into<String>>(mut self, version_id: T) -> Self {
+        self.version_id = Some(version_id.into());
+        self
+    }
+
+    /// Override the [S3RequestOptions] of the client for this object
+    ///
+    /// The options are not part of the [Display](fmt::Display) output.
+    pub fn with_request_options(mut self, options: S3RequestOptions) -> Self {
+        self.request_options = Some(options);
+        self
+    }
+
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
+    pub fn key(&self) -> &ObjectKey {
+        &self.key
+    }
+
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn request_options(&self) -> Option<&S3RequestOptions> {
+        self.request_options.as_ref()
+    }
+
+    /// Turn this into its two components
+    ///
+    /// The version id and the request options are dropped.
+    /// Use [S3Location::into_parts] to keep the version id.
+    pub fn into_inner(self) -> (Bucket, ObjectKey) {
+        (self.bucket, self.key)
+    }
+
+    /// Turn this into bucket, key and version id
+    ///
+    /// The request options are dropped.
+    pub fn into_parts(self) -> (Bucket, ObjectKey, Option<String>) {
+        (self.bucket, self.key, self.version_id)
+    }
+
+    /// The components identifying the object
+    fn id(&self) -> (&Bucket, &ObjectKey, Option<&str>) {
+        (&self.bucket, &self.key, self.version_id.as_deref())
+    }
+}
+
+impl PartialEq for S3Location {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for S3Location {}
+
+impl PartialOrd for S3Location {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for S3Location {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
+impl Hash for S3Location {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state)
+    }
+}
+
+impl fmt::Display for S3Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s3://{}/{}", self.bucket, self.key)?;
+        if let Some(version_id) = self.version_id.as_deref() {
+            write!(f, "?versionId={}", version_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for S3Location {
+    type Err = AnyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(uri) = s.strip_prefix("s3://") {
+            parse_s3_uri(uri)
+        } else if let Some(url) = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+        {
+            parse_url(url)
+        } else {
+            bail!("'{}' is neither an S3 URI nor an http(s) URL", s)
+        }
+        .with_context(|| format!("invalid S3 location '{}'", s))
+    }
+}
+
+impl TryFrom<&str> for S3Location {
+    type Error = AnyError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Parses `bucket/key` with an optional `?versionId=...`
+///
+/// Keys may contain `?` themselves. Only a `versionId` which is the
+/// sole query parameter after the last `?` is taken as the version id.
+fn parse_s3_uri(uri: &str) -> Result<S3Location, AnyError> {
+    let version_id = uri
+        .rsplit_once('?')
+        .and_then(|(uri, query)| Some((uri, query.strip_prefix("versionId=")?)))
+        .filter(|(_, version_id)| !version_id.contains('&'));
+    let (uri, version_id) = match version_id {
+        Some((_, "")) => bail!("the version id is empty"),
+        Some((uri, version_id)) => (uri, Some(version_id)),
+        None => (uri, None),
+    };
+
+    let (bucket, key) = uri.split_once('/').unwrap_or((uri, ""));
+
+    new_location(bucket, key.to_string(), version_id.map(str::to_string))
+}
+
+/// Parses a URL without its scheme in virtual-hosted or path style
+fn parse_url(url: &str) -> Result<S3Location, AnyError> {
+    let (url, query) = url.split_once('?').unwrap_or((url, ""));
+    let (host, path) = url.split_once('/').unwrap_or((url, ""));
+    let host = host.split(':').next().unwrap_or(host);
+
+    let domain = host
+        .strip_suffix(".amazonaws.com")
+        .or_else(|| host.strip_suffix(".amazonaws.com.cn"))
+        .with_context(|| format!("'{}' is not an S3 endpoint", host))?;
+
+    let (bucket, key) = if is_path_style_domain(domain) {
+        path.split_once('/').unwrap_or((path, ""))
+    } else {
+        // `bucket.s3`, `bucket.s3.region` or `bucket.s3-region`
+        let bucket_end = domain
+            .strip_suffix(".s3")
+            .map(str::len)
+            .max(domain.rfind(".s3."))
+            .max(domain.rfind(".s3-"))
+            .with_context(|| format!("'{}' is not an S3 endpoint", host))?;
+        (&domain[..bucket_end], path)
+    };
+
+    let key = percent_decode_str(key)
+        .decode_utf8()
+        .context("the key is not valid UTF-8 when URL-decoded")?;
+
+    let mut version_id = None;
+    for pair in query.split('&') {
+        if let Some(value) = pair.strip_prefix("versionId=") {
+            let value = percent_decode_str(value)
+                .decode_utf8()
+                .context("the version id is not valid UTF-8 when URL-decoded")?;
+            if value.is_empty() {
+                bail!("the version id is empty");
+            }
+            version_id = Some(value.into_owned());
+        }
+    }
+
+    new_location(bucket, key.into_owned(), version_id)
+}
+
+/// Whether the domain (without `.amazonaws.com`) is `s3`, `s3.region`,
+/// `s3-region` or `s3.dualstack.region`
+///
+/// Buckets of virtual-hosted style URLs may start with `s3.` themselves.
+fn is_path_style_domain(domain: &str) -> bool {
+    let region = match domain.strip_prefix("s3") {
+        Some("") => return true,
+        Some(rest) => rest
+            .strip_prefix(".dualstack.")
+            .or_else(|| rest.strip_prefix('.'))
+            .or_else(|| rest.strip_prefix('-')),
+        None => None,
+    };
+
+    region.is_some_and(|region| !region.is_empty() && !region.contains('.'))
+}
+
+fn new_location(
+    bucket: &str,
+    key: String,
+    version_id: Option<String>,
+) -> Result<S3Location, AnyError> {
+    if bucket.is_empty() {
+        bail!("the bucket is missing");
+    }
+    if key.is_empty() {
+        bail!("the key is missing");
+    }
+
+    let location = S3Location::new(bucket, ObjectKey::new(key));
+    Ok(match version_id {
+        Some(version_id) => location.with_version_id(version_id),
+        None => location,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location() -> S3Location {
+        Bucket::new("bucket").object("dir/my object")
+    }
+
+    #[test]
+    fn parse_s3_uri() {
+        let parsed: S3Location = "s3://bucket/dir/my object".parse().unwrap();
+        assert_eq!(parsed, location());
+    }
+
+    #[test]
+    fn parse_s3_uri_with_version_id() {
+        let parsed: S3Location = "s3://bucket/dir/my object?versionId=v1".parse().unwrap();
+        assert_eq!(parsed, location().with_version_id("v1"));
+    }
+
+    #[test]
+    fn parse_s3_uri_with_question_marks_in_key() {
+        let parsed: S3Location = "s3://bucket/dir/what?.txt".parse().unwrap();
+        assert_eq!(parsed, Bucket::new("bucket").object("dir/what?.txt"));
+
+        let parsed: S3Location = "s3://bucket/dir/a?versionId=v1&b".parse().unwrap();
+        assert_eq!(parsed, Bucket::new("bucket").object("dir/a?versionId=v1&b"));
+
+        let parsed: S3Location = "s3://bucket/dir/what??versionId=v1".parse().unwrap();
+        assert_eq!(
+            parsed,
+            Bucket::new("bucket")
+                .object("dir/what?")
+                .with_version_id("v1")
+        );
+    }
+
+    #[test]
+    fn request_options_are_not_compared() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |location: &S3Location| {
+            let mut hasher = DefaultHasher::new();
+            location.hash(&mut hasher);
+            hasher.finish()
+        };
+        let with_options = location().with_request_options(
+            S3RequestOptions::new().sse_customer_key("AES256", "a2V5", "bWQ1"),
+        );
+
+        assert_eq!(with_options, location());
+        assert_eq!(with_options.cmp(&location()), Ordering::Equal);
+        assert_eq!(hash(&with_options), hash(&location()));
+        assert_ne!(with_options, location().with_version_id("v1"));
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        let with_question_marks = Bucket::new("bucket").object("dir/a?versionId=v1&b?");
+        for location in [
+            location(),
+            location().with_version_id("v1"),
+            with_question_marks.clone(),
+            with_question_marks.with_version_id("v1"),
+        ] {
+            let parsed: S3Location = location.to_string().parse().unwrap();
+            assert_eq!(parsed, location);
+        }
+    }
+
+    #[test]
+    fn parse_virtual_hosted_style_urls() {
+        for url in [
+            "https://bucket.s3.amazonaws.com/dir/my%20object",
+            "https://bucket.s3.eu-central-1.amazonaws.com/dir/my%20object",
+            "https://bucket.s3-eu-central-1.amazonaws.com/dir/my%20object",
+            "https://bucket.s3.dualstack.eu-central-1.amazonaws.com/dir/my%20object",
+            "http://bucket.s3.amazonaws.com/dir/my%20object",
+        ] {
+            let parsed = S3Location::try_from(url).unwrap();
+            assert_eq!(parsed, location(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_virtual_hosted_style_url_with_dots_in_bucket() {
+        let parsed: S3Location = "https://my.s3.bucket.s3.eu-central-1.amazonaws.com/key"
+            .parse()
+            .unwrap();
+        assert_eq!(parsed, Bucket::new("my.s3.bucket").object("key"));
+    }
+
+    #[test]
+    fn parse_virtual_hosted_style_url_with_bucket_starting_with_s3() {
+        for (url, bucket) in [
+            ("https://s3.logs.s3.amazonaws.com/key", "s3.logs"),
+            (
+                "https://s3.logs.s3.eu-central-1.amazonaws.com/key",
+                "s3.logs",
+            ),
+            (
+                "https://s3-logs.s3-eu-central-1.amazonaws.com/key",
+                "s3-logs",
+            ),
+        ] {
+            let parsed = S3Location::try_from(url).unwrap();
+            assert_eq!(parsed, Bucket::new(bucket).object("key"), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_path_style_urls() {
+        for url in [
+            "https://s3.amazonaws.com/bucket/dir/my%20object",
+            "https://s3.eu-central-1.amazonaws.com/bucket/dir/my%20object",
+            "https://s3-eu-central-1.amazonaws.com/bucket/dir/my%20object",
+            "https://s3.dualstack.eu-central-1.amazonaws.com/bucket/dir/my%20object",
+            "https://s3.cn-north-1.amazonaws.com.cn/bucket/dir/my%20object",
+        ] {
+            let parsed = S3Location::try_from(url).unwrap();
+            assert_eq!(parsed, location(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_url_with_version_id() {
+        let parsed: S3Location =
+            "https://bucket.s3.amazonaws.com/dir/my%20object?response-content-type=x&versionId=v%2B1"
+                .parse()
+                .unwrap();
+        assert_eq!(parsed, location().with_version_id("v+1"));
+    }
+
+    #[test]
+    fn invalid_locations() {
+        for invalid in [
+            "bucket/key",
+            "s3://",
+            "s3://bucket",
+            "s3://bucket/",
+            "s3:///key",
+            "https://example.com/bucket/key",
+            "https://s3.amazonaws.com/bucket",
+            "https://bucket.s3.amazonaws.com/",
+            "s3://bucket/key?versionId=",
+            "https://bucket.s3.amazonaws.com/key?versionId=",
+        ] {
+            assert!(invalid.parse::<S3Location>().is_err(), "{}", invalid);
+        }
+    }
+}
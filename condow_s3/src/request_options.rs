@@ -0,0 +1,150 @@
+//! Options applied to the requests for an object
+use std::fmt;
+
+/// Options applied to "HeadObject" and "GetObject" requests
+///
+/// Options can be set on a client and overridden for an
+/// [S3Location](crate::S3Location) via
+/// [S3Location::with_request_options](crate::S3Location::with_request_options).
+/// Each option set on the location replaces the one of the client.
+///
+/// The SSE-C key is not printed by the [Debug](fmt::Debug) implementation.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub struct S3RequestOptions {
+    /// Algorithm of a customer provided encryption key (SSE-C), e.g. `AES256`
+    pub sse_customer_algorithm: Option<String>,
+    /// The base64 encoded customer provided encryption key (SSE-C)
+    pub sse_customer_key: Option<String>,
+    /// The base64 encoded MD5 digest of the customer provided encryption key (SSE-C)
+    pub sse_customer_key_md5: Option<String>,
+    /// Set to `requester` to download from requester pays buckets
+    pub request_payer: Option<String>,
+    /// The account id of the expected owner of the bucket
+    pub expected_bucket_owner: Option<String>,
+    /// Only download if the ETag of the object matches
+    pub if_match: Option<String>,
+    /// Only download if the object was not modified since the given HTTP date
+    pub if_unmodified_since: Option<String>,
+}
+
+impl S3RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Download objects encrypted with a customer provided key (SSE-C)
+    ///
+    /// `key` and `key_md5` must be base64 encoded.
+    pub fn sse_customer_key<A, K, M>(mut self, algorithm: A, key: K, key_md5: M) -> Self
+    where
+        A: Into<String>,
+        K: Into<String>,
+        M: Into<String>,
+    {
+        self.sse_customer_algorithm = Some(algorithm.into());
+        self.sse_customer_key = Some(key.into());
+        self.sse_customer_key_md5 = Some(key_md5.into());
+        self
+    }
+
+    /// Confirm that the requester pays for downloads from requester pays buckets
+    pub fn requester_pays(mut self) -> Self {
+        self.request_payer = Some("requester".to_string());
+        self
+    }
+
+    /// Fail if the bucket is not owned by the given account
+    pub fn expected_bucket_owner<T: Into<String>>(mut self, account_id: T) -> Self {
+        self.expected_bucket_owner = Some(account_id.into());
+        self
+    }
+
+    /// Fail if the ETag of the object does not match
+    pub fn if_match<T: Into<String>>(mut self, etag: T) -> Self {
+        self.if_match = Some(etag.into());
+        self
+    }
+
+    /// Fail if the object was modified since the given HTTP date
+    pub fn if_unmodified_since<T: Into<String>>(mut self, http_date: T) -> Self {
+        self.if_unmodified_since = Some(http_date.into());
+        self
+    }
+
+    /// Returns these options with all options set in `overrides` replaced
+    pub fn overridden_by(&self, overrides: Option<&S3RequestOptions>) -> S3RequestOptions {
+        let overrides = match overrides {
+            Some(overrides) => overrides,
+            None => return self.clone(),
+        };
+
+        let pick =
+            |own: &Option<String>, other: &Option<String>| other.clone().or_else(|| own.clone());
+
+        S3RequestOptions {
+            sse_customer_algorithm: pick(
+                &self.sse_customer_algorithm,
+                &overrides.sse_customer_algorithm,
+            ),
+            sse_customer_key: pick(&self.sse_customer_key, &overrides.sse_customer_key),
+            sse_customer_key_md5: pick(&self.sse_customer_key_md5, &overrides.sse_customer_key_md5),
+            request_payer: pick(&self.request_payer, &overrides.request_payer),
+            expected_bucket_owner: pick(
+                &self.expected_bucket_owner,
+                &overrides.expected_bucket_owner,
+            ),
+            if_match: pick(&self.if_match, &overrides.if_match),
+            if_unmodified_since: pick(&self.if_unmodified_since, &overrides.if_unmodified_since),
+        }
+    }
+}
+
+impl fmt::Debug for S3RequestOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3RequestOptions")
+            .field("sse_customer_algorithm", &self.sse_customer_algorithm)
+            .field(
+                "sse_customer_key",
+                &self.sse_customer_key.as_ref().map(|_| "***"),
+            )
+            .field("sse_customer_key_md5", &self.sse_customer_key_md5)
+            .field("request_payer", &self.request_payer)
+            .field("expected_bucket_owner", &self.expected_bucket_owner)
+            .field("if_match", &self.if_match)
+            .field("if_unmodified_since", &self.if_unmodified_since)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_set_options_only() {
+        let defaults = S3RequestOptions::new()
+            .requester_pays()
+            .expected_bucket_owner("123");
+        let overrides = S3RequestOptions::new()
+            .expected_bucket_owner("456")
+            .if_match("etag");
+
+        let options = defaults.overridden_by(Some(&overrides));
+
+        assert_eq!(options.request_payer.as_deref(), Some("requester"));
+        assert_eq!(options.expected_bucket_owner.as_deref(), Some("456"));
+        assert_eq!(options.if_match.as_deref(), Some("etag"));
+        assert_eq!(defaults.overridden_by(None), defaults);
+    }
+
+    #[test]
+    fn debug_does_not_print_the_key() {
+        let options = S3RequestOptions::new().sse_customer_key("AES256", "secret", "md5");
+
+        let debug = format!("{:?}", options);
+
+        assert!(!debug.contains("secret"), "{}", debug);
+        assert!(debug.contains("***"), "{}", debug);
+    }
+}
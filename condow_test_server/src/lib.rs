@@ -0,0 +1,242 @@
+//! A mock object store for the tests of the condow adapters
+//!
+//! An adapter describes with a [TestAdapter] how its store serves [DATA]
+//! and reports errors. [adapter_tests] generates the tests every adapter
+//! has to pass so that the tests of an adapter only need to cover what is
+//! specific to it, e.g. signing requests.
+use condow_core::{
+    condow_client::{CondowClient, DownloadSpec},
+    config::{Config, RetryConfig},
+    errors::CondowErrorKind,
+    Condow, InclusiveRange,
+};
+use futures::TryStreamExt;
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// The contents of the object served by the mocks
+pub const DATA: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+
+/// Serves [DATA] like object stores serve ranged GET requests
+///
+/// Requests without a range get all of [DATA] with status 200.
+pub struct RangeResponder {
+    range_header: &'static str,
+}
+
+impl RangeResponder {
+    /// Reads the range from the `range` header
+    pub fn new() -> Self {
+        Self::with_header("range")
+    }
+
+    /// Reads the range from the given header, e.g. `x-ms-range`
+    pub fn with_header(range_header: &'static str) -> Self {
+        Self { range_header }
+    }
+}
+
+impl Default for RangeResponder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Respond for RangeResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let range = match request.headers.get(self.range_header) {
+            Some(range) => range.to_str().unwrap().trim_start_matches("bytes="),
+            None => return ResponseTemplate::new(200).set_body_bytes(DATA),
+        };
+
+        let (start, end) = range.split_once('-').unwrap();
+        let start: usize = start.parse().unwrap();
+        let end = end.parse::<usize>().unwrap().min(DATA.len() - 1);
+
+        ResponseTemplate::new(206)
+            .insert_header(
+                "content-range",
+                format!("bytes {}-{}/{}", start, end, DATA.len()),
+            )
+            .set_body_bytes(&DATA[start..=end])
+    }
+}
+
+/// How an adapter and the store it talks to are set up for the shared tests
+pub trait TestAdapter {
+    type Client: CondowClient;
+
+    /// Mocks serving the size and the contents of [DATA]
+    /// at the location returned by [TestAdapter::location]
+    fn object_mocks() -> Vec<Mock>;
+
+    /// Mocks answering every request with the given status
+    /// the way the store reports errors
+    fn error_mocks(status: u16) -> Vec<Mock>;
+
+    /// A client sending its requests to the given server
+    fn client(server: &MockServer) -> Self::Client;
+
+    fn location(server: &MockServer) -> <Self::Client as CondowClient>::Location;
+}
+
+/// A stand-in for the store of the adapter serving [DATA]
+pub async fn object_server<A: TestAdapter>() -> MockServer {
+    let server = MockServer::start().await;
+    mount_object::<A>(&server).await;
+    server
+}
+
+pub async fn mount_object<A: TestAdapter>(server: &MockServer) {
+    for mock in A::object_mocks() {
+        mock.mount(server).await;
+    }
+}
+
+pub async fn server_responding_with<A: TestAdapter>(status: u16) -> MockServer {
+    let server = MockServer::start().await;
+    for mock in A::error_mocks(status) {
+        mock.mount(&server).await;
+    }
+    server
+}
+
+pub async fn get_size<A: TestAdapter>() {
+    let server = object_server::<A>().await;
+
+    let size = A::client(&server)
+        .get_size(A::location(&server))
+        .await
+        .unwrap();
+
+    assert_eq!(size, DATA.len() as u64);
+}
+
+pub async fn download_range<A: TestAdapter>() {
+    let server = object_server::<A>().await;
+
+    let (stream, bytes_hint) = A::client(&server)
+        .download(
+            A::location(&server),
+            DownloadSpec::Range(InclusiveRange(2, 5)),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(bytes_hint.exact(), Some(4));
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), b"cdef");
+}
+
+pub async fn download_concurrently<A: TestAdapter>() {
+    let server = object_server::<A>().await;
+    let condow = Condow::new(
+        A::client(&server),
+        Config::default().part_size_bytes(5).max_concurrency(3),
+    )
+    .unwrap();
+
+    let data = condow
+        .download(A::location(&server), ..)
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, DATA);
+}
+
+/// Throttling and server errors are retryable, all other errors are not
+pub async fn error_kinds<A: TestAdapter>() {
+    let expected = [
+        (404, CondowErrorKind::NotFound),
+        (401, CondowErrorKind::AccessDenied),
+        (403, CondowErrorKind::AccessDenied),
+        (416, CondowErrorKind::InvalidRange),
+        (429, CondowErrorKind::Remote),
+        (500, CondowErrorKind::Remote),
+        (503, CondowErrorKind::Remote),
+        (400, CondowErrorKind::Other),
+    ];
+
+    for (status, kind) in expected {
+        let server = server_responding_with::<A>(status).await;
+        let client = A::client(&server);
+
+        let err = client.get_size(A::location(&server)).await.unwrap_err();
+        assert_eq!(err.kind(), kind, "get_size {}", status);
+
+        let result = client
+            .download(A::location(&server), DownloadSpec::Complete)
+            .await;
+        assert_eq!(
+            result.err().map(|err| err.kind()),
+            Some(kind),
+            "download {}",
+            status
+        );
+    }
+}
+
+/// Each mock answers with 503 once before the requests reach the object
+pub async fn busy_server_is_retried<A: TestAdapter>() {
+    let server = MockServer::start().await;
+    let mut busy = Vec::new();
+    for mock in A::error_mocks(503) {
+        busy.push(mock.up_to_n_times(1).mount_as_scoped(&server).await);
+    }
+    mount_object::<A>(&server).await;
+    let condow = Condow::new(
+        A::client(&server),
+        Config::default().retries(RetryConfig::default().max_attempts(1).initial_delay_ms(0)),
+    )
+    .unwrap();
+
+    let data = condow
+        .download(A::location(&server), 0..10)
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, &DATA[0..10]);
+    let mut n_busy_responses = 0;
+    for guard in busy.iter() {
+        n_busy_responses += guard.received_requests().await.len();
+    }
+    assert!(n_busy_responses > 0, "the server was never busy");
+}
+
+/// Generates the tests every adapter has to pass for the given [TestAdapter]
+///
+/// The tests run on `tokio` which must be a dev-dependency of the adapter.
+#[macro_export]
+macro_rules! adapter_tests {
+    ($adapter:ty) => {
+        #[tokio::test]
+        async fn get_size() {
+            $crate::get_size::<$adapter>().await;
+        }
+
+        #[tokio::test]
+        async fn download_range() {
+            $crate::download_range::<$adapter>().await;
+        }
+
+        #[tokio::test]
+        async fn download_concurrently() {
+            $crate::download_concurrently::<$adapter>().await;
+        }
+
+        #[tokio::test]
+        async fn error_kinds() {
+            $crate::error_kinds::<$adapter>().await;
+        }
+
+        #[tokio::test]
+        async fn busy_server_is_retried() {
+            $crate::busy_server_is_retried::<$adapter>().await;
+        }
+    };
+}
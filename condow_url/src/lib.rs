@@ -0,0 +1,484 @@
+//! # CONcurrent DOWnloads from (pre-signed) URLs
+//!
+//! Download speed from object stores can be significantly improved by
+//! downloading parts of the file concurrently. This crate
+//! does exactly that for plain URLs like pre-signed S3 or GCS URLs
+//! which require no further credentials.
+//!
+//! ```rust, noexec
+//!
+//! use condow_url::*;
+//! use condow_url::config::Config;
+//!
+//! # async {
+//! let client = UrlClient::new();
+//! let condow = client.condow(Config::default()).unwrap();
+//!
+//! let location: UrlLocation = "https://my_bucket.s3.amazonaws.com/my_object?X-Amz-Signature=abc"
+//!     .parse()
+//!     .unwrap();
+//!
+//! let stream = condow.download(location, 23..46).await.unwrap();
+//! let downloaded_bytes: Vec<u8> = stream.into_vec().await.unwrap();
+//! # };
+//! # ()
+//! ```
+//!
+//! Pre-signed URLs are usually only valid for GET requests. Therefore the
+//! size of a BLOB is requested with a GET request for its first byte.
+//!
+//! # Expired URLs
+//!
+//! Requests on expired URLs fail with
+//! [Expired](errors::CondowErrorKind::Expired) which is not retried.
+//! With a [UrlRefresher] the client requests a fresh [UrlLocation] instead
+//! and sends the request again. Fresh locations are used for all following
+//! requests to the expired URL. The number of fresh locations kept is bounded
+//! (see [UrlClient::max_refreshed_locations]).
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Error as AnyError};
+use futures::future::BoxFuture;
+use reqwest::{header, Response, StatusCode};
+
+use condow_core::{
+    condow_client::*,
+    config::Config,
+    errors::{CondowError, CondowErrorKind},
+    streams::{BytesHint, BytesStream},
+};
+use condow_http::{http_err_to_condow_err, response_text, response_to_download};
+
+pub use condow_core::*;
+pub use reqwest::Url;
+
+/// A URL and headers to send with each request to it
+///
+/// The query, which contains the signature of pre-signed URLs,
+/// is neither part of the [Display](fmt::Display) nor of the
+/// [Debug](fmt::Debug) output. Only the names of the headers are printed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct UrlLocation {
+    url: Url,
+    headers: Vec<(String, String)>,
+}
+
+impl UrlLocation {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Send the given header with each request
+    ///
+    /// Invalid headers fail the requests.
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Turn this into the URL and the headers
+    pub fn into_inner(self) -> (Url, Vec<(String, String)>) {
+        (self.url, self.headers)
+    }
+}
+
+impl fmt::Display for UrlLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://", self.url.scheme())?;
+        if let Some(host) = self.url.host_str() {
+            write!(f, "{}", host)?;
+        }
+        if let Some(port) = self.url.port() {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.url.path())
+    }
+}
+
+impl fmt::Debug for UrlLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlLocation")
+            .field("url", &self.to_string())
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl From<Url> for UrlLocation {
+    fn from(url: Url) -> Self {
+        Self::new(url)
+    }
+}
+
+impl FromStr for UrlLocation {
+    type Err = AnyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s).context("not a valid URL")?;
+        Ok(Self::new(url))
+    }
+}
+
+/// Provides a fresh location for a location whose URL has expired
+///
+/// Concurrent requests to an expired URL may each request a fresh location.
+pub trait UrlRefresher: Send + Sync + 'static {
+    fn refresh(
+        &self,
+        expired: &UrlLocation,
+    ) -> BoxFuture<'static, Result<UrlLocation, CondowError>>;
+}
+
+impl<F> UrlRefresher for F
+where
+    F: Fn(&UrlLocation) -> BoxFuture<'static, Result<UrlLocation, CondowError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn refresh(
+        &self,
+        expired: &UrlLocation,
+    ) -> BoxFuture<'static, Result<UrlLocation, CondowError>> {
+        self(expired)
+    }
+}
+
+/// A client for (pre-signed) URLs to implement [CondowClient] on
+///
+/// No credentials are added to the requests. The server must
+/// support ranged GET requests.
+#[derive(Clone)]
+pub struct UrlClient {
+    http: reqwest::Client,
+    refresher: Option<Arc<dyn UrlRefresher>>,
+    max_refreshed_locations: usize,
+    refreshed: Arc<Mutex<RefreshedLocations>>,
+}
+
+impl UrlClient {
+    /// Create a client with a default HTTP client
+    pub fn new() -> Self {
+        Self::from_client(reqwest::Client::new())
+    }
+
+    /// Create a client with the given HTTP client
+    pub fn from_client(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            refresher: None,
+            max_refreshed_locations: 256,
+            refreshed: Default::default(),
+        }
+    }
+
+    /// Request fresh locations for expired URLs from the given [UrlRefresher]
+    pub fn refresher<R: UrlRefresher>(mut self, refresher: R) -> Self {
+        self.refresher = Some(Arc::new(refresher));
+        self
+    }
+
+    /// Set the maximum number of fresh locations kept for expired URLs
+    ///
+    /// The least recently used fresh location is evicted first. A request
+    /// to an expired URL whose fresh location was evicted refreshes it again.
+    ///
+    /// The default is 256. A value of 0 will be treated as 1.
+    pub fn max_refreshed_locations(mut self, max_refreshed_locations: usize) -> Self {
+        self.max_refreshed_locations = max_refreshed_locations.max(1);
+        self
+    }
+
+    /// The number of fresh locations currently kept for expired URLs
+    pub fn n_refreshed_locations(&self) -> usize {
+        self.refreshed.lock().unwrap().locations.len()
+    }
+
+    /// Create a concurrent downloader from this adapter and the given [Config]
+    pub fn condow(self, config: Config) -> Result<Condow<Self>, AnyError> {
+        Condow::new(self, config)
+    }
+
+    /// Sends a GET request and refreshes the location once if it expired
+    async fn get(
+        &self,
+        location: &UrlLocation,
+        range: Option<&str>,
+    ) -> Result<Response, CondowError> {
+        let current = self.current(location);
+
+        let err = match self.get_once(&current, range).await {
+            Ok(response) => return Ok(response),
+            Err(err) => err,
+        };
+
+        let refresher = match self.refresher.as_ref() {
+            Some(refresher) if err.kind() == CondowErrorKind::Expired => refresher,
+            _ => return Err(err),
+        };
+
+        // The fresh location expired as well
+        self.refreshed.lock().unwrap().remove(&location.url);
+
+        let fresh = refresher.refresh(&current).await?;
+        self.refreshed.lock().unwrap().insert(
+            location.url.clone(),
+            fresh.clone(),
+            self.max_refreshed_locations,
+        );
+
+        self.get_once(&fresh, range).await
+    }
+
+    /// The fresh location if the URL of the given location expired before
+    fn current(&self, location: &UrlLocation) -> UrlLocation {
+        self.refreshed
+            .lock()
+            .unwrap()
+            .get(&location.url)
+            .unwrap_or_else(|| location.clone())
+    }
+
+    /// Sends a GET request and fails unless the response is a success
+    ///
+    /// A response to a [FIRST_BYTE] range which is not satisfiable is passed on
+    /// since it contains the size of an empty BLOB.
+    async fn get_once(
+        &self,
+        location: &UrlLocation,
+        range: Option<&str>,
+    ) -> Result<Response, CondowError> {
+        let mut request = self.http.get(location.url.clone());
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+        for (name, value) in location.headers.iter() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await.map_err(http_err_to_condow_err)?;
+
+        let status = response.status();
+        if status.is_success()
+            || status == StatusCode::RANGE_NOT_SATISFIABLE && range == Some(FIRST_BYTE)
+        {
+            Ok(response)
+        } else {
+            Err(response_to_condow_err(response).await)
+        }
+    }
+}
+
+impl Default for UrlClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fresh locations by the URLs which expired with a counter for the least recent use
+#[derive(Default)]
+struct RefreshedLocations {
+    locations: HashMap<Url, (UrlLocation, u64)>,
+    n_accesses: u64,
+}
+
+impl RefreshedLocations {
+    fn get(&mut self, url: &Url) -> Option<UrlLocation> {
+        self.n_accesses += 1;
+        let n_accesses = self.n_accesses;
+        self.locations.get_mut(url).map(|(location, last_used)| {
+            *last_used = n_accesses;
+            location.clone()
+        })
+    }
+
+    fn insert(&mut self, url: Url, location: UrlLocation, max_locations: usize) {
+        self.n_accesses += 1;
+        self.locations.insert(url, (location, self.n_accesses));
+
+        while self.locations.len() > max_locations {
+            let least_recently_used = self
+                .locations
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(url, _)| url.clone());
+            match least_recently_used {
+                Some(url) => self.locations.remove(&url),
+                None => break,
+            };
+        }
+    }
+
+    fn remove(&mut self, url: &Url) {
+        self.locations.remove(url);
+    }
+}
+
+/// The range requested to get the size of a BLOB
+const FIRST_BYTE: &str = "bytes=0-0";
+
+impl CondowClient for UrlClient {
+    type Location = UrlLocation;
+
+    fn get_size(&self, location: Self::Location) -> BoxFuture<'static, Result<u64, CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let response = client
+                .get(&location, Some(FIRST_BYTE))
+                .await
+                .map_err(|err| err.with_location(&location))?;
+
+            let status = response.status();
+            let size = match status {
+                StatusCode::OK => response.content_length(),
+                _ => response
+                    .headers()
+                    .get(header::CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(size_from_content_range),
+            };
+
+            match size {
+                Some(size) => Ok(size),
+                None if status == StatusCode::RANGE_NOT_SATISFIABLE => {
+                    Err(response_to_condow_err(response)
+                        .await
+                        .with_location(&location))
+                }
+                None => Err(CondowError::new_other(
+                    "response did not contain the size of the BLOB",
+                )
+                .with_location(&location)),
+            }
+        };
+
+        Box::pin(f)
+    }
+
+    fn download(
+        &self,
+        location: Self::Location,
+        spec: DownloadSpec,
+    ) -> BoxFuture<'static, Result<(BytesStream, BytesHint), CondowError>> {
+        let client = self.clone();
+        let f = async move {
+            let range = spec.http_range_value();
+            let response = client
+                .get(&location, range.as_deref())
+                .await
+                .map_err(|err| err.with_location(&location))?;
+
+            response_to_download(response, &spec).map_err(|err| err.with_location(&location))
+        };
+
+        Box::pin(f)
+    }
+}
+
+/// The complete length of a `Content-Range` like `bytes 0-0/200` or `bytes */0`
+fn size_from_content_range(content_range: &str) -> Option<u64> {
+    let (_, size) = content_range.trim().rsplit_once('/')?;
+    size.parse().ok()
+}
+
+async fn response_to_condow_err(response: Response) -> CondowError {
+    let status = response.status();
+    let message = response_text(response).await;
+
+    status_to_condow_err(status, message)
+}
+
+/// Detects expired pre-signed URLs before mapping the status like any other adapter
+///
+/// S3 (403 "Request has expired") and GCS (400 "ExpiredToken") report
+/// expired pre-signed URLs in the body of client errors.
+fn status_to_condow_err(status: StatusCode, message: String) -> CondowError {
+    let is_expired = matches!(status.as_u16(), 400 | 401 | 403)
+        && message.to_ascii_lowercase().contains("expired");
+
+    if is_expired {
+        CondowError::new_expired(format!("{} - {}", status, message))
+    } else {
+        condow_http::status_to_condow_err(status, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_hides_query_and_header_values() {
+        let location: UrlLocation =
+            "https://bucket.s3.amazonaws.com/dir/object?X-Amz-Signature=secret"
+                .parse()
+                .unwrap();
+        let location = location.with_header("x-amz-server-side-encryption-customer-key", "key");
+
+        assert_eq!(
+            location.to_string(),
+            "https://bucket.s3.amazonaws.com/dir/object"
+        );
+        let debug = format!("{:?}", location);
+        assert!(!debug.contains("secret"), "{}", debug);
+        assert!(!debug.contains("\"key\""), "{}", debug);
+    }
+
+    #[test]
+    fn size_is_parsed_from_content_range() {
+        assert_eq!(size_from_content_range("bytes 0-0/200"), Some(200));
+        assert_eq!(size_from_content_range("bytes */0"), Some(0));
+        assert_eq!(size_from_content_range("bytes 0-0/*"), None);
+    }
+
+    #[test]
+    fn expired_urls() {
+        let expected = [
+            (
+                403,
+                "<Error><Code>AccessDenied</Code><Message>Request has expired</Message></Error>",
+                CondowErrorKind::Expired,
+            ),
+            (
+                400,
+                "<Error><Code>ExpiredToken</Code></Error>",
+                CondowErrorKind::Expired,
+            ),
+            (
+                403,
+                "<Error><Code>AccessDenied</Code></Error>",
+                CondowErrorKind::AccessDenied,
+            ),
+            (500, "expired", CondowErrorKind::Remote),
+        ];
+
+        for (status, body, kind) in expected {
+            let err = status_to_condow_err(StatusCode::from_u16(status).unwrap(), body.to_string());
+            assert_eq!(err.kind(), kind, "{} {}", status, body);
+        }
+        assert!(!CondowErrorKind::Expired.is_retryable());
+    }
+}
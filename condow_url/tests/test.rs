@@ -0,0 +1,280 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use condow_test_server::{adapter_tests, object_server, RangeResponder, TestAdapter, DATA};
+use condow_url::{
+    condow_client::{CondowClient, DownloadSpec},
+    config::{Config, RetryConfig},
+    errors::{CondowError, CondowErrorKind},
+    InclusiveRange, UrlClient, UrlLocation,
+};
+use futures::{future::BoxFuture, TryStreamExt};
+use wiremock::{
+    matchers::{header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const OBJECT_PATH: &str = "/bucket/dir/object";
+
+const EXPIRED: &str =
+    "<Error><Code>AccessDenied</Code><Message>Request has expired</Message></Error>";
+
+fn location(server: &MockServer, signature: &str) -> UrlLocation {
+    format!(
+        "{}{}?X-Amz-Signature={}",
+        server.uri(),
+        OBJECT_PATH,
+        signature
+    )
+    .parse()
+    .unwrap()
+}
+
+/// A stand-in for an object store serving a single object
+/// at [OBJECT_PATH] for the signature `valid`
+struct PresignedUrls;
+
+impl TestAdapter for PresignedUrls {
+    type Client = UrlClient;
+
+    fn object_mocks() -> Vec<Mock> {
+        vec![Mock::given(method("GET"))
+            .and(path(OBJECT_PATH))
+            .and(query_param("X-Amz-Signature", "valid"))
+            .respond_with(RangeResponder::new())]
+    }
+
+    fn error_mocks(status: u16) -> Vec<Mock> {
+        vec![Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(status).set_body_string("<Error/>"))]
+    }
+
+    fn client(_server: &MockServer) -> UrlClient {
+        UrlClient::new()
+    }
+
+    fn location(server: &MockServer) -> UrlLocation {
+        location(server, "valid")
+    }
+}
+
+adapter_tests!(PresignedUrls);
+
+/// Refreshes to the signature `valid` and counts the refreshes
+fn refresher(
+    server: &MockServer,
+    refreshes: Arc<AtomicUsize>,
+) -> impl Fn(&UrlLocation) -> BoxFuture<'static, Result<UrlLocation, CondowError>> {
+    let fresh = location(server, "valid");
+    move |_expired: &UrlLocation| {
+        refreshes.fetch_add(1, Ordering::SeqCst);
+        let fresh = fresh.clone();
+        Box::pin(async move { Ok(fresh) })
+    }
+}
+
+#[tokio::test]
+async fn get_size_of_empty_blob() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("range", "bytes=0-0"))
+        .respond_with(ResponseTemplate::new(416).insert_header("content-range", "bytes */0"))
+        .mount(&server)
+        .await;
+
+    let size = UrlClient::new()
+        .get_size(location(&server, "valid"))
+        .await
+        .unwrap();
+
+    assert_eq!(size, 0);
+}
+
+#[tokio::test]
+async fn headers_are_sent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(OBJECT_PATH))
+        .and(header(
+            "x-amz-server-side-encryption-customer-algorithm",
+            "AES256",
+        ))
+        .respond_with(RangeResponder::new())
+        .expect(1)
+        .mount(&server)
+        .await;
+    let location = location(&server, "valid")
+        .with_header("x-amz-server-side-encryption-customer-algorithm", "AES256");
+
+    let (stream, _) = UrlClient::new()
+        .download(location, DownloadSpec::Range(InclusiveRange(0, 9)))
+        .await
+        .unwrap();
+
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), &DATA[0..10]);
+}
+
+#[tokio::test]
+async fn signature_is_not_part_of_errors() {
+    // Nothing listens on the port once the listener is dropped
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let location: UrlLocation = format!(
+        "http://{}{}?X-Amz-Signature=secret",
+        listener.local_addr().unwrap(),
+        OBJECT_PATH
+    )
+    .parse()
+    .unwrap();
+    drop(listener);
+    let client = UrlClient::new();
+
+    let err = client.get_size(location.clone()).await.unwrap_err();
+    assert_eq!(err.kind(), CondowErrorKind::Io);
+    let debug = format!("{:?}", err);
+    assert!(!debug.contains("X-Amz-Signature"), "{}", debug);
+
+    let err = client
+        .download(location, DownloadSpec::Complete)
+        .await
+        .err()
+        .unwrap();
+    let debug = format!("{:?}", err);
+    assert!(!debug.contains("X-Amz-Signature"), "{}", debug);
+}
+
+#[tokio::test]
+async fn expired_urls_are_not_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(EXPIRED))
+        .mount(&server)
+        .await;
+    let condow = UrlClient::new()
+        .condow(
+            Config::default().retries(RetryConfig::default().max_attempts(3).initial_delay_ms(0)),
+        )
+        .unwrap();
+
+    let err = condow
+        .download(location(&server, "expired"), 0..10)
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(err.kind(), CondowErrorKind::Expired);
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+}
+
+#[tokio::test]
+async fn expired_urls_are_refreshed_once() {
+    let server = object_server::<PresignedUrls>().await;
+    Mock::given(method("GET"))
+        .and(query_param("X-Amz-Signature", "expired"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(EXPIRED))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let refreshes = Arc::new(AtomicUsize::new(0));
+    let condow = UrlClient::new()
+        .refresher(refresher(&server, Arc::clone(&refreshes)))
+        .condow(Config::default().part_size_bytes(5).max_concurrency(1))
+        .unwrap();
+
+    let data = condow
+        .download(location(&server, "expired"), ..)
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, DATA);
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn expired_fresh_urls_are_refreshed_again() {
+    let server = object_server::<PresignedUrls>().await;
+    Mock::given(method("GET"))
+        .and(query_param("X-Amz-Signature", "expired"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(EXPIRED))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // Valid for a single request
+    Mock::given(method("GET"))
+        .and(query_param("X-Amz-Signature", "fresh"))
+        .respond_with(RangeResponder::new())
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("X-Amz-Signature", "fresh"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(EXPIRED))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let refreshed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let client = UrlClient::new().refresher({
+        let refreshed = Arc::clone(&refreshed);
+        let fresh = [location(&server, "fresh"), location(&server, "valid")];
+        move |expired: &UrlLocation| {
+            let mut refreshed = refreshed.lock().unwrap();
+            refreshed.push(expired.url().clone());
+            let fresh = fresh[refreshed.len() - 1].clone();
+            Box::pin(async move { Ok(fresh) }) as BoxFuture<'static, _>
+        }
+    });
+    let condow = client
+        .clone()
+        .condow(Config::default().part_size_bytes(5).max_concurrency(1))
+        .unwrap();
+
+    let data = condow
+        .download(location(&server, "expired"), ..)
+        .await
+        .unwrap()
+        .into_vec()
+        .await
+        .unwrap();
+
+    assert_eq!(data, DATA);
+    assert_eq!(
+        *refreshed.lock().unwrap(),
+        [
+            location(&server, "expired").url().clone(),
+            location(&server, "fresh").url().clone()
+        ]
+    );
+    assert_eq!(client.n_refreshed_locations(), 1);
+}
+
+#[tokio::test]
+async fn number_of_refreshed_locations_is_bounded() {
+    let server = object_server::<PresignedUrls>().await;
+    for n in 0..4 {
+        Mock::given(method("GET"))
+            .and(query_param("X-Amz-Signature", format!("expired-{}", n)))
+            .respond_with(ResponseTemplate::new(403).set_body_string(EXPIRED))
+            .mount(&server)
+            .await;
+    }
+    let client = UrlClient::new()
+        .refresher(refresher(&server, Default::default()))
+        .max_refreshed_locations(2);
+
+    for n in 0..4 {
+        let size = client
+            .get_size(location(&server, &format!("expired-{}", n)))
+            .await
+            .unwrap();
+        assert_eq!(size, DATA.len() as u64);
+    }
+
+    assert_eq!(client.n_refreshed_locations(), 2);
+}